@@ -0,0 +1,175 @@
+//! Throughput of the interpreter loop on a few small programs. Each program is compiled once outside the
+//! measurement, so only verifying and running the bytecode is timed.
+//!
+//! cargo bench --bench interpreter
+//! cargo bench --bench interpreter --features handler-table
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use oxc_span::SourceType;
+
+use core_engine::bytecode::chunk::Chunk;
+use core_engine::bytecode::opcode;
+use core_engine::compiler::compile;
+use core_engine::compiler::compiler::{Compiler, CompilerReturn, OptimizationLevel};
+use core_engine::context::Context;
+use core_engine::disassembler::Disassembler;
+use core_engine::stack::Stack;
+use core_engine::vm::core::Engine;
+
+// calls and integer arithmetic
+const FIB: &str = "
+function fib(n) {
+  if (n === 0) { return 0; }
+  if (n === 1) { return 1; }
+  return fib(n - 1) + fib(n - 2);
+}
+fib(20);
+";
+
+// the program with its context, compiled against the same builtins every run starts from
+fn compiled(source: &str) -> (Context, Chunk) {
+  let mut ctx = Context::with_builtins();
+  let allocator = oxc_allocator::Allocator::default();
+  let compiled = compile(&allocator, &source.to_string(), &mut ctx).expect("benchmark program compiles");
+  (ctx, compiled.chunk)
+}
+
+// `compiled`, with the chunk rewritten only as far as `level`
+fn compiled_at(source: &str, level: OptimizationLevel) -> (Context, Chunk) {
+  compiled_with(source, level, true)
+}
+
+// `compiled_at`, with the integer opcodes only when `integer_arithmetic` is set
+fn compiled_with(source: &str, level: OptimizationLevel, integer_arithmetic: bool) -> (Context, Chunk) {
+  let mut ctx = Context::with_builtins();
+  let allocator = oxc_allocator::Allocator::default();
+  let source_type = SourceType::default().with_module(true).with_typescript(true);
+  let program = oxc_parser::Parser::new(&allocator, source, source_type).parse().program;
+  let compiled = Compiler::new("main".to_string(), source, &mut ctx)
+    .with_integer_arithmetic(integer_arithmetic)
+    .with_optimization_level(level)
+    .compile_program(&program)
+    .expect("benchmark program compiles");
+  (ctx, compiled.chunk)
+}
+
+// a counting loop adding up its index
+fn loop_sum(count: usize) -> String {
+  format!("let sum = 0;\nlet i = 0;\nwhile (i < {count}) {{ sum = sum + i; i = i + 1; }}\nsum;")
+}
+
+fn string_building(count: usize) -> String {
+  let elements = vec!["\"ab\""; count].join(", ");
+  format!(
+    "let text = \"\";\nfor (const part of [{}]) {{ text = text + part; }}\ntext;",
+    elements
+  )
+}
+
+// a counter bumped `count * count` times by two nested for-of loops, the body is the kind of code
+// superinstructions target: the loop binding is stored and popped, then `counter + 1` is stored and popped
+fn counting_loop(count: usize) -> String {
+  let elements: Vec<String> = (0..count).map(|index| index.to_string()).collect();
+  format!(
+    "let counter = 0;\nconst items = [{}];\nfor (const a of items) {{ for (const b of items) {{ counter = counter + 1; }} }}\ncounter;",
+    elements.join(", ")
+  )
+}
+
+// a function reading the global `step` on every iteration, each read goes through the load's inline cache
+fn global_loads(count: usize) -> String {
+  format!(
+    "let step = 1;\nfunction total() {{ let n = 0; let i = 0; while (i < {count}) {{ n = n + step; i = i + 1; }} return n; }}\ntotal();"
+  )
+}
+
+// reads and writes named properties `count` times, each key is an interned constant compared by pointer
+fn property_access(count: usize) -> String {
+  format!(
+    "const point = {{ x: 1, y: 2, z: 3 }};\nlet total = 0;\nlet i = 0;\nwhile (i < {count}) {{ point.x = point.y + point.z; total = total + point.x; i = i + 1; }}\ntotal;"
+  )
+}
+
+// 1 + 2 + ... + `count` in a counting loop, every variable only ever holds integers
+fn integer_sum(count: usize) -> String {
+  format!("let sum = 0;\nlet i = 1;\nwhile (i <= {count}) {{ sum = sum + i; i = i + 1; }}\nsum;")
+}
+
+fn bench_program(criterion: &mut Criterion, name: &str, source: &str) {
+  criterion.bench_function(name, |bencher| {
+    bencher.iter_batched(
+      || compiled(source),
+      |(mut ctx, chunk)| Engine::bootstrap_chunk(&mut ctx, chunk, false).expect("benchmark program runs"),
+      BatchSize::SmallInput,
+    )
+  });
+}
+
+// instructions one run of `chunk` dispatches
+fn dispatched(mut ctx: Context, chunk: Chunk) -> u64 {
+  let compiled = CompilerReturn { name: "main".to_string(), chunk };
+  let mut stack = Stack::new(64);
+  let mut vm = Engine::new(&mut ctx, &mut stack, &compiled);
+  vm.run().expect("benchmark program runs");
+  vm.consumed_fuel()
+}
+
+// the same program with and without superinstructions. the loop bodies account for nearly all of the
+// instructions dispatched, so the fused chunk has to dispatch fewer of them before either is timed
+fn bench_fusion(criterion: &mut Criterion, name: &str, source: &str) {
+  let (ctx, chunk) = compiled_at(source, OptimizationLevel::Peephole);
+  let unfused = dispatched(ctx, chunk);
+  let (ctx, chunk) = compiled_at(source, OptimizationLevel::Fused);
+  let fused = dispatched(ctx, chunk);
+  assert!(
+    fused < unfused,
+    "{name} dispatches {fused} instructions fused and {unfused} unfused"
+  );
+  for (suffix, level) in [
+    ("unfused", OptimizationLevel::Peephole),
+    ("fused", OptimizationLevel::Fused),
+  ] {
+    criterion.bench_function(&format!("{name}-{suffix}"), |bencher| {
+      bencher.iter_batched(
+        || compiled_at(source, level),
+        |(mut ctx, chunk)| Engine::bootstrap_chunk(&mut ctx, chunk, false).expect("benchmark program runs"),
+        BatchSize::SmallInput,
+      )
+    });
+  }
+}
+
+// the same program with its sums on ADD_INT and on the float ADD. fusion is off, so the integer variant
+// keeps a plain ADD_INT for every `+` instead of folding the constant ones into ADD_CONST
+fn bench_integer_arithmetic(criterion: &mut Criterion, name: &str, source: &str) {
+  for (suffix, integer_arithmetic) in [("int", true), ("float", false)] {
+    let (ctx, chunk) = compiled_with(source, OptimizationLevel::Peephole, integer_arithmetic);
+    let uses_add_int = Disassembler::new(&chunk, name, &ctx)
+      .instructions()
+      .iter()
+      .any(|instruction| instruction.opcode == opcode::OPCODE_ADD_INT as usize);
+    assert_eq!(
+      uses_add_int, integer_arithmetic,
+      "{name}-{suffix} adds with ADD_INT only on the integer path"
+    );
+    criterion.bench_function(&format!("{name}-{suffix}"), |bencher| {
+      bencher.iter_batched(
+        || compiled_with(source, OptimizationLevel::Peephole, integer_arithmetic),
+        |(mut ctx, chunk)| Engine::bootstrap_chunk(&mut ctx, chunk, false).expect("benchmark program runs"),
+        BatchSize::SmallInput,
+      )
+    });
+  }
+}
+
+fn interpreter(criterion: &mut Criterion) {
+  bench_program(criterion, "fib", FIB);
+  bench_program(criterion, "loop-sum", &loop_sum(10_000));
+  bench_program(criterion, "string-building", &string_building(1_000));
+  bench_fusion(criterion, "counting-loop", &counting_loop(100));
+  bench_program(criterion, "global-loads", &global_loads(100_000));
+  bench_program(criterion, "property-access", &property_access(100_000));
+  bench_integer_arithmetic(criterion, "integer-sum", &integer_sum(1_000_000));
+}
+
+criterion_group!(benches, interpreter);
+criterion_main!(benches);
@@ -0,0 +1,235 @@
+//! Methods of array values, `list.push(1)` reads them through GET_PROPERTY bound to the array.
+//! Arrays are shared, so mutating methods are seen through every reference.
+use crate::builtins::relative_index;
+use crate::errors::RuntimeErrorKind;
+use crate::values::native::{NativeFn, NativeResult};
+use crate::values::{ArrayValue, NativeFunctionValue, Value};
+use crate::vm::core::Engine;
+
+// the native behind `array.<name>`, None when arrays have no such method
+pub fn array_method(name: &str) -> Option<NativeFunctionValue> {
+  let (arity, function): (usize, NativeFn) = match name {
+    "push" => (1, push),
+    "pop" => (0, pop),
+    "indexOf" => (1, index_of),
+    "includes" => (1, includes),
+    "join" => (1, join),
+    "slice" => (2, slice),
+    "concat" => (1, concat),
+    "map" => (1, map),
+    "filter" => (1, filter),
+    "forEach" => (1, for_each),
+    "reduce" => (1, reduce),
+    _ => return None,
+  };
+  Some(NativeFunctionValue::new(name.to_string(), arity, function))
+}
+
+// returns the new length
+fn push(_: &mut Engine, this: &Value, arguments: &[Value]) -> NativeResult {
+  let array = this_array(this, "push")?;
+  array.get_elements_mut().extend_from_slice(arguments);
+  Ok(Value::create_number_value(array.len() as f64))
+}
+
+fn pop(_: &mut Engine, this: &Value, _: &[Value]) -> NativeResult {
+  let array = this_array(this, "pop")?;
+  let last = array.get_elements_mut().pop();
+  Ok(last.unwrap_or_else(Value::create_undefined_value))
+}
+
+// strict equality, so NaN is never found
+fn index_of(_: &mut Engine, this: &Value, arguments: &[Value]) -> NativeResult {
+  let array = this_array(this, "indexOf")?;
+  let search = argument(arguments, 0);
+  let elements = array.get_elements();
+  let from = relative_index(arguments.get(1), 0, elements.len());
+  let index = elements
+    .iter()
+    .skip(from)
+    .position(|element| element.strict_equals(&search));
+  Ok(Value::create_number_value(
+    index.map_or(-1.0, |index| (index + from) as f64),
+  ))
+}
+
+// SameValueZero, unlike indexOf it finds NaN
+fn includes(_: &mut Engine, this: &Value, arguments: &[Value]) -> NativeResult {
+  let array = this_array(this, "includes")?;
+  let search = argument(arguments, 0);
+  let elements = array.get_elements();
+  let from = relative_index(arguments.get(1), 0, elements.len());
+  let found = elements
+    .iter()
+    .skip(from)
+    .any(|element| element.strict_equals(&search) || (is_nan(element) && is_nan(&search)));
+  Ok(Value::create_boolean_value(found))
+}
+
+fn join(_: &mut Engine, this: &Value, arguments: &[Value]) -> NativeResult {
+  let array = this_array(this, "join")?;
+  let separator = match arguments.first() {
+    None | Some(Value::Undefined(_)) => ",".to_string(),
+    Some(separator) => separator.to_js_string(),
+  };
+  Ok(Value::create_string_value(array.join(&separator)))
+}
+
+// a shallow copy, negative positions count from the end
+fn slice(_: &mut Engine, this: &Value, arguments: &[Value]) -> NativeResult {
+  let array = this_array(this, "slice")?;
+  let elements = array.get_elements();
+  let start = relative_index(arguments.first(), 0, elements.len());
+  let end = relative_index(arguments.get(1), elements.len(), elements.len());
+  let sliced = if start < end {
+    elements[start..end].to_vec()
+  } else {
+    vec![]
+  };
+  Ok(Value::create_array_value(sliced))
+}
+
+// a new array, array arguments are spread one level and anything else is appended
+fn concat(_: &mut Engine, this: &Value, arguments: &[Value]) -> NativeResult {
+  let array = this_array(this, "concat")?;
+  let mut elements = array.get_elements().clone();
+  for argument in arguments {
+    match argument {
+      Value::Array(other) => elements.extend(other.get_elements().iter().cloned()),
+      _ => elements.push(argument.clone()),
+    }
+  }
+  Ok(Value::create_array_value(elements))
+}
+
+// callbacks get (element, index, array). the length is read once, like the spec does
+fn map(vm: &mut Engine, this: &Value, arguments: &[Value]) -> NativeResult {
+  let array = this_array(this, "map")?;
+  let callback = callback(arguments, "map")?;
+  let mut mapped = Vec::with_capacity(array.len());
+  for index in 0..array.len() {
+    mapped.push(vm.call_value(callback.clone(), &callback_arguments(this, array, index))?);
+  }
+  Ok(Value::create_array_value(mapped))
+}
+
+fn filter(vm: &mut Engine, this: &Value, arguments: &[Value]) -> NativeResult {
+  let array = this_array(this, "filter")?;
+  let callback = callback(arguments, "filter")?;
+  let mut kept = vec![];
+  for index in 0..array.len() {
+    let arguments = callback_arguments(this, array, index);
+    if vm.call_value(callback.clone(), &arguments)?.is_truthy() {
+      kept.push(arguments[0].clone());
+    }
+  }
+  Ok(Value::create_array_value(kept))
+}
+
+fn for_each(vm: &mut Engine, this: &Value, arguments: &[Value]) -> NativeResult {
+  let array = this_array(this, "forEach")?;
+  let callback = callback(arguments, "forEach")?;
+  for index in 0..array.len() {
+    vm.call_value(callback.clone(), &callback_arguments(this, array, index))?;
+  }
+  Ok(Value::create_undefined_value())
+}
+
+// the callback gets (accumulator, element, index, array), without an initial value the first element is one
+fn reduce(vm: &mut Engine, this: &Value, arguments: &[Value]) -> NativeResult {
+  let array = this_array(this, "reduce")?;
+  let callback = callback(arguments, "reduce")?;
+  let len = array.len();
+  let (mut accumulator, start) = match arguments.get(1) {
+    Some(initial) => (initial.clone(), 0),
+    None if len == 0 => {
+      return Err(RuntimeErrorKind::TypeError(
+        "Reduce of empty array with no initial value".to_string(),
+      ));
+    }
+    None => (array.get(0), 1),
+  };
+  for index in start..len {
+    let mut arguments = vec![accumulator];
+    arguments.extend(callback_arguments(this, array, index));
+    accumulator = vm.call_value(callback.clone(), &arguments)?;
+  }
+  Ok(accumulator)
+}
+
+fn callback(arguments: &[Value], method: &str) -> NativeResult {
+  let callback = argument(arguments, 0);
+  match callback {
+    Value::Function(_) | Value::NativeFunction(_) => Ok(callback),
+    _ => {
+      let message = format!(
+        "{} is not a function (in Array.prototype.{})",
+        callback.type_name(),
+        method
+      );
+      Err(RuntimeErrorKind::TypeError(message))
+    }
+  }
+}
+
+fn callback_arguments(this: &Value, array: &ArrayValue, index: usize) -> Vec<Value> {
+  vec![array.get(index), Value::create_number_value(index as f64), this.clone()]
+}
+
+fn this_array<'a>(this: &'a Value, method: &str) -> Result<&'a ArrayValue, RuntimeErrorKind> {
+  match this {
+    Value::Array(array) => Ok(array),
+    _ => Err(RuntimeErrorKind::TypeError(format!(
+      "Array.prototype.{} called on {}",
+      method,
+      this.type_name()
+    ))),
+  }
+}
+
+fn argument(arguments: &[Value], index: usize) -> Value {
+  arguments
+    .get(index)
+    .cloned()
+    .unwrap_or_else(Value::create_undefined_value)
+}
+
+fn is_nan(value: &Value) -> bool {
+  matches!(value, Value::Number(number) if number.get_value().is_nan())
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_support::run;
+  use crate::values::Value;
+
+  #[test]
+  fn push_in_a_loop_then_join() {
+    let source = "let a = []; let i = 0; while (i < 3) { a.push(i); i = i + 1; } a.join(\"-\")";
+    assert_eq!(run(source).to_js_string(), "0-1-2");
+    assert_eq!(run("[1, null, undefined, 2].join()").to_js_string(), "1,,,2");
+  }
+
+  #[test]
+  fn callbacks_run_for_each_element() {
+    assert_eq!(run("[1, 2, 3].map((x) => x * 2).join()").to_js_string(), "2,4,6");
+    assert_eq!(run("[1, 2, 3].filter((x) => x > 1).join()").to_js_string(), "2,3");
+    let source = "let sum = 0; [1, 2, 3].forEach((x) => { sum = sum + x; }); sum";
+    assert_eq!(run(source), Value::create_number_value(6.0));
+    assert_eq!(
+      run("[1, 2, 3].reduce((a, b) => a + b, 10)"),
+      Value::create_number_value(16.0)
+    );
+    assert_eq!(
+      run("[1, 2, 3].reduce((a, b) => a + b)"),
+      Value::create_number_value(6.0)
+    );
+  }
+
+  #[test]
+  fn reducing_an_empty_array_without_an_initial_value_throws() {
+    let source =
+      "let caught = false; try { [].reduce((a, b) => a + b); } catch (e) { caught = e instanceof TypeError; } caught";
+    assert_eq!(run(source), Value::create_boolean_value(true));
+  }
+}
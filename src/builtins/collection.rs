@@ -0,0 +1,243 @@
+//! The `Map` and `Set` globals. Instances keep their entries in an internal slot, compared with SameValueZero
+//! and visited in insertion order, and an own `size` property the methods keep up to date.
+use crate::errors::RuntimeErrorKind;
+use crate::values::collection::{CollectionData, InternalSlot};
+use crate::values::native::{NativeFn, NativeResult};
+use crate::values::{NativeFunctionValue, ObjectValue, Value};
+use crate::vm::core::Engine;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+  Map,
+  Set,
+}
+
+impl Kind {
+  fn name(self) -> &'static str {
+    match self {
+      Kind::Map => "Map",
+      Kind::Set => "Set",
+    }
+  }
+}
+
+pub fn create_map() -> Value {
+  let methods: [(&str, usize, NativeFn); 5] = [
+    ("get", 1, map_get),
+    ("set", 2, map_set),
+    ("has", 1, map_has),
+    ("delete", 1, map_delete),
+    ("forEach", 1, map_for_each),
+  ];
+  create_constructor(Kind::Map, map, &methods)
+}
+
+pub fn create_set() -> Value {
+  let methods: [(&str, usize, NativeFn); 4] = [
+    ("add", 1, set_add),
+    ("has", 1, set_has),
+    ("delete", 1, set_delete),
+    ("forEach", 1, set_for_each),
+  ];
+  create_constructor(Kind::Set, set, &methods)
+}
+
+fn create_constructor(kind: Kind, function: NativeFn, methods: &[(&str, usize, NativeFn)]) -> Value {
+  let prototype = ObjectValue::new();
+  for (name, arity, method) in methods {
+    prototype.set_property(name, Value::create_native_function_value(name, *arity, *method));
+  }
+  let constructor = NativeFunctionValue::constructor(kind.name().to_string(), 0, function, prototype);
+  Value::NativeFunction(constructor)
+}
+
+// new Map(entries), `entries` an optional array of [key, value] arrays
+fn map(_: &mut Engine, this: &Value, arguments: &[Value]) -> NativeResult {
+  let object = this_instance(this, Kind::Map)?;
+  let mut data = CollectionData::new();
+  for entry in initial_values(arguments, Kind::Map)? {
+    let Value::Array(entry) = entry else {
+      let message = format!("Iterator value {} is not an entry object", entry.debug_repr());
+      return Err(RuntimeErrorKind::TypeError(message));
+    };
+    data.set(entry.get(0), entry.get(1));
+  }
+  initialize(&object, InternalSlot::Map(data));
+  Ok(Value::Object(object))
+}
+
+// new Set(values), `values` an optional array
+fn set(_: &mut Engine, this: &Value, arguments: &[Value]) -> NativeResult {
+  let object = this_instance(this, Kind::Set)?;
+  let mut data = CollectionData::new();
+  for value in initial_values(arguments, Kind::Set)? {
+    data.set(value.clone(), value);
+  }
+  initialize(&object, InternalSlot::Set(data));
+  Ok(Value::Object(object))
+}
+
+fn map_get(_: &mut Engine, this: &Value, arguments: &[Value]) -> NativeResult {
+  let key = argument(arguments, 0);
+  let value = with_data(this, Kind::Map, "get", |data| data.get(&key))?;
+  Ok(value.unwrap_or_else(Value::create_undefined_value))
+}
+
+// returns the map, so calls can be chained
+fn map_set(_: &mut Engine, this: &Value, arguments: &[Value]) -> NativeResult {
+  let (key, value) = (argument(arguments, 0), argument(arguments, 1));
+  with_data(this, Kind::Map, "set", |data| data.set(key, value))?;
+  Ok(this.clone())
+}
+
+fn map_has(_: &mut Engine, this: &Value, arguments: &[Value]) -> NativeResult {
+  has(this, arguments, Kind::Map)
+}
+
+fn map_delete(_: &mut Engine, this: &Value, arguments: &[Value]) -> NativeResult {
+  delete(this, arguments, Kind::Map)
+}
+
+fn map_for_each(vm: &mut Engine, this: &Value, arguments: &[Value]) -> NativeResult {
+  for_each(vm, this, arguments, Kind::Map)
+}
+
+// returns the set, so calls can be chained
+fn set_add(_: &mut Engine, this: &Value, arguments: &[Value]) -> NativeResult {
+  let value = argument(arguments, 0);
+  with_data(this, Kind::Set, "add", |data| data.set(value.clone(), value))?;
+  Ok(this.clone())
+}
+
+fn set_has(_: &mut Engine, this: &Value, arguments: &[Value]) -> NativeResult {
+  has(this, arguments, Kind::Set)
+}
+
+fn set_delete(_: &mut Engine, this: &Value, arguments: &[Value]) -> NativeResult {
+  delete(this, arguments, Kind::Set)
+}
+
+fn set_for_each(vm: &mut Engine, this: &Value, arguments: &[Value]) -> NativeResult {
+  for_each(vm, this, arguments, Kind::Set)
+}
+
+fn has(this: &Value, arguments: &[Value], kind: Kind) -> NativeResult {
+  let key = argument(arguments, 0);
+  let found = with_data(this, kind, "has", |data| data.has(&key))?;
+  Ok(Value::create_boolean_value(found))
+}
+
+fn delete(this: &Value, arguments: &[Value], kind: Kind) -> NativeResult {
+  let key = argument(arguments, 0);
+  let deleted = with_data(this, kind, "delete", |data| data.delete(&key))?;
+  Ok(Value::create_boolean_value(deleted))
+}
+
+// callback(value, key, collection) for each entry, a Set passes its value as the key too. entries added by
+// the callback are visited as well, deleted ones that weren't reached yet are not
+fn for_each(vm: &mut Engine, this: &Value, arguments: &[Value], kind: Kind) -> NativeResult {
+  let callback = argument(arguments, 0);
+  if !matches!(callback, Value::Function(_) | Value::NativeFunction(_)) {
+    let message = format!(
+      "{} is not a function (in {}.prototype.forEach)",
+      callback.type_name(),
+      kind.name()
+    );
+    return Err(RuntimeErrorKind::TypeError(message));
+  }
+  let mut position = 0;
+  // the slot is borrowed only to read the next entry, the callback is free to change the collection
+  while let Some((found, key, value)) = with_data(this, kind, "forEach", |data| data.next_entry(position))? {
+    vm.call_value(callback.clone(), &[value, key, this.clone()])?;
+    position = found + 1;
+  }
+  Ok(Value::create_undefined_value())
+}
+
+// runs `action` on the entries of a Map or Set receiver, then refreshes its `size`
+fn with_data<T>(
+  this: &Value,
+  kind: Kind,
+  method: &str,
+  action: impl FnOnce(&mut CollectionData) -> T,
+) -> Result<T, RuntimeErrorKind> {
+  let incompatible = || {
+    let message = format!(
+      "Method {}.prototype.{} called on incompatible receiver {}",
+      kind.name(),
+      method,
+      this.debug_repr()
+    );
+    RuntimeErrorKind::TypeError(message)
+  };
+  let Value::Object(object) = this else {
+    return Err(incompatible());
+  };
+  let (result, size) = match (&mut *object.internal_slot(), kind) {
+    (Some(InternalSlot::Map(data)), Kind::Map) | (Some(InternalSlot::Set(data)), Kind::Set) => {
+      let result = action(data);
+      (result, data.size())
+    }
+    _ => return Err(incompatible()),
+  };
+  object.set_property("size", Value::create_number_value(size as f64));
+  Ok(result)
+}
+
+// only `new` hands the constructor an instance to fill
+fn this_instance(this: &Value, kind: Kind) -> Result<ObjectValue, RuntimeErrorKind> {
+  match this {
+    Value::Object(object) if object.internal_slot().is_none() => Ok(object.clone()),
+    _ => Err(RuntimeErrorKind::TypeError(format!(
+      "Constructor {} requires 'new'",
+      kind.name()
+    ))),
+  }
+}
+
+// the elements of the optional first argument, undefined and null start the collection empty
+fn initial_values(arguments: &[Value], kind: Kind) -> Result<Vec<Value>, RuntimeErrorKind> {
+  match argument(arguments, 0) {
+    Value::Undefined(_) | Value::Null(_) => Ok(Vec::new()),
+    Value::Array(array) => Ok(array.get_elements().clone()),
+    value => Err(RuntimeErrorKind::TypeError(format!(
+      "{} is not iterable (in new {})",
+      value.type_name(),
+      kind.name()
+    ))),
+  }
+}
+
+fn initialize(object: &ObjectValue, slot: InternalSlot) {
+  let size = match &slot {
+    InternalSlot::Map(data) | InternalSlot::Set(data) => data.size(),
+  };
+  object.set_internal_slot(slot);
+  object.set_property("size", Value::create_number_value(size as f64));
+}
+
+fn argument(arguments: &[Value], index: usize) -> Value {
+  arguments
+    .get(index)
+    .cloned()
+    .unwrap_or_else(Value::create_undefined_value)
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_support::run;
+
+  #[test]
+  fn keys_are_compared_with_same_value_zero() {
+    let source = r#"
+      let m = new Map();
+      m.set(NaN, "nan");
+      m.set(0, "zero");
+      m.set("0", "string");
+      [m.get(NaN), m.get(-0), m.get("0"), m.size].join(" ")
+    "#;
+    assert_eq!(run(source).to_js_string(), "nan zero string 3");
+    let source = "let s = new Set([1, 1, NaN, NaN, 0, -0]); [s.size, s.has(NaN), s.has(-0)].join(\" \")";
+    assert_eq!(run(source).to_js_string(), "3 true true");
+  }
+}
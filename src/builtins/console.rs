@@ -0,0 +1,87 @@
+//! The `console` global: log goes to the context's output, error and warn to its error output.
+use std::io::Write;
+
+use crate::errors::RuntimeErrorKind;
+use crate::values::native::NativeResult;
+use crate::values::{ObjectValue, Value};
+use crate::vm::core::Engine;
+
+pub fn create_console() -> Value {
+  let console = ObjectValue::new();
+  console.set_property("log", Value::create_native_function_value("log", 0, log));
+  console.set_property("error", Value::create_native_function_value("error", 0, error));
+  console.set_property("warn", Value::create_native_function_value("warn", 0, warn));
+  Value::Object(console)
+}
+
+fn log(vm: &mut Engine, _: &Value, arguments: &[Value]) -> NativeResult {
+  print(vm.ctx().output(), arguments)
+}
+
+fn error(vm: &mut Engine, _: &Value, arguments: &[Value]) -> NativeResult {
+  print(vm.ctx().error_output(), arguments)
+}
+
+fn warn(vm: &mut Engine, _: &Value, arguments: &[Value]) -> NativeResult {
+  print(vm.ctx().error_output(), arguments)
+}
+
+// the arguments as one line on `writer`
+fn print(writer: &mut dyn Write, arguments: &[Value]) -> NativeResult {
+  match writeln!(writer, "{}", format_arguments(arguments)) {
+    Ok(()) => Ok(Value::create_undefined_value()),
+    Err(error) => Err(RuntimeErrorKind::TypeError(format!(
+      "console could not write: {}",
+      error
+    ))),
+  }
+}
+
+// arguments separated by a space, strings are printed as they are and everything else like the disassembler
+pub fn format_arguments(arguments: &[Value]) -> String {
+  let parts: Vec<String> = arguments
+    .iter()
+    .map(|argument| match argument {
+      Value::String(string) => string.get_value().to_string(),
+      _ => argument.debug_repr(),
+    })
+    .collect();
+  parts.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::context::Context;
+  use crate::test_support::SharedBuffer;
+  use crate::vm::core::Engine;
+
+  // what `source` printed to the output and to the error output
+  fn printed(source: &str) -> (String, String) {
+    let (output, error_output) = (SharedBuffer::default(), SharedBuffer::default());
+    let mut ctx = Context::with_builtins()
+      .with_output(Box::new(output.clone()))
+      .with_error_output(Box::new(error_output.clone()));
+    Engine::bootstrap_repl(&mut ctx, &source.to_string()).unwrap();
+    (output.contents(), error_output.contents())
+  }
+
+  #[test]
+  fn log_prints_its_arguments_to_the_output() {
+    let (output, error_output) = printed("console.log(\"a\", 1, [1, \"b\"], { c: true }); console.log()");
+    assert_eq!(output, "a 1 [ 1, 'b' ] { c: true }\n\n");
+    assert_eq!(error_output, "");
+  }
+
+  #[test]
+  fn error_and_warn_print_to_the_error_output() {
+    let (output, error_output) = printed("console.error(\"bad\", 2); console.warn(\"careful\")");
+    assert_eq!(output, "");
+    assert_eq!(error_output, "bad 2\ncareful\n");
+  }
+
+  #[test]
+  fn log_prints_an_object_inside_itself_as_circular() {
+    let (output, _) = printed("let a = {}; a.self = a; console.log(a)");
+    assert_eq!(output, "{ self: [Circular] }\n");
+  }
+}
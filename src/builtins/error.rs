@@ -0,0 +1,143 @@
+//! The `Error` global and its `TypeError` and `RangeError` subclasses. Instances get a `message` and a `stack`
+//! captured from the engine's calls when they're built, errors the vm raises itself become the same objects.
+use crate::errors::RuntimeErrorKind;
+use crate::values::native::{NativeFn, NativeResult};
+use crate::values::{NativeFunctionValue, ObjectValue, Value};
+use crate::vm::core::Engine;
+
+// the constructors to register as globals, the prototypes of the subclasses inherit from Error.prototype
+pub fn create_error_constructors() -> Vec<(&'static str, Value)> {
+  let error_prototype = ObjectValue::new();
+  error_prototype.set_property("name", Value::create_string_value("Error".to_string()));
+  error_prototype.set_property("message", Value::create_string_value(String::new()));
+  error_prototype.set_property(
+    "toString",
+    Value::create_native_function_value("toString", 0, to_string),
+  );
+  let constructors: [(&str, NativeFn); 3] = [("Error", error), ("TypeError", type_error), ("RangeError", range_error)];
+  constructors
+    .into_iter()
+    .map(|(name, function)| {
+      let prototype = match name {
+        "Error" => error_prototype.clone(),
+        _ => {
+          let prototype = ObjectValue::with_prototype(error_prototype.clone());
+          prototype.set_property("name", Value::create_string_value(name.to_string()));
+          prototype
+        }
+      };
+      let constructor = NativeFunctionValue::constructor(name.to_string(), 1, function, prototype);
+      (name, Value::NativeFunction(constructor))
+    })
+    .collect()
+}
+
+// the object a script sees for an error the vm raised, an instance of the global of the same name.
+// the kinds without a global (ReferenceError, ...) inherit from Error.prototype and carry their own `name`
+pub fn error_object(vm: &mut Engine, kind: &RuntimeErrorKind) -> ObjectValue {
+  let name = kind.name();
+  let object = match global_prototype(vm, name).or_else(|| global_prototype(vm, "Error")) {
+    Some(prototype) => ObjectValue::with_prototype(prototype),
+    None => ObjectValue::new(),
+  };
+  if object.get_property("name").to_js_string() != name {
+    object.set_property("name", Value::create_string_value(name.to_string()));
+  }
+  object.set_property("message", Value::create_string_value(kind.message()));
+  capture_stack(vm, &object);
+  object
+}
+
+fn error(vm: &mut Engine, this: &Value, arguments: &[Value]) -> NativeResult {
+  construct_error(vm, this, arguments, "Error")
+}
+
+fn type_error(vm: &mut Engine, this: &Value, arguments: &[Value]) -> NativeResult {
+  construct_error(vm, this, arguments, "TypeError")
+}
+
+fn range_error(vm: &mut Engine, this: &Value, arguments: &[Value]) -> NativeResult {
+  construct_error(vm, this, arguments, "RangeError")
+}
+
+// `new Error(message)` fills the instance the engine made, `Error(message)` without `new` builds its own
+fn construct_error(vm: &mut Engine, this: &Value, arguments: &[Value], name: &str) -> NativeResult {
+  let object = match this {
+    Value::Object(object) => object.clone(),
+    _ => match global_prototype(vm, name) {
+      Some(prototype) => ObjectValue::with_prototype(prototype),
+      None => ObjectValue::new(),
+    },
+  };
+  // an undefined message leaves the inherited empty one
+  if let Some(message) = arguments
+    .first()
+    .filter(|message| !matches!(message, Value::Undefined(_)))
+  {
+    object.set_property("message", Value::create_string_value(message.to_js_string()));
+  }
+  capture_stack(vm, &object);
+  Ok(Value::Object(object))
+}
+
+// Error.prototype.toString
+fn to_string(_: &mut Engine, this: &Value, _: &[Value]) -> NativeResult {
+  match this {
+    Value::Object(object) => Ok(Value::create_string_value(describe(object))),
+    _ => {
+      let message = format!("Error.prototype.toString called on {}", this.type_name());
+      Err(RuntimeErrorKind::TypeError(message))
+    }
+  }
+}
+
+// `name: message`, an empty part and its colon are left out
+fn describe(object: &ObjectValue) -> String {
+  let name = match object.get_property("name") {
+    Value::Undefined(_) => "Error".to_string(),
+    name => name.to_js_string(),
+  };
+  let message = match object.get_property("message") {
+    Value::Undefined(_) => String::new(),
+    message => message.to_js_string(),
+  };
+  match (name.is_empty(), message.is_empty()) {
+    (_, true) => name,
+    (true, false) => message,
+    (false, false) => format!("{}: {}", name, message),
+  }
+}
+
+// `stack` is the description followed by one `at` line per active call, like the uncaught error report
+fn capture_stack(vm: &mut Engine, object: &ObjectValue) {
+  let mut stack = describe(object);
+  for frame in vm.stack_trace() {
+    stack.push_str("\n    at ");
+    stack.push_str(&frame);
+  }
+  object.set_property("stack", Value::create_string_value(stack));
+}
+
+fn global_prototype(vm: &mut Engine, name: &str) -> Option<ObjectValue> {
+  match vm.ctx().get_global(name) {
+    Some(Value::NativeFunction(constructor)) => constructor.get_prototype(),
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_support::run;
+
+  #[test]
+  fn errors_carry_a_message_and_a_stack() {
+    let source = r#"function make() { return new Error("boom"); } let e = make(); e.message"#;
+    assert_eq!(run(source).to_js_string(), "boom");
+    let source = r#"function make() { return new Error("boom"); } let e = make(); e.stack"#;
+    let stack = run(source).to_js_string();
+    assert!(stack.starts_with("Error: boom\n    at make"), "{stack}");
+    let source =
+      r#"let e = new TypeError("bad"); [e instanceof TypeError, e instanceof Error, e.toString()].join(" ")"#;
+    assert_eq!(run(source).to_js_string(), "true true TypeError: bad");
+  }
+}
@@ -0,0 +1,423 @@
+//! The `JSON` global: `parse` builds engine values from json text, `stringify` writes them back.
+use crate::errors::RuntimeErrorKind;
+use crate::values::native::NativeResult;
+use crate::values::{ObjectValue, Value};
+use crate::vm::core::Engine;
+
+pub fn create_json() -> Value {
+  let json = ObjectValue::new();
+  json.set_property("parse", Value::create_native_function_value("parse", 2, parse));
+  json.set_property(
+    "stringify",
+    Value::create_native_function_value("stringify", 3, stringify),
+  );
+  Value::Object(json)
+}
+
+fn parse(_: &mut Engine, _: &Value, arguments: &[Value]) -> NativeResult {
+  let text = arguments.first().map_or("undefined".to_string(), Value::to_js_string);
+  let mut parser = Parser { chars: text.chars().collect(), position: 0 };
+  parser.skip_whitespace();
+  let value = parser.parse_value()?;
+  parser.skip_whitespace();
+  if parser.position < parser.chars.len() {
+    return Err(parser.unexpected());
+  }
+  Ok(value)
+}
+
+fn stringify(_: &mut Engine, _: &Value, arguments: &[Value]) -> NativeResult {
+  let value = arguments.first().cloned().unwrap_or_else(Value::create_undefined_value);
+  let mut seen = vec![];
+  match serialize(&value, &mut seen)? {
+    Some(json) => Ok(Value::create_string_value(json)),
+    None => Ok(Value::create_undefined_value()),
+  }
+}
+
+// None for values json can't represent (undefined, functions, symbols), the caller decides what to do
+fn serialize(value: &Value, seen: &mut Vec<Value>) -> Result<Option<String>, RuntimeErrorKind> {
+  let json = match value {
+    Value::Undefined(_) | Value::Function(_) | Value::NativeFunction(_) | Value::Symbol(_) => return Ok(None),
+    Value::Null(_) => "null".to_string(),
+    Value::Boolean(boolean) => boolean.get_value().to_string(),
+    Value::Number(number) if number.get_value().is_finite() => number.to_js_string(),
+    Value::Number(_) => "null".to_string(),
+    Value::String(string) => quote(string.get_value()),
+    Value::BigInt(_) => {
+      return Err(RuntimeErrorKind::TypeError(
+        "Do not know how to serialize a BigInt".to_string(),
+      ))
+    }
+    Value::Array(array) => {
+      enter(value, seen)?;
+      let mut elements = vec![];
+      for element in array.get_elements().clone().iter() {
+        // holes in the output would shift the indices, so unrepresentable elements become null
+        elements.push(serialize(element, seen)?.unwrap_or_else(|| "null".to_string()));
+      }
+      seen.pop();
+      format!("[{}]", elements.join(","))
+    }
+    Value::Object(object) => {
+      enter(value, seen)?;
+      let mut properties = vec![];
+      for (key, property) in object.get_properties() {
+        // undefined and function properties are left out
+        if let Some(json) = serialize(&property, seen)? {
+          properties.push(format!("{}:{}", quote(&key), json));
+        }
+      }
+      seen.pop();
+      format!("{{{}}}", properties.join(","))
+    }
+    // a regexp has no enumerable own properties
+    Value::RegExp(_) => "{}".to_string(),
+  };
+  Ok(Some(json))
+}
+
+// tracks the containers being written, meeting one again means the structure is circular
+fn enter(value: &Value, seen: &mut Vec<Value>) -> Result<(), RuntimeErrorKind> {
+  if seen.iter().any(|ancestor| ancestor.strict_equals(value)) {
+    return Err(RuntimeErrorKind::TypeError(
+      "Converting circular structure to JSON".to_string(),
+    ));
+  }
+  seen.push(value.clone());
+  Ok(())
+}
+
+fn quote(value: &str) -> String {
+  let mut quoted = String::with_capacity(value.len() + 2);
+  quoted.push('"');
+  for c in value.chars() {
+    match c {
+      '"' => quoted.push_str("\\\""),
+      '\\' => quoted.push_str("\\\\"),
+      '\n' => quoted.push_str("\\n"),
+      '\r' => quoted.push_str("\\r"),
+      '\t' => quoted.push_str("\\t"),
+      '\u{08}' => quoted.push_str("\\b"),
+      '\u{0C}' => quoted.push_str("\\f"),
+      c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+      c => quoted.push(c),
+    }
+  }
+  quoted.push('"');
+  quoted
+}
+
+struct Parser {
+  chars: Vec<char>,
+  position: usize,
+}
+
+impl Parser {
+  fn parse_value(&mut self) -> NativeResult {
+    match self.peek() {
+      Some('{') => self.parse_object(),
+      Some('[') => self.parse_array(),
+      Some('"') => Ok(Value::create_string_value(self.parse_string()?)),
+      Some('t') => self.parse_literal("true", Value::create_boolean_value(true)),
+      Some('f') => self.parse_literal("false", Value::create_boolean_value(false)),
+      Some('n') => self.parse_literal("null", Value::create_null_value()),
+      Some(c) if c == '-' || c.is_ascii_digit() => self.parse_number(),
+      _ => Err(self.unexpected()),
+    }
+  }
+
+  fn parse_object(&mut self) -> NativeResult {
+    let object = ObjectValue::new();
+    self.position += 1;
+    self.skip_whitespace();
+    if self.eat('}') {
+      return Ok(Value::Object(object));
+    }
+    loop {
+      self.skip_whitespace();
+      if self.peek() != Some('"') {
+        return Err(self.unexpected());
+      }
+      let key = self.parse_string()?;
+      self.skip_whitespace();
+      if !self.eat(':') {
+        return Err(self.unexpected());
+      }
+      self.skip_whitespace();
+      let value = self.parse_value()?;
+      object.set_property(&key, value);
+      self.skip_whitespace();
+      if self.eat('}') {
+        return Ok(Value::Object(object));
+      }
+      if !self.eat(',') {
+        return Err(self.unexpected());
+      }
+    }
+  }
+
+  fn parse_array(&mut self) -> NativeResult {
+    let mut elements = vec![];
+    self.position += 1;
+    self.skip_whitespace();
+    if self.eat(']') {
+      return Ok(Value::create_array_value(elements));
+    }
+    loop {
+      self.skip_whitespace();
+      elements.push(self.parse_value()?);
+      self.skip_whitespace();
+      if self.eat(']') {
+        return Ok(Value::create_array_value(elements));
+      }
+      if !self.eat(',') {
+        return Err(self.unexpected());
+      }
+    }
+  }
+
+  fn parse_string(&mut self) -> Result<String, RuntimeErrorKind> {
+    self.position += 1;
+    let mut string = String::new();
+    loop {
+      let Some(c) = self.next() else {
+        return Err(self.syntax_error("Unterminated string in JSON"));
+      };
+      match c {
+        '"' => return Ok(string),
+        '\\' => match self.next() {
+          Some('"') => string.push('"'),
+          Some('\\') => string.push('\\'),
+          Some('/') => string.push('/'),
+          Some('b') => string.push('\u{08}'),
+          Some('f') => string.push('\u{0C}'),
+          Some('n') => string.push('\n'),
+          Some('r') => string.push('\r'),
+          Some('t') => string.push('\t'),
+          Some('u') => string.push(self.parse_unicode_escape()?),
+          _ => return Err(self.syntax_error("Bad escaped character in JSON")),
+        },
+        c if (c as u32) < 0x20 => return Err(self.syntax_error("Bad control character in string literal in JSON")),
+        c => string.push(c),
+      }
+    }
+  }
+
+  // `\uXXXX`, a surrogate pair spans two escapes, a lone surrogate becomes U+FFFD
+  fn parse_unicode_escape(&mut self) -> Result<char, RuntimeErrorKind> {
+    let high = self.parse_hex4()?;
+    if (0xD800..0xDC00).contains(&high) && self.chars[self.position..].starts_with(&['\\', 'u']) {
+      let position = self.position;
+      self.position += 2;
+      let low = self.parse_hex4()?;
+      if (0xDC00..0xE000).contains(&low) {
+        let code = 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00);
+        return Ok(char::from_u32(code).unwrap_or('\u{FFFD}'));
+      }
+      self.position = position;
+    }
+    Ok(char::from_u32(high).unwrap_or('\u{FFFD}'))
+  }
+
+  fn parse_hex4(&mut self) -> Result<u32, RuntimeErrorKind> {
+    let mut code = 0;
+    for _ in 0..4 {
+      let digit = self.next().and_then(|c| c.to_digit(16));
+      let Some(digit) = digit else {
+        return Err(self.syntax_error("Bad Unicode escape in JSON"));
+      };
+      code = code * 16 + digit;
+    }
+    Ok(code)
+  }
+
+  // -?(0|[1-9][0-9]*)(\.[0-9]+)?([eE][+-]?[0-9]+)?
+  fn parse_number(&mut self) -> NativeResult {
+    let start = self.position;
+    self.eat('-');
+    if !self.eat('0') && self.digits() == 0 {
+      return Err(self.unexpected());
+    }
+    if self.eat('.') && self.digits() == 0 {
+      return Err(self.unexpected());
+    }
+    if self.eat('e') || self.eat('E') {
+      if !self.eat('+') {
+        self.eat('-');
+      }
+      if self.digits() == 0 {
+        return Err(self.unexpected());
+      }
+    }
+    let text: String = self.chars[start..self.position].iter().collect();
+    Ok(Value::create_number_value(text.parse().unwrap_or(f64::NAN)))
+  }
+
+  fn digits(&mut self) -> usize {
+    let start = self.position;
+    while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+      self.position += 1;
+    }
+    self.position - start
+  }
+
+  fn parse_literal(&mut self, literal: &str, value: Value) -> NativeResult {
+    for expected in literal.chars() {
+      if self.peek() != Some(expected) {
+        return Err(self.unexpected());
+      }
+      self.position += 1;
+    }
+    Ok(value)
+  }
+
+  fn skip_whitespace(&mut self) {
+    while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+      self.position += 1;
+    }
+  }
+
+  fn peek(&self) -> Option<char> {
+    self.chars.get(self.position).copied()
+  }
+
+  fn next(&mut self) -> Option<char> {
+    let c = self.peek()?;
+    self.position += 1;
+    Some(c)
+  }
+
+  fn eat(&mut self, expected: char) -> bool {
+    if self.peek() == Some(expected) {
+      self.position += 1;
+      return true;
+    }
+    false
+  }
+
+  fn unexpected(&self) -> RuntimeErrorKind {
+    match self.peek() {
+      Some(c) => self.syntax_error(&format!(
+        "Unexpected token '{}' in JSON at position {}",
+        c, self.position
+      )),
+      None => self.syntax_error("Unexpected end of JSON input"),
+    }
+  }
+
+  fn syntax_error(&self, message: &str) -> RuntimeErrorKind {
+    RuntimeErrorKind::SyntaxError(message.to_string())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::errors::RuntimeErrorKind;
+  use crate::test_support::{run, runtime_error};
+
+  fn stringified(source: &str) -> String {
+    run(&format!("JSON.stringify({})", source))
+      .as_string()
+      .unwrap()
+      .to_string()
+  }
+
+  // the message of the SyntaxError parsing `text` throws
+  fn parse_error(text: &str) -> String {
+    match runtime_error(&format!("JSON.parse({})", text)).kind {
+      RuntimeErrorKind::SyntaxError(message) => message,
+      kind => panic!("expected a SyntaxError for {}, got {:?}", text, kind),
+    }
+  }
+
+  #[test]
+  fn parse_builds_nested_values() {
+    let source = r#"let v = JSON.parse('{"a": [1, {"b": null}], "c": {"d": [true, false]}}'); v"#;
+    assert_eq!(
+      run(source).debug_repr(),
+      "{ a: [ 1, { b: null } ], c: { d: [ true, false ] } }"
+    );
+    assert_eq!(run("JSON.parse(' [ [], {} ] ')").debug_repr(), "[ [], {} ]");
+  }
+
+  #[test]
+  fn parse_reads_escapes() {
+    let source = r#"JSON.parse('"a\\"b\\\\c\\/d\\n\\t\\u0041\\ud83d\\ude00"')"#;
+    assert_eq!(run(source).as_string(), Some("a\"b\\c/d\n\tA\u{1F600}"));
+  }
+
+  #[test]
+  fn parse_reads_numbers() {
+    let cases = [
+      ("0", 0.0),
+      ("-12", -12.0),
+      ("3.25", 3.25),
+      ("1e3", 1000.0),
+      ("-2.5E-2", -0.025),
+    ];
+    for (text, expected) in cases {
+      assert_eq!(
+        run(&format!("JSON.parse('{}')", text)).as_number(),
+        Some(expected),
+        "{}",
+        text
+      );
+    }
+  }
+
+  #[test]
+  fn parse_rejects_bad_input() {
+    assert_eq!(parse_error("'{\"a\" 1}'"), "Unexpected token '1' in JSON at position 5");
+    assert_eq!(parse_error("'[1,]'"), "Unexpected token ']' in JSON at position 3");
+    assert_eq!(parse_error("'01'"), "Unexpected token '1' in JSON at position 1");
+    assert_eq!(parse_error("'\"abc'"), "Unterminated string in JSON");
+    assert_eq!(parse_error(r#"'"\\x"'"#), "Bad escaped character in JSON");
+    assert_eq!(parse_error("''"), "Unexpected end of JSON input");
+    assert_eq!(parse_error("undefined"), "Unexpected token 'u' in JSON at position 0");
+  }
+
+  #[test]
+  fn parse_rejects_trailing_data() {
+    assert_eq!(parse_error("'1 2'"), "Unexpected token '2' in JSON at position 2");
+    assert_eq!(parse_error("'{} x'"), "Unexpected token 'x' in JSON at position 3");
+    assert_eq!(run("JSON.parse(' true \\n')").to_js_string(), "true");
+  }
+
+  #[test]
+  fn stringify_keeps_insertion_order() {
+    assert_eq!(
+      stringified("{ b: 1, a: [1, \"x\"], c: { z: null, y: true } }"),
+      r#"{"b":1,"a":[1,"x"],"c":{"z":null,"y":true}}"#
+    );
+    assert_eq!(
+      run("let o = {}; o.z = 1; o.a = 2; o.z = 3; JSON.stringify(o)").as_string(),
+      Some(r#"{"z":3,"a":2}"#)
+    );
+  }
+
+  #[test]
+  fn stringify_omits_undefined_and_functions() {
+    assert_eq!(stringified("{ a: undefined, b: () => 1, c: 2 }"), r#"{"c":2}"#);
+    // inside arrays they keep their index as null
+    assert_eq!(stringified("[undefined, () => 1, NaN, 1]"), "[null,null,null,1]");
+    assert_eq!(run("JSON.stringify(undefined)").type_name(), "undefined");
+    assert_eq!(stringified("\"a\\nb\""), r#""a\nb""#);
+  }
+
+  #[test]
+  fn stringify_throws_on_circular_input() {
+    for source in [
+      "let a = {}; a.self = a; JSON.stringify(a)",
+      "let b = [1]; b.push({ b: b }); JSON.stringify(b)",
+    ] {
+      let error = runtime_error(source);
+      assert!(matches!(error.kind, RuntimeErrorKind::TypeError(_)), "{:?}", error.kind);
+    }
+    // shared but not circular is fine
+    assert_eq!(
+      run("let c = { n: 1 }; JSON.stringify([c, c])").as_string(),
+      Some(r#"[{"n":1},{"n":1}]"#)
+    );
+  }
+}
@@ -0,0 +1,197 @@
+//! The `Math` global. Every function converts its arguments with ToNumber, missing ones are NaN.
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+use crate::values::native::{NativeFn, NativeResult};
+use crate::values::{ObjectValue, Value};
+use crate::vm::core::Engine;
+
+pub fn create_math() -> Value {
+  let math = ObjectValue::new();
+  math.set_property("PI", Value::create_number_value(std::f64::consts::PI));
+  math.set_property("E", Value::create_number_value(std::f64::consts::E));
+  let functions: [(&str, usize, NativeFn); 9] = [
+    ("floor", 1, floor),
+    ("ceil", 1, ceil),
+    ("round", 1, round),
+    ("abs", 1, abs),
+    ("sqrt", 1, sqrt),
+    ("pow", 2, pow),
+    ("min", 2, min),
+    ("max", 2, max),
+    ("random", 0, random),
+  ];
+  for (name, arity, function) in functions {
+    math.set_property(name, Value::create_native_function_value(name, arity, function));
+  }
+  Value::Object(math)
+}
+
+fn argument(arguments: &[Value], index: usize) -> f64 {
+  arguments.get(index).map_or(f64::NAN, Value::to_number)
+}
+
+fn number(value: f64) -> NativeResult {
+  Ok(Value::create_number_value(value))
+}
+
+fn floor(_: &mut Engine, _: &Value, arguments: &[Value]) -> NativeResult {
+  number(argument(arguments, 0).floor())
+}
+
+fn ceil(_: &mut Engine, _: &Value, arguments: &[Value]) -> NativeResult {
+  number(argument(arguments, 0).ceil())
+}
+
+// halves round toward +Infinity (Math.round(-2.5) is -2), and -0.5 <= x < 0 rounds to -0
+fn round(_: &mut Engine, _: &Value, arguments: &[Value]) -> NativeResult {
+  let value = argument(arguments, 0);
+  if !value.is_finite() || value == 0.0 {
+    return number(value);
+  }
+  if (-0.5..0.0).contains(&value) {
+    return number(-0.0);
+  }
+  // not floor(x + 0.5), that rounds 0.49999999999999994 up
+  let floor = value.floor();
+  number(if value - floor >= 0.5 { floor + 1.0 } else { floor })
+}
+
+fn abs(_: &mut Engine, _: &Value, arguments: &[Value]) -> NativeResult {
+  number(argument(arguments, 0).abs())
+}
+
+fn sqrt(_: &mut Engine, _: &Value, arguments: &[Value]) -> NativeResult {
+  number(argument(arguments, 0).sqrt())
+}
+
+fn pow(_: &mut Engine, _: &Value, arguments: &[Value]) -> NativeResult {
+  let (base, exponent) = (argument(arguments, 0), argument(arguments, 1));
+  // js says 1 ** NaN and (-1) ** ±Infinity are NaN, rust's powf says 1
+  if base.abs() == 1.0 && !exponent.is_finite() {
+    return number(f64::NAN);
+  }
+  number(base.powf(exponent))
+}
+
+// variadic, Infinity with no arguments, NaN wins and -0 is smaller than +0
+fn min(_: &mut Engine, _: &Value, arguments: &[Value]) -> NativeResult {
+  let mut result = f64::INFINITY;
+  for value in arguments.iter().map(Value::to_number) {
+    if value.is_nan() {
+      return number(f64::NAN);
+    }
+    if value < result || (value == 0.0 && result == 0.0 && value.is_sign_negative()) {
+      result = value;
+    }
+  }
+  number(result)
+}
+
+// variadic, -Infinity with no arguments, NaN wins and +0 is bigger than -0
+fn max(_: &mut Engine, _: &Value, arguments: &[Value]) -> NativeResult {
+  let mut result = f64::NEG_INFINITY;
+  for value in arguments.iter().map(Value::to_number) {
+    if value.is_nan() {
+      return number(f64::NAN);
+    }
+    if value > result || (value == 0.0 && result == 0.0 && value.is_sign_positive()) {
+      result = value;
+    }
+  }
+  number(result)
+}
+
+thread_local! {
+  // xorshift64 state, seeded from the std hasher's per-process random keys
+  static SEED: Cell<u64> = Cell::new(RandomState::new().build_hasher().finish() | 1);
+}
+
+// uniform in [0, 1)
+fn random(_: &mut Engine, _: &Value, _: &[Value]) -> NativeResult {
+  let bits = SEED.with(|seed| {
+    let mut state = seed.get();
+    state ^= state << 13;
+    state ^= state >> 7;
+    state ^= state << 17;
+    seed.set(state);
+    state
+  });
+  // the top 53 bits fill the mantissa
+  number((bits >> 11) as f64 / (1u64 << 53) as f64)
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_support::run;
+
+  fn number(source: &str) -> f64 {
+    run(source).as_number().unwrap()
+  }
+
+  #[test]
+  fn nan_goes_through_every_function() {
+    for call in [
+      "floor(NaN)",
+      "ceil(\"a\")",
+      "round()",
+      "abs(undefined)",
+      "sqrt(-1)",
+      "pow(NaN, 2)",
+      "pow(1, NaN)",
+    ] {
+      assert!(number(&format!("Math.{}", call)).is_nan(), "Math.{}", call);
+    }
+    assert!(number("Math.min(1, NaN, 0 - Infinity)").is_nan());
+    assert!(number("Math.max(NaN, Infinity)").is_nan());
+    assert_eq!(number("Math.pow(NaN, 0)"), 1.0);
+  }
+
+  #[test]
+  fn negative_zero_keeps_its_sign() {
+    for call in [
+      "round(-0.5)",
+      "round(-0.2)",
+      "round(-0)",
+      "ceil(-0.5)",
+      "floor(-0)",
+      "min(0, -0)",
+      "min(-0, 0)",
+    ] {
+      let result = number(&format!("Math.{}", call));
+      assert!(
+        result == 0.0 && result.is_sign_negative(),
+        "Math.{} is {}",
+        call,
+        result
+      );
+    }
+    assert!(number("Math.max(-0, 0)").is_sign_positive());
+    assert!(number("Math.abs(-0)").is_sign_positive());
+  }
+
+  #[test]
+  fn min_and_max_without_arguments_are_the_infinities() {
+    assert_eq!(number("Math.max()"), f64::NEG_INFINITY);
+    assert_eq!(number("Math.min()"), f64::INFINITY);
+    assert_eq!(number("Math.max(3, \"7\", 5)"), 7.0);
+    assert_eq!(number("Math.min(3, 1, 2)"), 1.0);
+  }
+
+  #[test]
+  fn halves_round_toward_positive_infinity() {
+    assert_eq!(number("Math.round(2.5)"), 3.0);
+    assert_eq!(number("Math.round(-2.5)"), -2.0);
+    assert_eq!(number("Math.round(-2.6)"), -3.0);
+    assert_eq!(number("Math.round(0.49999999999999994)"), 0.0);
+  }
+
+  #[test]
+  fn random_stays_in_the_unit_interval() {
+    for _ in 0..100 {
+      let value = number("Math.random()");
+      assert!((0.0..1.0).contains(&value));
+    }
+  }
+}
@@ -0,0 +1,53 @@
+//! Host functions every script can call, registered by `Context::with_builtins`.
+use crate::errors::RuntimeErrorKind;
+use crate::values::native::NativeResult;
+use crate::values::Value;
+use crate::vm::core::Engine;
+
+pub mod array;
+pub mod collection;
+pub mod console;
+pub mod error;
+pub mod json;
+pub mod math;
+pub mod object;
+pub mod string;
+
+// stands in for Symbol.iterator until symbols exist: an object with a callable `@@iterator` is iterable
+pub const ITERATOR_KEY: &str = "@@iterator";
+
+// print(...values): writes the values separated by a space, followed by a newline
+pub fn print(vm: &mut Engine, _: &Value, arguments: &[Value]) -> NativeResult {
+  let line = arguments
+    .iter()
+    .map(|value| value.to_string())
+    .collect::<Vec<_>>()
+    .join(" ");
+  match writeln!(vm.ctx().output(), "{}", line) {
+    Ok(()) => Ok(Value::create_undefined_value()),
+    Err(error) => Err(RuntimeErrorKind::TypeError(format!("print could not write: {}", error))),
+  }
+}
+
+// ToIntegerOrInfinity of an optional argument, a missing one is 0
+pub fn to_integer(value: Option<&Value>) -> f64 {
+  let number = value.map_or(0.0, Value::to_number);
+  if number.is_nan() {
+    return 0.0;
+  }
+  number.trunc()
+}
+
+// a position for slice-like methods: negative counts back from `len`, the result is clamped to 0..=len
+// and a missing or undefined argument is `default`
+pub fn relative_index(value: Option<&Value>, default: usize, len: usize) -> usize {
+  if matches!(value, None | Some(Value::Undefined(_))) {
+    return default;
+  }
+  let index = to_integer(value);
+  if index < 0.0 {
+    (len as f64 + index).max(0.0) as usize
+  } else {
+    index.min(len as f64) as usize
+  }
+}
@@ -0,0 +1,123 @@
+//! The `Object` global, only its static helpers for now: keys, values, entries and assign.
+use crate::errors::RuntimeErrorKind;
+use crate::values::native::NativeResult;
+use crate::values::{ObjectValue, Value};
+use crate::vm::core::Engine;
+
+pub fn create_object() -> Value {
+  let object = ObjectValue::new();
+  object.set_property("keys", Value::create_native_function_value("keys", 1, keys));
+  object.set_property("values", Value::create_native_function_value("values", 1, values));
+  object.set_property("entries", Value::create_native_function_value("entries", 1, entries));
+  object.set_property("assign", Value::create_native_function_value("assign", 2, assign));
+  Value::Object(object)
+}
+
+fn keys(_: &mut Engine, _: &Value, arguments: &[Value]) -> NativeResult {
+  let keys = own_properties(arguments.first(), "keys")?
+    .into_iter()
+    .map(|(key, _)| Value::create_string_value(key))
+    .collect();
+  Ok(Value::create_array_value(keys))
+}
+
+fn values(_: &mut Engine, _: &Value, arguments: &[Value]) -> NativeResult {
+  let values = own_properties(arguments.first(), "values")?
+    .into_iter()
+    .map(|(_, value)| value)
+    .collect();
+  Ok(Value::create_array_value(values))
+}
+
+// [key, value] pairs
+fn entries(_: &mut Engine, _: &Value, arguments: &[Value]) -> NativeResult {
+  let entries = own_properties(arguments.first(), "entries")?
+    .into_iter()
+    .map(|(key, value)| Value::create_array_value(vec![Value::create_string_value(key), value]))
+    .collect();
+  Ok(Value::create_array_value(entries))
+}
+
+// copies every source into the target left to right and returns the target itself.
+// null and undefined sources are skipped
+fn assign(_: &mut Engine, _: &Value, arguments: &[Value]) -> NativeResult {
+  let target = match arguments.first() {
+    Some(Value::Object(target)) => target,
+    // primitives would be boxed into wrapper objects, the engine has none yet
+    _ => {
+      return Err(RuntimeErrorKind::TypeError(
+        "Object.assign target must be an object".to_string(),
+      ))
+    }
+  };
+  for source in arguments.iter().skip(1) {
+    if matches!(source, Value::Undefined(_) | Value::Null(_)) {
+      continue;
+    }
+    for (key, value) in own_properties(Some(source), "assign")? {
+      target.set_property(&key, value);
+    }
+  }
+  Ok(arguments[0].clone())
+}
+
+// the keys `for (key in value)` visits, as strings. null and undefined have none instead of throwing.
+// inherited properties are left out, class methods live on the prototype and aren't enumerable in js
+pub fn enumerable_keys(value: &Value) -> Vec<Value> {
+  let Ok(properties) = own_properties(Some(value), "keys") else {
+    return vec![];
+  };
+  properties
+    .into_iter()
+    .map(|(key, _)| Value::create_string_value(key))
+    .collect()
+}
+
+// the enumerable own properties in insertion order, arrays and strings list their indices
+fn own_properties(value: Option<&Value>, method: &str) -> Result<Vec<(String, Value)>, RuntimeErrorKind> {
+  let properties = match value {
+    Some(Value::Object(object)) => object.get_properties(),
+    Some(Value::Array(array)) => indexed(array.get_elements().iter().cloned()),
+    Some(Value::String(string)) => {
+      let units: Vec<u16> = string.get_value().encode_utf16().collect();
+      indexed(
+        units
+          .iter()
+          .map(|unit| Value::create_string_value(String::from_utf16_lossy(&[*unit]))),
+      )
+    }
+    None | Some(Value::Undefined(_)) | Some(Value::Null(_)) => {
+      let message = format!("Object.{} cannot convert undefined or null to object", method);
+      return Err(RuntimeErrorKind::TypeError(message));
+    }
+    Some(_) => vec![],
+  };
+  Ok(properties)
+}
+
+fn indexed(values: impl Iterator<Item = Value>) -> Vec<(String, Value)> {
+  values
+    .enumerate()
+    .map(|(index, value)| (index.to_string(), value))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_support::run;
+
+  #[test]
+  fn object_statics_read_own_properties_in_order() {
+    let source = r#"
+      let o = { b: 1, a: 2 };
+      [Object.keys(o).join(), Object.values(o).join(), Object.entries(o).join(";")].join(" ")
+    "#;
+    assert_eq!(run(source).to_js_string(), "b,a 1,2 b,1;a,2");
+    let source = r#"
+      let target = { a: 0 };
+      let result = Object.assign(target, { b: 2 }, { a: 1 });
+      [result === target, target.a, target.b, Object.keys(target).join()].join(" ")
+    "#;
+    assert_eq!(run(source).to_js_string(), "true 1 2 a,b");
+  }
+}
@@ -0,0 +1,120 @@
+//! Methods of string values, `"abc".slice(1)` reads them through GET_PROPERTY bound to the string.
+//! Positions and lengths count UTF-16 code units like js does, not bytes or chars.
+use crate::builtins::{relative_index, to_integer};
+use crate::values::native::{NativeFn, NativeResult};
+use crate::values::{NativeFunctionValue, Value};
+use crate::vm::core::Engine;
+
+// the native behind `string.<name>`, None when strings have no such method
+pub fn string_method(name: &str) -> Option<NativeFunctionValue> {
+  let (arity, function): (usize, NativeFn) = match name {
+    "indexOf" => (1, index_of),
+    "includes" => (1, includes),
+    "slice" => (2, slice),
+    "split" => (2, split),
+    "toUpperCase" => (0, to_upper_case),
+    "toLowerCase" => (0, to_lower_case),
+    _ => return None,
+  };
+  Some(NativeFunctionValue::new(name.to_string(), arity, function))
+}
+
+// `string[index]`, a one code unit string or undefined past the end
+pub fn char_at(string: &str, index: usize) -> Value {
+  match string.encode_utf16().nth(index) {
+    Some(unit) => Value::create_string_value(String::from_utf16_lossy(&[unit])),
+    None => Value::create_undefined_value(),
+  }
+}
+
+fn index_of(_: &mut Engine, this: &Value, arguments: &[Value]) -> NativeResult {
+  let units = code_units(this);
+  let search = code_units(&argument(arguments, 0));
+  let from = to_integer(arguments.get(1)).clamp(0.0, units.len() as f64) as usize;
+  let index = find(&units, &search, from).map_or(-1.0, |index| index as f64);
+  Ok(Value::create_number_value(index))
+}
+
+fn includes(_: &mut Engine, this: &Value, arguments: &[Value]) -> NativeResult {
+  let units = code_units(this);
+  let search = code_units(&argument(arguments, 0));
+  let from = to_integer(arguments.get(1)).clamp(0.0, units.len() as f64) as usize;
+  Ok(Value::create_boolean_value(find(&units, &search, from).is_some()))
+}
+
+// negative positions count from the end, an end before the start is an empty string
+fn slice(_: &mut Engine, this: &Value, arguments: &[Value]) -> NativeResult {
+  let units = code_units(this);
+  let start = relative_index(arguments.first(), 0, units.len());
+  let end = relative_index(arguments.get(1), units.len(), units.len());
+  let sliced = if start < end { &units[start..end] } else { &[][..] };
+  Ok(Value::create_string_value(String::from_utf16_lossy(sliced)))
+}
+
+// no separator keeps the whole string, an empty one splits every code unit
+fn split(_: &mut Engine, this: &Value, arguments: &[Value]) -> NativeResult {
+  let string = this.to_js_string();
+  let limit = match arguments.get(1) {
+    None | Some(Value::Undefined(_)) => usize::MAX,
+    Some(limit) => limit.to_number() as u32 as usize,
+  };
+  let parts: Vec<String> = match arguments.first() {
+    None | Some(Value::Undefined(_)) => vec![string],
+    Some(separator) => {
+      let separator = separator.to_js_string();
+      if separator.is_empty() {
+        let units: Vec<u16> = string.encode_utf16().collect();
+        units.iter().map(|unit| String::from_utf16_lossy(&[*unit])).collect()
+      } else {
+        string.split(separator.as_str()).map(str::to_string).collect()
+      }
+    }
+  };
+  let elements = parts.into_iter().take(limit).map(Value::create_string_value).collect();
+  Ok(Value::create_array_value(elements))
+}
+
+fn to_upper_case(_: &mut Engine, this: &Value, _: &[Value]) -> NativeResult {
+  Ok(Value::create_string_value(this.to_js_string().to_uppercase()))
+}
+
+fn to_lower_case(_: &mut Engine, this: &Value, _: &[Value]) -> NativeResult {
+  Ok(Value::create_string_value(this.to_js_string().to_lowercase()))
+}
+
+fn argument(arguments: &[Value], index: usize) -> Value {
+  arguments
+    .get(index)
+    .cloned()
+    .unwrap_or_else(Value::create_undefined_value)
+}
+
+fn code_units(value: &Value) -> Vec<u16> {
+  value.to_js_string().encode_utf16().collect()
+}
+
+fn find(units: &[u16], search: &[u16], from: usize) -> Option<usize> {
+  if search.is_empty() {
+    return Some(from);
+  }
+  units[from..]
+    .windows(search.len())
+    .position(|window| window == search)
+    .map(|index| index + from)
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_support::run;
+  use crate::values::Value;
+
+  #[test]
+  fn strings_count_utf16_code_units() {
+    // the emoji is a surrogate pair
+    assert_eq!(run(r#""héllo😀".length"#), Value::create_number_value(7.0));
+    assert_eq!(run(r#""hello".slice(-3, -1)"#).to_js_string(), "ll");
+    assert_eq!(run(r#""hello".slice(3, 1)"#).to_js_string(), "");
+    assert_eq!(run(r#"let s = "hi"; s[0]"#).to_js_string(), "h");
+    assert!(run(r#""hi"[5]"#).is_undefined());
+  }
+}
@@ -0,0 +1,76 @@
+//! A unit of compiled bytecode: the program and every function body get their own chunk.
+use crate::context::GlobalCache;
+use crate::utils::{decode_operand, line_for_offset, opcode_operands};
+use crate::values::shape::InlineCache;
+use crate::values::Value;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Chunk {
+  pub code: Vec<u8>,
+  pub constants: Vec<Value>,
+  // (bytecode offset, source line), only pushed when the line changes
+  pub lines: Vec<(usize, u32)>,
+  // names of the globals the code indexes into, in context order. only the program chunk fills it,
+  // a loaded chunk needs it to rebuild the context its LOAD/SET operands point at
+  pub globals: Vec<String>,
+  // deepest the operand stack gets in this chunk, not counting the arguments of a call
+  pub max_stack: usize,
+  // names of the local variables by stack slot, debug metadata for the disassembler. the vm reserves one
+  // slot per name after the arguments when the chunk starts running
+  pub locals: Vec<String>,
+  // filled by the vm as it runs, never serialized
+  pub inline_cache: InlineCache,
+  pub global_cache: GlobalCache,
+}
+
+impl Chunk {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  // appends a byte that was generated from `line`
+  pub fn write(&mut self, byte: u8, line: u32) {
+    let is_new_line = self.lines.last().is_none_or(|&(_, last)| last != line);
+    if is_new_line {
+      self.lines.push((self.code.len(), line));
+    }
+    self.code.push(byte);
+  }
+
+  // appends to the pool without dedup, the compiler decides when a slot can be shared
+  pub fn add_constant(&mut self, value: Value) -> usize {
+    self.constants.push(value);
+    self.constants.len() - 1
+  }
+
+  pub fn len(&self) -> usize {
+    self.code.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.code.is_empty()
+  }
+
+  pub fn read_u8(&self, offset: usize) -> Option<usize> {
+    decode_operand(&self.code, offset, 1)
+  }
+
+  pub fn read_u16(&self, offset: usize) -> Option<usize> {
+    decode_operand(&self.code, offset, 2)
+  }
+
+  pub fn read_u32(&self, offset: usize) -> Option<usize> {
+    decode_operand(&self.code, offset, 4)
+  }
+
+  // operand of the instruction at `offset`, whatever its width
+  pub fn read_operand(&self, offset: usize) -> Option<usize> {
+    let width = opcode_operands(*self.code.get(offset)?);
+    decode_operand(&self.code, offset + 1, width)
+  }
+
+  // source line of the instruction at `offset`
+  pub fn line_for_offset(&self, offset: usize) -> Option<u32> {
+    line_for_offset(&self.lines, offset)
+  }
+}
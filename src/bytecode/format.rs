@@ -0,0 +1,6 @@
+//! The serialized bytecode header. The file format stores operands as varints, the in-memory code doesn't.
+
+// every serialized program starts with these bytes
+pub const BYTECODE_MAGIC: &[u8; 4] = b"CENG";
+// bump whenever the opcode numbering or the serialized layout changes, stale caches are rejected
+pub const BYTECODE_VERSION: u16 = 21;
@@ -1 +1,5 @@
+pub mod chunk;
+pub mod format;
 pub mod opcode;
+mod serializer;
+pub mod verifier;
@@ -1,33 +1,82 @@
 //! The Engine bytecode.
 #![allow(dead_code)]
 
-/// Opcodes for Engine bytecode instructions.
-pub const OPCODE_HALF: usize = 0x00; // Stop the program
-pub const OPCODE_CONST: usize = 0x01; // Push a constant onto the stack
-pub const OPCODE_ADD: usize = 0x02; // Add two values
-pub const OPCODE_SUB: usize = 0x03; // Subtract two values
-pub const OPCODE_MUL: usize = 0x04; // Multiply two values
-pub const OPCODE_DIV: usize = 0x05; // Divide two values
-pub const OPCODE_MOD: usize = 0x06; // Modulo two values
-pub const OPCODE_NEG: usize = 0x07; // Negate a value
-pub const OPCODE_NOT: usize = 0x08; // Logical not
-pub const OPCODE_AND: usize = 0x09; // Logical and
-pub const OPCODE_OR: usize = 0x0A; // Logical or
-pub const OPCODE_XOR: usize = 0x0B; // Logical xor
-pub const OPCODE_SHL: usize = 0x0C; // Shift left
-pub const OPCODE_SHR: usize = 0x0D; // Shift right
-pub const OPCODE_LT: usize = 0x0E; // Less than
-pub const OPCODE_LE: usize = 0x0F; // Less than or equal
-pub const OPCODE_GT: usize = 0x10; // Greater than
-pub const OPCODE_GE: usize = 0x11; // Greater than or equal
-pub const OPCODE_EQ: usize = 0x12; // Equal (===)
-pub const OPCODE_WEAK_EQ: usize = 0x13; // Weak equal (==)
-pub const OPCODE_NE: usize = 0x14; // Not equal (!=)
-pub const OPCODE_JUMP: usize = 0x15; // Jump to an instruction
-pub const OPCODE_JUMP_IF_FALSE: usize = 0x16; // Jump to an instruction if a value is false
-pub const OPCODE_LOAD_GLOBAL_SCOPE: usize = 0x17; // Load a global variable
-pub const OPCODE_SET_GLOBAL_SCOPE: usize = 0x18; // Store a global variable
-pub const OPCODE_LOAD_LOCAL_SCOPE: usize = 0x19; // Load a local variable
-pub const OPCODE_SET_LOCAL_SCOPE: usize = 0x1A; // Store a local variable
-pub const OPCODE_POP: usize = 0x1B; // Pop a value from the stack
-pub const OPCODE_SCOPE_EXIT: usize = 0x1C; // Exit the current scope
+/// Largest index a narrow (one byte) operand can encode, bigger ones use the `_LONG` form of the opcode.
+pub const MAX_NARROW_OPERAND: usize = u8::MAX as usize;
+
+/// Largest constant pool an `OPCODE_CONST_LONG` operand can address (32 bits).
+pub const MAX_CONSTANTS: usize = u32::MAX as usize;
+
+/// Opcodes for Engine bytecode instructions, one byte each.
+/// Operands follow the opcode little-endian, their width is given by `utils::opcode_operands`.
+pub const OPCODE_HALT: u8 = 0x00; // Stop the program
+pub const OPCODE_CONST: u8 = 0x01; // Push a constant onto the stack
+pub const OPCODE_ADD: u8 = 0x02; // Add two values
+pub const OPCODE_SUB: u8 = 0x03; // Subtract two values
+pub const OPCODE_MUL: u8 = 0x04; // Multiply two values
+pub const OPCODE_DIV: u8 = 0x05; // Divide two values
+pub const OPCODE_MOD: u8 = 0x06; // Modulo two values
+pub const OPCODE_NEG: u8 = 0x07; // Negate a value
+pub const OPCODE_NOT: u8 = 0x08; // Logical not
+pub const OPCODE_AND: u8 = 0x09; // Logical and
+pub const OPCODE_OR: u8 = 0x0A; // Logical or
+pub const OPCODE_XOR: u8 = 0x0B; // Logical xor
+pub const OPCODE_SHL: u8 = 0x0C; // Shift left
+pub const OPCODE_SHR: u8 = 0x0D; // Shift right
+pub const OPCODE_LT: u8 = 0x0E; // Less than
+pub const OPCODE_LE: u8 = 0x0F; // Less than or equal
+pub const OPCODE_GT: u8 = 0x10; // Greater than
+pub const OPCODE_GE: u8 = 0x11; // Greater than or equal
+pub const OPCODE_EQ: u8 = 0x12; // Equal (===)
+pub const OPCODE_WEAK_EQ: u8 = 0x13; // Weak equal (==)
+pub const OPCODE_NE: u8 = 0x14; // Not equal (!=)
+pub const OPCODE_JUMP: u8 = 0x15; // Jump by a signed distance from the next instruction
+pub const OPCODE_JUMP_IF_FALSE: u8 = 0x16; // Jump by a signed distance if a value is false
+pub const OPCODE_LOAD_GLOBAL_SCOPE: u8 = 0x17; // Load a global variable
+pub const OPCODE_SET_GLOBAL_SCOPE: u8 = 0x18; // Store a global variable
+pub const OPCODE_GET_LOCAL: u8 = 0x19; // Push the local variable in a stack slot of the current frame
+pub const OPCODE_SET_LOCAL: u8 = 0x1A; // Store into the local variable in a stack slot of the current frame
+pub const OPCODE_POP: u8 = 0x1B; // Pop a value from the stack
+pub const OPCODE_SCOPE_EXIT: u8 = 0x1C; // Exit the current scope
+pub const OPCODE_LOAD_THIS: u8 = 0x1D; // Push the receiver of the current call
+pub const OPCODE_CALL: u8 = 0x1E; // Call a function with the given number of arguments
+pub const OPCODE_RETURN: u8 = 0x1F; // Return from the current function
+pub const OPCODE_LOAD_ARGUMENT: u8 = 0x20; // Load an argument of the current call
+pub const OPCODE_SET_ARGUMENT: u8 = 0x21; // Store an argument of the current call
+pub const OPCODE_CONST_LONG: u8 = 0x22; // Push a constant whose index needs 32 bits
+pub const OPCODE_LOAD_GLOBAL_SCOPE_LONG: u8 = 0x23; // Load a global variable whose index needs 32 bits
+pub const OPCODE_SET_GLOBAL_SCOPE_LONG: u8 = 0x24; // Store a global variable whose index needs 32 bits
+pub const OPCODE_NEW_ARRAY: u8 = 0x25; // Collect the given number of values into a new array
+pub const OPCODE_GET_PROPERTY: u8 = 0x26; // Read the property named by a string constant
+pub const OPCODE_GET_ELEMENT: u8 = 0x27; // Read the property whose key is on the stack
+pub const OPCODE_NEW_OBJECT: u8 = 0x28; // Push a new empty object
+pub const OPCODE_INIT_PROPERTY: u8 = 0x29; // Define a property of the object below the value
+pub const OPCODE_NEW: u8 = 0x2A; // Call a constructor with the given number of arguments and a fresh `this`
+pub const OPCODE_SET_PROPERTY: u8 = 0x2B; // Store to the property named by a string constant
+pub const OPCODE_SET_ELEMENT: u8 = 0x2C; // Store to the property whose key is on the stack
+pub const OPCODE_DUP: u8 = 0x2D; // Push a copy of the top of the stack
+pub const OPCODE_CALL_METHOD: u8 = 0x2E; // Call with the value below the callee as `this`
+pub const OPCODE_INSTANCEOF: u8 = 0x2F; // Whether a constructor's prototype is on a value's prototype chain
+pub const OPCODE_ITERATE: u8 = 0x30; // Start iterating a value for for-of, pushing the state ITERATE_NEXT reads
+pub const OPCODE_ITERATE_NEXT: u8 = 0x31; // Push the next element of an iteration, or jump once there's none
+pub const OPCODE_ENUMERATE: u8 = 0x32; // Replace a value with the array of keys for-in visits
+pub const OPCODE_SWAP: u8 = 0x33; // Exchange the two values on top of the stack
+pub const OPCODE_ADD_INT: u8 = 0x34; // Add operands expected to be integers, falling back to ADD
+pub const OPCODE_SUB_INT: u8 = 0x35; // Subtract operands expected to be integers, falling back to SUB
+pub const OPCODE_MUL_INT: u8 = 0x36; // Multiply operands expected to be integers, falling back to MUL
+
+// superinstructions, each one does the work of a pair the peephole pass fused
+pub const OPCODE_ADD_CONST: u8 = 0x37; // Add a constant to the top of the stack (CONST + ADD_INT)
+pub const OPCODE_SET_LOCAL_POP: u8 = 0x38; // Store into a local variable and pop the value (SET_LOCAL + POP)
+pub const OPCODE_SET_GLOBAL_POP: u8 = 0x39; // Store a global variable and pop the value (SET_GLOBAL_SCOPE + POP)
+pub const OPCODE_THROW: u8 = 0x3A; // Pop a value and throw it
+pub const OPCODE_SETUP_TRY: u8 = 0x3B; // Catch errors at a signed distance from the next instruction until POP_TRY
+pub const OPCODE_POP_TRY: u8 = 0x3C; // Leave the innermost try block
+pub const OPCODE_LT_CONST: u8 = 0x3D; // Whether the top of the stack is less than a constant (CONST + LT)
+
+/// Highest opcode in use, any byte above it is not an instruction.
+pub const LAST_OPCODE: u8 = OPCODE_LT_CONST;
+
+/// The old, misspelled name of `OPCODE_HALT`.
+#[deprecated(note = "renamed to `OPCODE_HALT`")]
+pub const OPCODE_HALF: u8 = OPCODE_HALT;
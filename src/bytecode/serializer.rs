@@ -0,0 +1,389 @@
+use crate::bytecode::chunk::Chunk;
+use crate::bytecode::format::{BYTECODE_MAGIC, BYTECODE_VERSION};
+use crate::errors::DecodeError;
+use crate::utils::{decode_operand, encode_operand, is_jump, opcode_operands};
+use crate::values::{FunctionValue, Value};
+
+// constant tags, one per `Value` variant
+const TAG_UNDEFINED: u8 = 0x00;
+const TAG_NULL: u8 = 0x01;
+const TAG_BOOLEAN: u8 = 0x02;
+const TAG_STRING: u8 = 0x03;
+const TAG_SYMBOL: u8 = 0x04;
+const TAG_NUMBER: u8 = 0x05;
+const TAG_BIGINT: u8 = 0x06;
+const TAG_OBJECT: u8 = 0x07;
+const TAG_FUNCTION: u8 = 0x08;
+const TAG_REGEXP: u8 = 0x09;
+
+impl Chunk {
+  // layout: magic, version (u16 le), then the chunk: global names, constants (tagged), code, the line table,
+  // the max stack depth and the local names. lengths and offsets are LEB128 varints. the code is the instruction count
+  // followed by each opcode byte and its operand as a varint (zigzag for jump distances), loading widens
+  // them back to the fixed operand widths the compiler and vm work with, so offsets stay valid.
+  // varint operands only exist in this file format: in memory a chunk's code keeps the fixed width
+  // `opcode_operands` gives each opcode, which is what the vm and the disassembler decode.
+  pub fn serialize(&self) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    buffer.extend_from_slice(BYTECODE_MAGIC);
+    buffer.extend_from_slice(&BYTECODE_VERSION.to_le_bytes());
+    write_chunk(&mut buffer, self);
+    buffer
+  }
+
+  pub fn deserialize(data: &[u8]) -> Result<Chunk, DecodeError> {
+    let mut reader = Reader { data, position: 0 };
+    if reader.read_bytes(BYTECODE_MAGIC.len())? != BYTECODE_MAGIC {
+      return Err(DecodeError::InvalidMagic);
+    }
+    let version = u16::from_le_bytes(reader.read_bytes(2)?.try_into().unwrap());
+    if version != BYTECODE_VERSION {
+      return Err(DecodeError::VersionMismatch { found: version, expected: BYTECODE_VERSION });
+    }
+    let chunk = reader.read_chunk()?;
+    if reader.position != data.len() {
+      return Err(DecodeError::TrailingBytes(data.len() - reader.position));
+    }
+    Ok(chunk)
+  }
+}
+
+struct Reader<'a> {
+  data: &'a [u8],
+  position: usize,
+}
+
+impl<'a> Reader<'a> {
+  fn read_byte(&mut self) -> Result<u8, DecodeError> {
+    let byte = *self.data.get(self.position).ok_or(DecodeError::UnexpectedEnd)?;
+    self.position += 1;
+    Ok(byte)
+  }
+
+  fn read_bytes(&mut self, count: usize) -> Result<&'a [u8], DecodeError> {
+    let end = self.position.checked_add(count).ok_or(DecodeError::UnexpectedEnd)?;
+    let bytes = self.data.get(self.position..end).ok_or(DecodeError::UnexpectedEnd)?;
+    self.position = end;
+    Ok(bytes)
+  }
+
+  fn read_varint(&mut self) -> Result<usize, DecodeError> {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+      if shift >= usize::BITS {
+        return Err(DecodeError::InvalidVarint);
+      }
+      let byte = self.read_byte()?;
+      value |= ((byte & 0x7F) as usize) << shift;
+      if byte & 0x80 == 0 {
+        return Ok(value);
+      }
+      shift += 7;
+    }
+  }
+
+  fn read_string(&mut self) -> Result<String, DecodeError> {
+    let len = self.read_varint()?;
+    let bytes = self.read_bytes(len)?;
+    String::from_utf8(bytes.to_vec()).map_err(|_| DecodeError::InvalidUtf8)
+  }
+
+  fn read_chunk(&mut self) -> Result<Chunk, DecodeError> {
+    let globals_len = self.read_varint()?;
+    let mut globals = Vec::new();
+    for _ in 0..globals_len {
+      globals.push(self.read_string()?);
+    }
+    let constants_len = self.read_varint()?;
+    let mut constants = Vec::new();
+    for _ in 0..constants_len {
+      constants.push(self.read_constant()?);
+    }
+    let code = self.read_code()?;
+    let lines_len = self.read_varint()?;
+    let mut lines = Vec::new();
+    for _ in 0..lines_len {
+      let offset = self.read_varint()?;
+      let line = u32::try_from(self.read_varint()?).map_err(|_| DecodeError::InvalidVarint)?;
+      lines.push((offset, line));
+    }
+    let max_stack = self.read_varint()?;
+    let locals_len = self.read_varint()?;
+    let mut locals = Vec::new();
+    for _ in 0..locals_len {
+      locals.push(self.read_string()?);
+    }
+    Ok(Chunk { code, constants, lines, globals, max_stack, locals, ..Chunk::new() })
+  }
+
+  fn read_code(&mut self) -> Result<Vec<u8>, DecodeError> {
+    let count = self.read_varint()?;
+    let mut code = Vec::new();
+    for _ in 0..count {
+      let opcode = self.read_byte()?;
+      code.push(opcode);
+      let width = opcode_operands(opcode);
+      if width == 0 {
+        continue;
+      }
+      let mut operand = self.read_varint()?;
+      // jump distances are signed, zigzag keeps short backward jumps short
+      if is_jump(opcode) {
+        let zigzag = u16::try_from(operand).map_err(|_| DecodeError::InvalidVarint)?;
+        operand = ((zigzag >> 1) as i16 ^ -((zigzag & 1) as i16)) as u16 as usize;
+      }
+      // an operand wider than its slot would be silently truncated
+      if width < usize::BITS as usize / 8 && operand >> (width * 8) != 0 {
+        return Err(DecodeError::InvalidVarint);
+      }
+      let start = code.len();
+      code.resize(start + width, 0);
+      encode_operand(&mut code, start, width, operand);
+    }
+    Ok(code)
+  }
+
+  fn read_constant(&mut self) -> Result<Value, DecodeError> {
+    let tag = self.read_byte()?;
+    match tag {
+      TAG_UNDEFINED => Ok(Value::create_undefined_value()),
+      TAG_NULL => Ok(Value::create_null_value()),
+      TAG_BOOLEAN => Ok(Value::create_boolean_value(self.read_byte()? != 0)),
+      TAG_STRING => Ok(Value::create_string_value(self.read_string()?)),
+      TAG_SYMBOL => Ok(Value::create_symbol_value(self.read_string()?)),
+      TAG_NUMBER => {
+        let bytes = self.read_bytes(8)?;
+        Ok(Value::create_number_value(f64::from_le_bytes(
+          bytes.try_into().unwrap(),
+        )))
+      }
+      TAG_BIGINT => {
+        let bytes = self.read_bytes(16)?;
+        Ok(Value::create_bigint_value(i128::from_le_bytes(
+          bytes.try_into().unwrap(),
+        )))
+      }
+      TAG_OBJECT => Ok(Value::create_object_value()),
+      TAG_FUNCTION => {
+        let name = self.read_string()?;
+        let arity = self.read_varint()?;
+        let is_arrow = self.read_byte()? != 0;
+        let chunk = self.read_chunk()?;
+        let function = FunctionValue::new(name, arity, is_arrow, chunk);
+        Ok(Value::create_function_value(function))
+      }
+      TAG_REGEXP => {
+        let pattern = self.read_string()?;
+        let flags = self.read_string()?;
+        Ok(Value::create_regexp_value(&pattern, &flags))
+      }
+      _ => Err(DecodeError::InvalidTag(tag)),
+    }
+  }
+}
+
+fn write_constant(buffer: &mut Vec<u8>, value: &Value) {
+  match value {
+    Value::Undefined(_) => buffer.push(TAG_UNDEFINED),
+    Value::Null(_) => buffer.push(TAG_NULL),
+    Value::Boolean(boolean) => {
+      buffer.push(TAG_BOOLEAN);
+      buffer.push(boolean.get_value() as u8);
+    }
+    Value::String(string) => {
+      buffer.push(TAG_STRING);
+      write_string(buffer, string.get_value());
+    }
+    Value::Symbol(symbol) => {
+      buffer.push(TAG_SYMBOL);
+      write_string(buffer, symbol.get_value());
+    }
+    Value::Number(number) => {
+      buffer.push(TAG_NUMBER);
+      buffer.extend_from_slice(&number.get_value().to_le_bytes());
+    }
+    Value::BigInt(bigint) => {
+      buffer.push(TAG_BIGINT);
+      buffer.extend_from_slice(&bigint.get_value().to_le_bytes());
+    }
+    Value::Object(_) => buffer.push(TAG_OBJECT),
+    // natives are globals the host registers, the compiler never puts them in a pool
+    Value::NativeFunction(function) => {
+      unreachable!(
+        "[Serializer] native function {} in a constant pool",
+        function.get_name()
+      )
+    }
+    // arrays are built at runtime by NEW_ARRAY, never pooled
+    Value::Array(_) => unreachable!("[Serializer] array in a constant pool"),
+    Value::RegExp(regexp) => {
+      buffer.push(TAG_REGEXP);
+      write_string(buffer, regexp.get_pattern());
+      write_string(buffer, regexp.get_flags());
+    }
+    // nested functions carry a whole chunk, written recursively
+    Value::Function(function) => {
+      buffer.push(TAG_FUNCTION);
+      write_string(buffer, function.get_name());
+      write_varint(buffer, function.get_arity());
+      buffer.push(function.is_arrow() as u8);
+      write_chunk(buffer, function.get_chunk());
+    }
+  }
+}
+
+fn write_chunk(buffer: &mut Vec<u8>, chunk: &Chunk) {
+  write_varint(buffer, chunk.globals.len());
+  for name in &chunk.globals {
+    write_string(buffer, name);
+  }
+  write_varint(buffer, chunk.constants.len());
+  for constant in &chunk.constants {
+    write_constant(buffer, constant);
+  }
+  write_code(buffer, &chunk.code);
+  write_varint(buffer, chunk.lines.len());
+  for &(offset, line) in &chunk.lines {
+    write_varint(buffer, offset);
+    write_varint(buffer, line as usize);
+  }
+  write_varint(buffer, chunk.max_stack);
+  write_varint(buffer, chunk.locals.len());
+  for name in &chunk.locals {
+    write_string(buffer, name);
+  }
+}
+
+fn write_code(buffer: &mut Vec<u8>, code: &[u8]) {
+  let mut encoded = Vec::with_capacity(code.len());
+  let mut count = 0;
+  let mut offset = 0;
+  while offset < code.len() {
+    let opcode = code[offset];
+    let width = opcode_operands(opcode);
+    encoded.push(opcode);
+    count += 1;
+    if width > 0 {
+      let Some(mut operand) = decode_operand(code, offset + 1, width) else {
+        unreachable!("[Serializer] truncated instruction at {:04X}", offset);
+      };
+      if is_jump(opcode) {
+        let distance = operand as u16 as i16;
+        operand = ((distance << 1) ^ (distance >> 15)) as u16 as usize;
+      }
+      write_varint(&mut encoded, operand);
+    }
+    offset += 1 + width;
+  }
+  write_varint(buffer, count);
+  buffer.extend_from_slice(&encoded);
+}
+
+fn write_string(buffer: &mut Vec<u8>, value: &str) {
+  write_varint(buffer, value.len());
+  buffer.extend_from_slice(value.as_bytes());
+}
+
+fn write_varint(buffer: &mut Vec<u8>, mut value: usize) {
+  loop {
+    let byte = (value & 0x7F) as u8;
+    value >>= 7;
+    if value == 0 {
+      buffer.push(byte);
+      return;
+    }
+    buffer.push(byte | 0x80);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use oxc_allocator::Allocator;
+
+  use crate::bytecode::chunk::Chunk;
+  use crate::bytecode::format::BYTECODE_VERSION;
+  use crate::bytecode::opcode;
+  use crate::compiler::compile;
+  use crate::context::Context;
+  use crate::disassembler::Disassembler;
+  use crate::errors::DecodeError;
+
+  fn compile_chunk(source: &str, ctx: &mut Context) -> Chunk {
+    compile(&Allocator::default(), &source.to_string(), ctx).unwrap().chunk
+  }
+
+  #[test]
+  fn a_chunk_round_trips_with_its_code_constants_and_lines() {
+    let mut ctx = Context::new();
+    let source = "function add(a, b) {\n  return a + b;\n}\nlet s = \"sum\";\nlet total = add(1, 2.5);\n";
+    let chunk = compile_chunk(source, &mut ctx);
+    let loaded = Chunk::deserialize(&chunk.serialize()).unwrap();
+    assert_eq!(loaded.code, chunk.code);
+    assert_eq!(loaded.constants, chunk.constants);
+    assert_eq!(loaded.lines, chunk.lines);
+    assert_eq!(loaded.globals, chunk.globals);
+    assert_eq!(loaded.max_stack, chunk.max_stack);
+    // the function keeps the line table of its own body
+    assert_eq!(
+      loaded.constants[0].get_function().get_chunk().lines,
+      chunk.constants[0].get_function().get_chunk().lines
+    );
+  }
+
+  #[test]
+  fn varint_operands_shrink_the_file_but_not_the_code() {
+    let mut ctx = Context::new();
+    // GET_PROPERTY takes a four byte constant index in memory, small indices take one byte on disk
+    let source = format!("let o = {{ a: 1 }};{}", "o.a;".repeat(200));
+    let chunk = compile_chunk(&source, &mut ctx);
+    let bytes = chunk.serialize();
+    assert!(
+      bytes.len() < chunk.code.len(),
+      "{} bytes for {} bytes of code",
+      bytes.len(),
+      chunk.code.len()
+    );
+    let loaded = Chunk::deserialize(&bytes).unwrap();
+    let listing = |chunk: &Chunk| {
+      let mut output = Vec::new();
+      Disassembler::new(chunk, "main", &ctx)
+        .disassemble_to(&mut output)
+        .unwrap();
+      String::from_utf8(output).unwrap()
+    };
+    assert_eq!(listing(&loaded), listing(&chunk));
+  }
+
+  #[test]
+  fn serialized_bytecode_starts_with_the_magic() {
+    let mut ctx = Context::new();
+    let chunk = compile_chunk("let a = 1;", &mut ctx);
+    assert!(chunk.serialize().starts_with(b"CENG"));
+  }
+
+  #[test]
+  fn bytecode_of_another_version_is_rejected() {
+    let mut ctx = Context::new();
+    let mut bytes = compile_chunk("let a = 1;", &mut ctx).serialize();
+    bytes[4..6].copy_from_slice(&(BYTECODE_VERSION + 1).to_le_bytes());
+    assert_eq!(
+      Chunk::deserialize(&bytes).unwrap_err(),
+      DecodeError::VersionMismatch { found: BYTECODE_VERSION + 1, expected: BYTECODE_VERSION }
+    );
+  }
+
+  #[test]
+  fn constants_past_the_narrow_operand_round_trip() {
+    let mut ctx = Context::new();
+    let strings = (0..300).map(|n| format!("\"s{n}\"")).collect::<Vec<_>>().join(", ");
+    let chunk = compile_chunk(&format!("let a = [{strings}];"), &mut ctx);
+    let instructions = Disassembler::new(&chunk, "main", &ctx).instructions();
+    assert!(instructions
+      .iter()
+      .any(|instruction| instruction.opcode == opcode::OPCODE_CONST_LONG as usize));
+    let loaded = Chunk::deserialize(&chunk.serialize()).unwrap();
+    assert_eq!(loaded.code, chunk.code);
+    assert_eq!(loaded.constants, chunk.constants);
+  }
+}
@@ -0,0 +1,148 @@
+//! Checks a chunk before the vm trusts it: operands in range and jumps landing on instruction starts.
+use crate::bytecode::chunk::Chunk;
+use crate::bytecode::opcode;
+use crate::context::Context;
+use crate::errors::VerifyError;
+use crate::utils::{decode_operand, is_jump, jump_target, opcode_operands};
+use crate::values::Value;
+
+pub fn verify(chunk: &Chunk, ctx: &Context) -> Result<(), VerifyError> {
+  verify_chunk(chunk, ctx, None)
+}
+
+// `arity` is None for the program, arguments only exist inside functions
+fn verify_chunk(chunk: &Chunk, ctx: &Context, arity: Option<usize>) -> Result<(), VerifyError> {
+  let code = &chunk.code;
+  let mut starts = vec![false; code.len()];
+  let mut jumps = vec![];
+  let mut last = None;
+  let mut offset = 0;
+  while offset < code.len() {
+    let instruction = code[offset];
+    if instruction > opcode::LAST_OPCODE {
+      return Err(VerifyError::UnknownOpcode { offset, opcode: instruction });
+    }
+    let width = opcode_operands(instruction);
+    let Some(operand) = decode_operand(code, offset + 1, width) else {
+      return Err(VerifyError::TruncatedOperand { offset, opcode: instruction });
+    };
+    match instruction {
+      opcode::OPCODE_CONST
+      | opcode::OPCODE_CONST_LONG
+      | opcode::OPCODE_ADD_CONST
+      | opcode::OPCODE_LT_CONST
+      | opcode::OPCODE_GET_PROPERTY
+      | opcode::OPCODE_INIT_PROPERTY
+      | opcode::OPCODE_SET_PROPERTY
+        if operand >= chunk.constants.len() =>
+      {
+        return Err(VerifyError::ConstantOutOfRange { offset, index: operand, len: chunk.constants.len() });
+      }
+      opcode::OPCODE_LOAD_GLOBAL_SCOPE
+      | opcode::OPCODE_SET_GLOBAL_SCOPE
+      | opcode::OPCODE_LOAD_GLOBAL_SCOPE_LONG
+      | opcode::OPCODE_SET_GLOBAL_SCOPE_LONG
+      | opcode::OPCODE_SET_GLOBAL_POP
+        if operand >= ctx.get_global_variables().len() =>
+      {
+        return Err(VerifyError::GlobalOutOfRange { offset, index: operand, len: ctx.get_global_variables().len() });
+      }
+      opcode::OPCODE_LOAD_ARGUMENT | opcode::OPCODE_SET_ARGUMENT if operand >= arity.unwrap_or(0) => {
+        return Err(VerifyError::ArgumentOutOfRange { offset, index: operand, arity: arity.unwrap_or(0) });
+      }
+      opcode::OPCODE_GET_LOCAL | opcode::OPCODE_SET_LOCAL | opcode::OPCODE_SET_LOCAL_POP
+        if operand >= chunk.locals.len() =>
+      {
+        return Err(VerifyError::LocalOutOfRange { offset, index: operand, len: chunk.locals.len() });
+      }
+      _ if is_jump(instruction) => jumps.push(offset),
+      _ => {}
+    }
+    starts[offset] = true;
+    last = Some(instruction);
+    offset += 1 + width;
+  }
+
+  for offset in jumps {
+    let target = jump_target(code, offset);
+    match target {
+      Some(target) if target < code.len() && starts[target] => {}
+      Some(target) if target < code.len() => return Err(VerifyError::JumpIntoOperand { offset, target }),
+      _ => return Err(VerifyError::JumpOutOfBounds { offset }),
+    }
+  }
+
+  if !matches!(
+    last,
+    Some(opcode::OPCODE_HALT | opcode::OPCODE_RETURN | opcode::OPCODE_THROW)
+  ) {
+    return Err(VerifyError::MissingTerminator { offset: code.len() });
+  }
+
+  // function bodies are chunks of their own, with the same globals
+  for constant in &chunk.constants {
+    if let Value::Function(function) = constant {
+      verify_chunk(function.get_chunk(), ctx, Some(function.get_arity()))?;
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::verify;
+  use crate::bytecode::chunk::Chunk;
+  use crate::bytecode::opcode;
+  use crate::context::Context;
+  use crate::errors::VerifyError;
+  use crate::values::Value;
+
+  fn verify_code(code: Vec<u8>, constants: Vec<Value>) -> Result<(), VerifyError> {
+    let mut chunk = Chunk::new();
+    chunk.code = code;
+    chunk.constants = constants;
+    verify(&chunk, &Context::new())
+  }
+
+  #[test]
+  fn bad_bytecode_is_rejected() {
+    let one = || vec![Value::create_number_value(1.0)];
+    assert_eq!(
+      verify_code(vec![0xFE], vec![]),
+      Err(VerifyError::UnknownOpcode { offset: 0, opcode: 0xFE })
+    );
+    assert_eq!(
+      verify_code(vec![opcode::OPCODE_CONST], vec![]),
+      Err(VerifyError::TruncatedOperand { offset: 0, opcode: opcode::OPCODE_CONST })
+    );
+    assert_eq!(
+      verify_code(vec![opcode::OPCODE_CONST, 1, opcode::OPCODE_HALT], one()),
+      Err(VerifyError::ConstantOutOfRange { offset: 0, index: 1, len: 1 })
+    );
+    let globals = Context::new().get_global_variables().len();
+    assert_eq!(
+      verify_code(vec![opcode::OPCODE_LOAD_GLOBAL_SCOPE, 200, opcode::OPCODE_HALT], vec![]),
+      Err(VerifyError::GlobalOutOfRange { offset: 0, index: 200, len: globals })
+    );
+    assert_eq!(
+      verify_code(vec![opcode::OPCODE_JUMP, 100, 0, opcode::OPCODE_HALT], vec![]),
+      Err(VerifyError::JumpOutOfBounds { offset: 0 })
+    );
+    // lands on the operand of the CONST
+    assert_eq!(
+      verify_code(
+        vec![opcode::OPCODE_JUMP, 1, 0, opcode::OPCODE_CONST, 0, opcode::OPCODE_HALT],
+        one()
+      ),
+      Err(VerifyError::JumpIntoOperand { offset: 0, target: 4 })
+    );
+    assert!(matches!(
+      verify_code(vec![opcode::OPCODE_CONST, 0], one()),
+      Err(VerifyError::MissingTerminator { .. })
+    ));
+    assert_eq!(
+      verify_code(vec![opcode::OPCODE_CONST, 0, opcode::OPCODE_HALT], one()),
+      Ok(())
+    );
+  }
+}
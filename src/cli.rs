@@ -11,7 +11,7 @@ pub fn command_line() -> clap::ArgMatches {
         .about("run a javascript or typescript file.")
         .arg(
           Arg::new("file")
-            .help("the javascript or typescript file to execute.")
+            .help("the javascript or typescript file to execute, or a .cbc file produced by `compile`.")
             .required(true),
         )
         .arg(
@@ -22,6 +22,7 @@ pub fn command_line() -> clap::ArgMatches {
             .action(clap::ArgAction::SetTrue),
         ),
     )
+    .subcommand(Command::new("repl").about("start an interactive shell, each line is run as it's entered."))
     .subcommand(
       Command::new("compile")
         .about("compile a javascript or typescript file to bytecode.")
@@ -36,6 +37,12 @@ pub fn command_line() -> clap::ArgMatches {
             .long("debug")
             .action(ArgAction::SetTrue)
             .help("enable the disassembler and debugger for detailed analysis during compilation."),
+        )
+        .arg(
+          Arg::new("output")
+            .short('o')
+            .long("output")
+            .help("where to write the bytecode, defaults to the input file with a .cbc extension."),
         ),
     )
     .get_matches();
@@ -1,73 +1,379 @@
 #![allow(dead_code)]
+use crate::bytecode::chunk::Chunk;
 use crate::bytecode::opcode;
+use crate::compiler::folding::{self, Folded};
+use crate::compiler::integers;
+use crate::compiler::{hoisting, peephole, stack_depth};
 use crate::context::{Context, Kind};
-use crate::values::Value;
+use crate::errors::CompileError;
+use crate::utils::{jump_target, long_opcode, opcode_operands, patch_jump_target};
+use crate::values::{FunctionValue, NumberValue, Value};
 use oxc_ast::ast::{self, AssignmentTarget, Program};
-use oxc_syntax::NumberBase;
+use oxc_ast::syntax_directed_operations::BoundNames;
+use oxc_span::{GetSpan, Span};
+use oxc_syntax::BigintBase;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
 
-pub struct Compiler<'ctx> {
-  code: Vec<usize>,
+pub struct Compiler<'ctx, 'ast> {
+  chunk: Chunk,
   name: String,
-  constants: Vec<Value>,
+  // pool slot of every hashable constant, so dedup doesn't scan the whole pool
+  constant_indices: HashMap<ConstantKey, usize>,
   ctx: &'ctx mut Context,
+  line_starts: Vec<usize>,
+  current_line: u32,
+  // set once the current block can't fall through (e.g. after a return), later statements are dead code
+  terminated: bool,
+  // first error found, generation keeps going and `compile` reports it at the end
+  error: Option<CompileError>,
+  // parameters of the function being compiled, an identifier naming one loads that argument slot
+  parameters: Vec<String>,
+  // the block-scoped variables in scope, innermost last. each one gets a stack slot of its own in the frame,
+  // only the top level of the program declares globals in the context
+  locals: Vec<Local>,
+  // blocks entered since the start of the program or function body
+  scope_depth: usize,
+  // locals and parameters of the enclosing functions, a function can't capture them yet
+  enclosing: Rc<HashSet<String>>,
+  // `let`, `const` and `class` names of the blocks in scope whose declaration hasn't run yet, with the depth
+  // of their block. using one there is the temporal dead zone
+  uninitialized: Vec<(String, usize)>,
+  // compiling a function body, `return` leaves the call frame instead of stopping the program
+  is_function: bool,
+  // repl mode: the last expression statement keeps its value on the stack so `HALT` returns it
+  keep_last_value: bool,
+  // loops enclosing the current statement, innermost last. a function body starts with none
+  loops: Vec<Loop>,
+  // try blocks (between SETUP_TRY and POP_TRY) enclosing the current statement, innermost last
+  tries: Vec<Try<'ast>>,
+  // values the enclosing statements keep on the stack: the iterators of for-of loops and a return value
+  // waiting for finally blocks. `break` and `continue` pop the ones above their loop
+  stack_values: usize,
+  // variables only ever assigned integers, arithmetic on them uses the integer opcodes.
+  // none unless the analysis is enabled with `with_integer_arithmetic`
+  integer_variables: Rc<HashSet<String>>,
+  integer_arithmetic: bool,
+  optimization_level: OptimizationLevel,
+  // most constants one chunk may hold, `opcode::MAX_CONSTANTS` unless set lower
+  constant_limit: usize,
 }
 
-pub struct CompilerReturn {
+// how much a finished chunk is rewritten. below `Fused` every instruction the compiler emitted is still there
+// to read in the disassembly, which is easier to follow when debugging the compiler
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OptimizationLevel {
+  // the code as generated
+  None,
+  // dead constants and jumps to the next instruction removed, see `peephole::optimize`
+  Peephole,
+  // common pairs of instructions fused into superinstructions as well, see `peephole::fuse`
+  #[default]
+  Fused,
+}
+
+struct Local {
   name: String,
-  pub code: Vec<usize>,
-  pub constants: Vec<Value>,
+  kind: Kind,
+  slot: usize,
+  depth: usize,
+}
+
+// where an identifier resolves to
+#[derive(Clone, Copy)]
+enum Variable {
+  Local(usize),
+  Argument(usize),
+  Global(usize),
+}
+
+// where `continue` goes and the `break` jumps to patch once the end of the loop is known
+struct Loop {
+  start: usize,
+  breaks: Vec<usize>,
+  // try blocks already entered when the loop started, `break` and `continue` leave the ones entered since
+  tries: usize,
+  stack_values: usize,
+}
+
+// a try block being compiled. the finally block is compiled again on every `break`, `continue` and `return`
+// leaving it, with only the loops entered before the try statement in scope
+#[derive(Clone, Copy)]
+struct Try<'ast> {
+  finalizer: Option<&'ast ast::BlockStatement<'ast>>,
+  loops: usize,
 }
 
-impl<'ctx> Compiler<'ctx> {
-  pub fn new(name: String, ctx: &'ctx mut Context) -> Self {
-    Self { name, code: Vec::new(), constants: Vec::new(), ctx }
+// hashable identity of a constant. numbers are keyed by their bit pattern, so every NaN
+// literal shares one slot while 0 and -0 (equal under ==) stay distinct
+#[derive(Hash, PartialEq, Eq)]
+enum ConstantKey {
+  Undefined,
+  Null,
+  Boolean(bool),
+  Number(u64),
+  BigInt(i128),
+  String(Rc<str>),
+}
+
+impl ConstantKey {
+  fn from_value(value: &Value) -> Option<ConstantKey> {
+    match value {
+      Value::Undefined(_) => Some(ConstantKey::Undefined),
+      Value::Null(_) => Some(ConstantKey::Null),
+      Value::Boolean(boolean) => Some(ConstantKey::Boolean(boolean.get_value())),
+      Value::Number(number) => Some(ConstantKey::Number(number.get_value().to_bits())),
+      Value::BigInt(bigint) => Some(ConstantKey::BigInt(bigint.get_value())),
+      Value::String(string) => Some(ConstantKey::String(string.get_shared())),
+      _ => None,
+    }
   }
+}
+
+pub struct CompilerReturn {
+  pub name: String,
+  pub chunk: Chunk,
+}
+
+// a try block of the program: its first instruction and where the error goes, the catch block or the
+// finally block that rethrows it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExceptionHandler {
+  pub try_start: usize,
+  pub catch: usize,
+}
 
-  pub fn compile(program: &Program, ctx: &'ctx mut Context) -> CompilerReturn {
-    let mut compiler = Compiler::new("main".to_string(), ctx);
-    compiler.generate_program(program);
-    CompilerReturn { name: compiler.name, code: compiler.code, constants: compiler.constants }
+impl CompilerReturn {
+  // source line of the instruction at `offset`
+  pub fn line_for_offset(&self, offset: usize) -> Option<u32> {
+    self.chunk.line_for_offset(offset)
   }
 
-  fn generate_program(&mut self, program: &Program) {
-    for statement in &program.body {
-      self.generate_statement(statement);
+  // the try blocks of the program's own code, outermost first. they're read back from the SETUP_TRY
+  // instructions, whose operand the peephole pass keeps pointing at the catch block like any jump
+  pub fn exception_handlers(&self) -> Vec<ExceptionHandler> {
+    let code = &self.chunk.code;
+    let mut handlers = vec![];
+    let mut offset = 0;
+    while offset < code.len() {
+      let width = opcode_operands(code[offset]);
+      if code[offset] == opcode::OPCODE_SETUP_TRY {
+        if let Some(catch) = jump_target(code, offset) {
+          handlers.push(ExceptionHandler { try_start: offset + 1 + width, catch });
+        }
+      }
+      offset += 1 + width;
+    }
+    handlers
+  }
+}
+
+impl<'ctx, 'ast> Compiler<'ctx, 'ast> {
+  pub fn new(name: String, source: &str, ctx: &'ctx mut Context) -> Self {
+    let mut line_starts = vec![0];
+    line_starts.extend(source.match_indices('\n').map(|(index, _)| index + 1));
+    Self {
+      name,
+      chunk: Chunk::new(),
+      constant_indices: HashMap::new(),
+      ctx,
+      line_starts,
+      current_line: 1,
+      terminated: false,
+      error: None,
+      parameters: Vec::new(),
+      locals: Vec::new(),
+      scope_depth: 0,
+      enclosing: Rc::new(HashSet::new()),
+      uninitialized: Vec::new(),
+      is_function: false,
+      keep_last_value: false,
+      loops: Vec::new(),
+      tries: Vec::new(),
+      stack_values: 0,
+      integer_variables: Rc::new(HashSet::new()),
+      integer_arithmetic: false,
+      optimization_level: OptimizationLevel::default(),
+      constant_limit: opcode::MAX_CONSTANTS,
+    }
+  }
+
+  // compiler for a function body, it shares the context and line table but owns its code and constants
+  fn function_compiler<'body>(&mut self, name: String, parameters: Vec<String>) -> Compiler<'_, 'body> {
+    let mut enclosing = (*self.enclosing).clone();
+    enclosing.extend(self.parameters.iter().cloned());
+    enclosing.extend(self.locals.iter().map(|local| local.name.clone()));
+    Compiler {
+      name,
+      chunk: Chunk::new(),
+      constant_indices: HashMap::new(),
+      ctx: &mut *self.ctx,
+      line_starts: self.line_starts.clone(),
+      current_line: self.current_line,
+      terminated: false,
+      error: None,
+      parameters,
+      locals: Vec::new(),
+      scope_depth: 0,
+      enclosing: Rc::new(enclosing),
+      uninitialized: Vec::new(),
+      is_function: true,
+      keep_last_value: false,
+      loops: Vec::new(),
+      tries: Vec::new(),
+      stack_values: 0,
+      integer_variables: self.integer_variables.clone(),
+      integer_arithmetic: self.integer_arithmetic,
+      optimization_level: self.optimization_level,
+      constant_limit: self.constant_limit,
+    }
+  }
+
+  pub fn with_last_value(mut self, keep_last_value: bool) -> Self {
+    self.keep_last_value = keep_last_value;
+    self
+  }
+
+  // runs the integer analysis over the program before generating it, see `integers::integer_variables`
+  pub fn with_integer_arithmetic(mut self, integer_arithmetic: bool) -> Self {
+    self.integer_arithmetic = integer_arithmetic;
+    self
+  }
+
+  pub fn with_optimization_level(mut self, optimization_level: OptimizationLevel) -> Self {
+    self.optimization_level = optimization_level;
+    self
+  }
+
+  pub fn with_constant_limit(mut self, constant_limit: usize) -> Self {
+    self.constant_limit = constant_limit;
+    self
+  }
+
+  pub fn compile(program: &Program, source: &str, ctx: &'ctx mut Context) -> Result<CompilerReturn, CompileError> {
+    Compiler::new("main".to_string(), source, ctx)
+      .with_integer_arithmetic(true)
+      .compile_program(program)
+  }
+
+  pub fn compile_program(mut self, program: &'ast Program<'ast>) -> Result<CompilerReturn, CompileError> {
+    if self.integer_arithmetic {
+      self.integer_variables = Rc::new(integers::integer_variables(program));
+    }
+    self.generate_program(program);
+    if let Some(error) = self.error {
+      return Err(error);
+    }
+    finish_chunk(&mut self.chunk, &self.name, self.optimization_level);
+    self.chunk.globals = self
+      .ctx
+      .get_global_variables()
+      .iter()
+      .map(|store| store.name.to_string())
+      .collect();
+    Ok(CompilerReturn { name: self.name, chunk: self.chunk })
+  }
+
+  fn generate_program(&mut self, program: &'ast Program<'ast>) {
+    self.hoist_var_declarations(&program.body);
+    self.enter_dead_zone(&program.body);
+    let last = program.body.len().saturating_sub(1);
+    for (index, statement) in program.body.iter().enumerate() {
+      match statement {
+        ast::Statement::ExpressionStatement(stmt) if self.keep_last_value && index == last && !self.terminated => {
+          self.set_current_line(stmt.span);
+          self.generate_expression(&stmt.expression);
+        }
+        _ => self.generate_statement(statement),
+      }
+    }
+    if !self.terminated {
+      self.emit(opcode::OPCODE_HALT);
     }
-    self.emit(opcode::OPCODE_HALF);
   }
 
-  fn generate_statement(&mut self, statement: &ast::Statement) {
+  fn generate_statement(&mut self, statement: &'ast ast::Statement<'ast>) {
+    if self.terminated {
+      return;
+    }
+    self.set_current_line(statement.span());
     match statement {
-      ast::Statement::ExpressionStatement(stmt) => self.generate_expression(&stmt.expression),
+      ast::Statement::ExpressionStatement(stmt) => self.generate_expression_statement(stmt),
       ast::Statement::Declaration(decl) => self.generate_declaration(decl),
       ast::Statement::IfStatement(stmt) => self.generate_if_statement(stmt),
-      ast::Statement::EmptyStatement(_) => self.generate_empty_statement(),
+      // a stray `;` has nothing to run
+      ast::Statement::EmptyStatement(_) => {}
       ast::Statement::BlockStatement(stmt) => self.generate_block_statement(stmt),
+      ast::Statement::ReturnStatement(stmt) => self.generate_return_statement(stmt),
+      ast::Statement::WhileStatement(stmt) => self.generate_while_statement(stmt),
+      ast::Statement::ForOfStatement(stmt) => self.generate_for_of_statement(stmt),
+      ast::Statement::ForInStatement(stmt) => self.generate_for_in_statement(stmt),
+      ast::Statement::BreakStatement(stmt) => self.generate_break_statement(stmt),
+      ast::Statement::ContinueStatement(stmt) => self.generate_continue_statement(stmt),
+      ast::Statement::ThrowStatement(stmt) => self.generate_throw_statement(stmt),
+      ast::Statement::TryStatement(stmt) => self.generate_try_statement(stmt),
       _ => panic!("Unknown statement"),
     }
   }
 
   fn generate_expression(&mut self, expression: &ast::Expression) {
+    self.set_current_line(expression.span());
     match expression {
       ast::Expression::NumericLiteral(value) => self.generate_numeric_literal(value),
+      ast::Expression::BigintLiteral(literal) => self.generate_bigint_literal(literal),
       ast::Expression::BooleanLiteral(value) => self.generate_boolean_literal(value),
+      ast::Expression::NullLiteral(_) => self.generate_null_literal(),
       ast::Expression::StringLiteral(literal) => self.generate_string_literal(literal),
+      ast::Expression::RegExpLiteral(literal) => self.generate_regexp_literal(literal),
       ast::Expression::BinaryExpression(binary) => self.generate_binary_expression(binary),
+      ast::Expression::UnaryExpression(unary) => self.generate_unary_expression(unary),
       ast::Expression::Identifier(identifier) => self.generate_identifier(identifier),
+      ast::Expression::ThisExpression(_) => self.emit(opcode::OPCODE_LOAD_THIS),
+      ast::Expression::ArrowFunctionExpression(arrow) => {
+        self.generate_arrow_function_expression(arrow, "anonymous".to_string())
+      }
+      ast::Expression::FunctionExpression(function) => {
+        let name = function
+          .id
+          .as_ref()
+          .map_or("anonymous".to_string(), |id| id.name.to_string());
+        self.generate_function(function, name)
+      }
+      ast::Expression::CallExpression(call) => self.generate_call_expression(call),
+      ast::Expression::NewExpression(new) => self.generate_new_expression(new),
+      ast::Expression::ClassExpression(class) => {
+        let name = class
+          .id
+          .as_ref()
+          .map_or("anonymous".to_string(), |id| id.name.to_string());
+        self.generate_class(class, name)
+      }
+      ast::Expression::ArrayExpression(array) => self.generate_array_expression(array),
+      ast::Expression::MemberExpression(member) => self.generate_member_expression(member),
+      ast::Expression::ObjectExpression(object) => self.generate_object_expression(object),
+      ast::Expression::SequenceExpression(sequence) => self.generate_sequence_expression(sequence),
+      // grouping only matters to the parser, `(a + b)` compiles exactly like `a + b`
+      ast::Expression::ParenthesizedExpression(parenthesized) => self.generate_expression(&parenthesized.expression),
       ast::Expression::AssignmentExpression(assignment) => self.generate_assignment_expression(assignment),
       _ => panic!("Unknown expression"),
     }
   }
 
-  fn generate_block_statement(&mut self, statement: &ast::BlockStatement) {
+  fn generate_block_statement(&mut self, statement: &'ast ast::BlockStatement<'ast>) {
     self.enter_scope();
+    self.enter_dead_zone(&statement.body);
     for stmt in &statement.body {
       self.generate_statement(stmt);
     }
-    self.emit(opcode::OPCODE_POP);
     self.exit_scope();
   }
 
+  // the value of an expression statement is never used, so it's discarded to keep the stack bounded
+  fn generate_expression_statement(&mut self, statement: &ast::ExpressionStatement) {
+    self.generate_expression(&statement.expression);
+    self.emit(opcode::OPCODE_POP);
+  }
+
   fn generate_assignment_expression(&mut self, assignment: &ast::AssignmentExpression) {
     match assignment.operator.as_str() {
       "=" => self.generate_assignment_target(&assignment.left, &assignment.right),
@@ -76,32 +382,75 @@ impl<'ctx> Compiler<'ctx> {
   }
 
   fn generate_assignment_target(&mut self, target: &AssignmentTarget, init: &ast::Expression) {
+    if let AssignmentTarget::SimpleAssignmentTarget(ast::SimpleAssignmentTarget::MemberAssignmentTarget(member)) =
+      target
+    {
+      self.generate_member_assignment(member, init);
+      return;
+    }
+    if let AssignmentTarget::SimpleAssignmentTarget(ast::SimpleAssignmentTarget::AssignmentTargetIdentifier(id)) =
+      target
+    {
+      if self.in_dead_zone(&id.name) {
+        self.report(CompileError::UsedBeforeDeclaration { name: id.name.to_string() });
+        self.generate_expression(init);
+        return;
+      }
+    }
     if target.is_identifier() {
-      let variable_idx = self.get_assignment_target(target);
+      let variable = self.get_assignment_target(target);
       self.generate_expression(init);
-      self.emit(opcode::OPCODE_SET_GLOBAL_SCOPE);
-      self.emit(variable_idx);
+      self.emit_assignment(variable);
       return;
     }
     panic!("Unknown left assignment expression");
   }
 
-  fn get_assignment_target(&mut self, identifier: &ast::AssignmentTarget) -> usize {
+  // object (and key), then the value, the SET leaves the value on the stack like any assignment
+  fn generate_member_assignment(&mut self, member: &ast::MemberExpression, value: &ast::Expression) {
+    match member {
+      ast::MemberExpression::StaticMemberExpression(member) => {
+        self.generate_expression(&member.object);
+        self.generate_expression(value);
+        let index = self.get_string_constant_index(&member.property.name);
+        self.emit_operand(opcode::OPCODE_SET_PROPERTY, index);
+      }
+      ast::MemberExpression::ComputedMemberExpression(member) => {
+        self.generate_expression(&member.object);
+        self.generate_expression(&member.expression);
+        self.generate_expression(value);
+        self.emit(opcode::OPCODE_SET_ELEMENT);
+      }
+      ast::MemberExpression::PrivateFieldExpression(_) => panic!("[Compiler] private fields are not supported yet"),
+    }
+  }
+
+  fn get_assignment_target(&mut self, identifier: &ast::AssignmentTarget) -> Variable {
     match identifier {
       ast::AssignmentTarget::SimpleAssignmentTarget(assign) => self.get_simple_assignment_target(assign),
       ast::AssignmentTarget::AssignmentTargetPattern(_) => panic!("AssignmentTargetPattern is not supported"),
     }
   }
 
-  fn get_simple_assignment_target(&mut self, target: &ast::SimpleAssignmentTarget) -> usize {
+  fn get_simple_assignment_target(&mut self, target: &ast::SimpleAssignmentTarget) -> Variable {
     match target {
       ast::SimpleAssignmentTarget::AssignmentTargetIdentifier(id) => {
-        if let Some(kind) = self.ctx.get_kind_variable(&id.name) {
-          if kind == Kind::Const {
-            panic!("[Compiler] TypeError: '{}' is a read-only variable", id.name);
-          }
+        let Some(variable) = self.resolve_variable(&id.name) else {
+          panic!("[Compiler] Reference Error: {} is not defined", id.name);
+        };
+        let kind = match variable {
+          Variable::Local(slot) => self
+            .locals
+            .iter()
+            .find(|local| local.slot == slot)
+            .map(|local| &local.kind),
+          Variable::Argument(_) => None,
+          Variable::Global(index) => self.ctx.get_global_variable(index).map(|store| &store.kind),
+        };
+        if kind == Some(&Kind::Const) {
+          panic!("[Compiler] TypeError: '{}' is a read-only variable", id.name);
         }
-        self.get_variable_index(id)
+        variable
       }
       _ => panic!("Unknown left assignment expression"),
     }
@@ -110,34 +459,615 @@ impl<'ctx> Compiler<'ctx> {
   fn generate_declaration(&mut self, declaration: &ast::Declaration) {
     match declaration {
       ast::Declaration::VariableDeclaration(decl) => self.generate_variable_declaration(decl),
+      ast::Declaration::FunctionDeclaration(function) => self.generate_function_declaration(function),
+      ast::Declaration::ClassDeclaration(class) => self.generate_class_declaration(class),
       _ => panic!("Unknown declaration"),
     }
   }
 
-  fn generate_if_statement(&mut self, statement: &ast::IfStatement) {
+  fn generate_if_statement(&mut self, statement: &'ast ast::IfStatement<'ast>) {
+    // the test is consumed by JUMP_IF_FALSE, neither branch leaves anything behind
     self.generate_expression(&statement.test);
-    self.emit(opcode::OPCODE_JUMP_IF_FALSE);
-    let jump_if_false_address = self.code.len();
-    self.emit(0);
+    let jump_if_false = self.emit_jump(opcode::OPCODE_JUMP_IF_FALSE);
     self.generate_statement(&statement.consequent);
-    self.emit(opcode::OPCODE_JUMP);
-    let jump_address = self.code.len();
-    self.emit(0);
-    self.code[jump_if_false_address] = self.code.len();
+    let consequent_terminated = self.terminated;
+    let jump = self.emit_jump(opcode::OPCODE_JUMP);
+    // the alternate is a jump target, so it's reachable even if the consequent returned
+    self.terminated = false;
+    self.patch_jump(jump_if_false);
     if let Some(alternate) = &statement.alternate {
       self.generate_statement(alternate);
     }
-    self.code[jump_address] = self.code.len();
+    let alternate_terminated = statement.alternate.is_some() && self.terminated;
+    self.terminated = consequent_terminated && alternate_terminated;
+    self.patch_jump(jump);
+  }
+
+  // the test runs before every iteration, `continue` jumps back to it and JUMP_IF_FALSE leaves the loop
+  fn generate_while_statement(&mut self, statement: &'ast ast::WhileStatement<'ast>) {
+    let start = self.chunk.len();
+    self.generate_expression(&statement.test);
+    let exit = self.emit_jump(opcode::OPCODE_JUMP_IF_FALSE);
+    self
+      .loops
+      .push(Loop { start, breaks: vec![], tries: self.tries.len(), stack_values: self.stack_values });
+    self.generate_statement(&statement.body);
+    let Loop { breaks, .. } = self.loops.pop().expect("[Compiler] loop to close");
+    // the code after the loop is reachable through the test, whatever the body ends with
+    self.terminated = false;
+    self.emit_loop(start);
+    self.patch_jump(exit);
+    for jump in breaks {
+      self.patch_jump(jump);
+    }
+  }
+
+  // `for (const x of iterable)` over arrays, strings and objects with an `@@iterator` method
+  fn generate_for_of_statement(&mut self, statement: &'ast ast::ForOfStatement<'ast>) {
+    if statement.r#await {
+      panic!("[Compiler] for await is not supported yet");
+    }
+    self.generate_expression(&statement.right);
+    self.generate_iteration(&statement.left, &statement.body);
+  }
+
+  // `for (const key in object)` is a for-of over the keys, ENUMERATE snapshots them before the first iteration
+  fn generate_for_in_statement(&mut self, statement: &'ast ast::ForInStatement<'ast>) {
+    self.generate_expression(&statement.right);
+    self.emit(opcode::OPCODE_ENUMERATE);
+    self.generate_iteration(&statement.left, &statement.body);
+  }
+
+  // the loop of for-of and for-in over the value on top of the stack. ITERATE turns it into two values
+  // that stay on the stack for the whole loop, ITERATE_NEXT pushes the next element or leaves once there's none
+  fn generate_iteration(&mut self, left: &ast::ForStatementLeft, body: &'ast ast::Statement<'ast>) {
+    self.emit(opcode::OPCODE_ITERATE);
+    self.stack_values += 2;
+    self.enter_scope();
+    let start = self.chunk.len();
+    let exit = self.emit_jump(opcode::OPCODE_ITERATE_NEXT);
+    // the binding is stored again every iteration, so each one sees its own element.
+    // TODO: the binding is one frame slot reused by every iteration, not a fresh binding per iteration.
+    // nothing can observe that yet because a closure reading it is rejected ("closures are not supported
+    // yet"); once closures capture locals, each iteration needs its own captured cell
+    self.generate_loop_binding(left);
+    self.emit(opcode::OPCODE_POP);
+    self
+      .loops
+      .push(Loop { start, breaks: vec![], tries: self.tries.len(), stack_values: self.stack_values });
+    self.generate_statement(body);
+    let Loop { breaks, .. } = self.loops.pop().expect("[Compiler] loop to close");
+    // the code after the loop is reachable through ITERATE_NEXT, whatever the body ends with
+    self.terminated = false;
+    self.emit_loop(start);
+    self.patch_jump(exit);
+    for jump in breaks {
+      self.patch_jump(jump);
+    }
+    self.exit_scope();
+    // the iterable and the index
+    self.emit(opcode::OPCODE_POP);
+    self.emit(opcode::OPCODE_POP);
+    self.stack_values -= 2;
+  }
+
+  // stores the element on top of the stack into the loop's binding, declared here for `let`/`const`
+  fn generate_loop_binding(&mut self, left: &ast::ForStatementLeft) {
+    match left {
+      ast::ForStatementLeft::VariableDeclaration(declaration) => {
+        let kind = match declaration.kind {
+          ast::VariableDeclarationKind::Let => Kind::Let,
+          ast::VariableDeclarationKind::Const => Kind::Const,
+          ast::VariableDeclarationKind::Var => Kind::Var,
+        };
+        if declaration.declarations.len() != 1 {
+          panic!("[Compiler] SyntaxError: a for-in/of loop declares exactly one variable");
+        }
+        let ast::BindingPatternKind::BindingIdentifier(ident) = &declaration.declarations[0].id.kind else {
+          panic!("[Compiler] destructuring in a for-in/of head is not supported yet");
+        };
+        if kind == Kind::Var {
+          let variable = self.hoisted_variable(&ident.name);
+          self.emit_assignment(variable);
+        } else {
+          let idx = self.define_variable(&ident.name, kind);
+          self.emit_store(idx);
+        }
+      }
+      ast::ForStatementLeft::AssignmentTarget(target) => {
+        let AssignmentTarget::SimpleAssignmentTarget(ast::SimpleAssignmentTarget::AssignmentTargetIdentifier(_)) =
+          target
+        else {
+          panic!("[Compiler] a for-in/of head can only assign to a variable yet");
+        };
+        let variable = self.get_assignment_target(target);
+        self.emit_assignment(variable);
+      }
+      ast::ForStatementLeft::UsingDeclaration(_) => panic!("[Compiler] using declarations are not supported yet"),
+    }
+  }
+
+  // the stack is the same at `break` as at the end of the body, so it can jump straight out
+  fn generate_break_statement(&mut self, statement: &ast::BreakStatement) {
+    if statement.label.is_some() {
+      panic!("[Compiler] labeled break is not supported yet");
+    }
+    let Some((tries, stack_values)) = self.loops.last().map(|current| (current.tries, current.stack_values)) else {
+      panic!("[Compiler] SyntaxError: Illegal break statement");
+    };
+    self.leave_tries(tries);
+    if !self.terminated {
+      self.pop_stack_values(stack_values);
+      let jump = self.emit_jump(opcode::OPCODE_JUMP);
+      if let Some(current) = self.loops.last_mut() {
+        current.breaks.push(jump);
+      }
+    }
+    self.terminated = true;
+  }
+
+  fn generate_continue_statement(&mut self, statement: &ast::ContinueStatement) {
+    if statement.label.is_some() {
+      panic!("[Compiler] labeled continue is not supported yet");
+    }
+    let Some((start, tries, stack_values)) = self
+      .loops
+      .last()
+      .map(|current| (current.start, current.tries, current.stack_values))
+    else {
+      panic!("[Compiler] SyntaxError: Illegal continue statement: no surrounding iteration statement");
+    };
+    self.leave_tries(tries);
+    if !self.terminated {
+      self.pop_stack_values(stack_values);
+      self.emit_loop(start);
+    }
+    self.terminated = true;
+  }
+
+  // jumping out of the try blocks entered after the first `tries` one, innermost first: each handler is
+  // dropped and its finally block runs. a finally block that returns, throws or jumps itself ends it there
+  fn leave_tries(&mut self, tries: usize) {
+    let mut left = vec![];
+    while self.tries.len() > tries && !self.terminated {
+      let Some(current) = self.tries.pop() else { break };
+      left.push(current);
+      self.emit(opcode::OPCODE_POP_TRY);
+      if let Some(finalizer) = current.finalizer {
+        let inner_loops = self.loops.split_off(current.loops);
+        self.generate_block_statement(finalizer);
+        self.loops.extend(inner_loops);
+      }
+    }
+    self.tries.extend(left.into_iter().rev());
+  }
+
+  // drops what the statements inside the loop keep on the stack, leaving it as the loop's body found it
+  fn pop_stack_values(&mut self, stack_values: usize) {
+    for _ in stack_values..self.stack_values {
+      self.emit(opcode::OPCODE_POP);
+    }
+  }
+
+  // a top-level return stops the program with its value. the vm drops the try blocks of a frame that
+  // returns, so they're only left one by one when there are finally blocks to run first, the value
+  // waiting on the stack meanwhile
+  fn generate_return_statement(&mut self, statement: &ast::ReturnStatement) {
+    if let Some(argument) = &statement.argument {
+      self.generate_expression(argument);
+    } else {
+      self.generate_undefined();
+    }
+    if self.tries.iter().any(|current| current.finalizer.is_some()) {
+      self.stack_values += 1;
+      self.leave_tries(0);
+      self.stack_values -= 1;
+    }
+    if !self.terminated {
+      if self.is_function {
+        self.emit(opcode::OPCODE_RETURN);
+      } else {
+        self.emit(opcode::OPCODE_HALT);
+      }
+    }
+    self.terminated = true;
+  }
+
+  fn generate_throw_statement(&mut self, statement: &ast::ThrowStatement) {
+    self.generate_expression(&statement.argument);
+    self.emit(opcode::OPCODE_THROW);
+    self.terminated = true;
+  }
+
+  // the try block runs between SETUP_TRY and POP_TRY, an error there (or in what it calls) jumps to the catch
+  // block with the error's value on the stack. the finally block is compiled on every way out: after the try
+  // block, after the catch block, and before rethrowing an error neither of them handled
+  fn generate_try_statement(&mut self, statement: &'ast ast::TryStatement<'ast>) {
+    let finalizer = statement.finalizer.as_deref();
+    let setup = self.emit_jump(opcode::OPCODE_SETUP_TRY);
+    self.tries.push(Try { finalizer, loops: self.loops.len() });
+    self.generate_block_statement(&statement.block);
+    self.tries.pop();
+    let mut exits = vec![];
+    self.generate_try_exit(finalizer, &mut exits);
+    let mut terminated = self.terminated;
+
+    self.patch_jump(setup);
+    self.terminated = false;
+    let Some(handler) = &statement.handler else {
+      // try/finally, the error goes straight to the rethrow
+      self.generate_rethrow(finalizer);
+      self.patch_exits(exits, terminated);
+      return;
+    };
+    self.enter_scope();
+    match &handler.param {
+      Some(param) => self.bind_pattern(&param.pattern, &Kind::Let),
+      None => self.emit(opcode::OPCODE_POP),
+    }
+    // the caught value is bound, an error thrown by the catch block only has to run the finally block
+    let rethrow = finalizer.map(|_| self.emit_jump(opcode::OPCODE_SETUP_TRY));
+    if rethrow.is_some() {
+      self.tries.push(Try { finalizer, loops: self.loops.len() });
+    }
+    self.generate_block_statement(&handler.body);
+    if rethrow.is_some() {
+      self.tries.pop();
+    }
+    self.exit_scope();
+    match rethrow {
+      Some(rethrow) => {
+        self.generate_try_exit(finalizer, &mut exits);
+        terminated &= self.terminated;
+        self.patch_jump(rethrow);
+        self.terminated = false;
+        self.generate_rethrow(finalizer);
+      }
+      // the statement ends right after the catch block, it falls through
+      None => terminated &= self.terminated,
+    }
+    self.patch_exits(exits, terminated);
+  }
+
+  // a protected block that finished normally: its handler is dropped, the finally block runs and the
+  // statement is done
+  fn generate_try_exit(&mut self, finalizer: Option<&'ast ast::BlockStatement<'ast>>, exits: &mut Vec<usize>) {
+    if self.terminated {
+      return;
+    }
+    self.emit(opcode::OPCODE_POP_TRY);
+    if let Some(finalizer) = finalizer {
+      self.generate_block_statement(finalizer);
+    }
+    if !self.terminated {
+      exits.push(self.emit_jump(opcode::OPCODE_JUMP));
+    }
+  }
+
+  // the error on top of the stack wasn't handled, it's thrown again once the finally block ran
+  fn generate_rethrow(&mut self, finalizer: Option<&'ast ast::BlockStatement<'ast>>) {
+    if let Some(finalizer) = finalizer {
+      // a `break` or `continue` there drops the error instead
+      self.stack_values += 1;
+      self.generate_block_statement(finalizer);
+      self.stack_values -= 1;
+    }
+    if !self.terminated {
+      self.emit(opcode::OPCODE_THROW);
+    }
+    self.terminated = true;
+  }
+
+  fn patch_exits(&mut self, exits: Vec<usize>, terminated: bool) {
+    for exit in exits {
+      self.patch_jump(exit);
+    }
+    self.terminated = terminated;
+  }
+
+  fn generate_arrow_function_expression(&mut self, arrow: &ast::ArrowFunctionExpression, name: String) {
+    self.generate_function_value(name, &arrow.params, &arrow.body, arrow.expression, true);
+  }
+
+  // `function` declarations and expressions, unlike arrows they get their own `this` and can be constructors
+  fn generate_function(&mut self, function: &ast::Function, name: String) {
+    if function.generator || function.r#async {
+      panic!("[Compiler] generators and async functions are not supported yet");
+    }
+    let Some(body) = &function.body else {
+      panic!("[Compiler] function '{}' has no body", name);
+    };
+    self.generate_function_value(name, &function.params, body, false, false);
+  }
+
+  // `function name() {}` binds its name like a `let` for now, it isn't hoisted yet
+  fn generate_function_declaration(&mut self, function: &ast::Function) {
+    let Some(id) = &function.id else {
+      panic!("[Compiler] SyntaxError: function declarations need a name");
+    };
+    let idx = self.define_variable(&id.name, Kind::Let);
+    self.generate_function(function, id.name.to_string());
+    self.emit_store(idx);
+    self.emit(opcode::OPCODE_POP);
+  }
+
+  // the body is compiled into its own function value, which is pushed as a constant
+  fn generate_function_value(
+    &mut self,
+    name: String,
+    params: &ast::FormalParameters,
+    body: &ast::FunctionBody,
+    is_expression: bool,
+    is_arrow: bool,
+  ) {
+    let parameters = self.get_parameter_names(params);
+    let arity = parameters.len();
+    let mut compiler = self.function_compiler(name, parameters);
+    compiler.generate_function_body(body, is_expression);
+    let Compiler { name, chunk, error, .. } = compiler;
+    self.emit_function(name, chunk, error, arity, is_arrow);
+  }
+
+  // optimizes the chunk a function compiler produced and pushes the function as a constant
+  fn emit_function(
+    &mut self,
+    name: String,
+    mut chunk: Chunk,
+    error: Option<CompileError>,
+    arity: usize,
+    is_arrow: bool,
+  ) {
+    if let Some(error) = error {
+      self.report(error);
+    }
+    finish_chunk(&mut chunk, &name, self.optimization_level);
+    let function = FunctionValue::new(name, arity, is_arrow, chunk);
+    let index = self.constant_index(Value::create_function_value(function));
+    self.emit_constant(index);
+  }
+
+  // `class Name {}` binds its name like a `let`
+  fn generate_class_declaration(&mut self, class: &ast::Class) {
+    let Some(id) = &class.id else {
+      panic!("[Compiler] SyntaxError: class declarations need a name");
+    };
+    let idx = self.define_variable(&id.name, Kind::Let);
+    self.generate_class(class, id.name.to_string());
+    self.emit_store(idx);
+    self.emit(opcode::OPCODE_POP);
+    self.leave_dead_zone(&id.name);
+  }
+
+  // a class compiles to its constructor function, then every method is stored on the constructor's
+  // `prototype` so instances find it through their prototype chain
+  fn generate_class(&mut self, class: &ast::Class, name: String) {
+    if class.super_class.is_some() {
+      panic!("[Compiler] class inheritance (extends) is not supported yet");
+    }
+    let mut constructor = None;
+    let mut methods = vec![];
+    for element in &class.body.body {
+      let ast::ClassElement::MethodDefinition(method) = element else {
+        panic!("[Compiler] class fields and static blocks are not supported yet");
+      };
+      if method.r#static {
+        panic!("[Compiler] static class members are not supported yet");
+      }
+      match method.kind {
+        ast::MethodDefinitionKind::Constructor => constructor = Some(&method.value),
+        ast::MethodDefinitionKind::Method => methods.push(method),
+        ast::MethodDefinitionKind::Get | ast::MethodDefinitionKind::Set => {
+          panic!("[Compiler] class accessors are not supported yet")
+        }
+      }
+    }
+    let parameters = match constructor {
+      Some(constructor) => self.get_parameter_names(&constructor.params),
+      None => vec![],
+    };
+    let arity = parameters.len();
+    let mut compiler = self.function_compiler(name, parameters);
+    match constructor.and_then(|constructor| constructor.body.as_ref()) {
+      Some(body) => compiler.generate_function_body(body, false),
+      None => {
+        compiler.generate_undefined();
+        compiler.emit(opcode::OPCODE_RETURN);
+      }
+    }
+    let Compiler { name, chunk, error, .. } = compiler;
+    self.emit_function(name, chunk, error, arity, false);
+    // the constructor stays on the stack, each method is stored on a copy of its prototype
+    let prototype = self.get_string_constant_index("prototype");
+    for method in methods {
+      let Some(key) = method.key.static_name().filter(|_| !method.computed) else {
+        panic!("[Compiler] computed method names are not supported yet");
+      };
+      let key = key.to_string();
+      self.emit(opcode::OPCODE_DUP);
+      self.emit_operand(opcode::OPCODE_GET_PROPERTY, prototype);
+      self.generate_function(&method.value, key.clone());
+      let index = self.get_string_constant_index(&key);
+      self.emit_operand(opcode::OPCODE_SET_PROPERTY, index);
+      self.emit(opcode::OPCODE_POP);
+    }
+  }
+
+  fn generate_function_body(&mut self, body: &'ast ast::FunctionBody<'ast>, is_expression: bool) {
+    // a concise arrow body (`x => x + 1`) is a single expression that's returned implicitly
+    if is_expression {
+      if let Some(ast::Statement::ExpressionStatement(statement)) = body.statements.first() {
+        self.generate_expression(&statement.expression);
+        self.emit(opcode::OPCODE_RETURN);
+        return;
+      }
+    }
+    self.enter_scope();
+    self.hoist_var_declarations(&body.statements);
+    self.enter_dead_zone(&body.statements);
+    for statement in &body.statements {
+      self.generate_statement(statement);
+    }
+    self.exit_scope();
+    if !self.terminated {
+      self.generate_undefined();
+      self.emit(opcode::OPCODE_RETURN);
+    }
+  }
+
+  fn get_parameter_names(&self, params: &ast::FormalParameters) -> Vec<String> {
+    if params.rest.is_some() {
+      panic!("[Compiler] rest parameters are not supported yet");
+    }
+    let mut names = vec![];
+    for param in &params.items {
+      match &param.pattern.kind {
+        ast::BindingPatternKind::BindingIdentifier(ident) => names.push(ident.name.to_string()),
+        _ => panic!("[Compiler] destructuring parameters are not supported yet"),
+      }
+    }
+    names
+  }
+
+  // `a, b, c` evaluates every expression but only the last one is the result
+  fn generate_sequence_expression(&mut self, sequence: &ast::SequenceExpression) {
+    let last = sequence.expressions.len().saturating_sub(1);
+    for (index, expression) in sequence.expressions.iter().enumerate() {
+      self.generate_expression(expression);
+      if index != last {
+        self.emit(opcode::OPCODE_POP);
+      }
+    }
+  }
+
+  // the callee goes below its arguments, `OPCODE_CALL` replaces them all with the result
+  fn generate_call_expression(&mut self, call: &ast::CallExpression) {
+    // `object.method(...)` and `object[key](...)`: the object is evaluated once and kept below the method
+    // as its `this`
+    if let ast::Expression::MemberExpression(member) = &call.callee {
+      match &**member {
+        ast::MemberExpression::StaticMemberExpression(member) => {
+          self.generate_expression(&member.object);
+          self.emit(opcode::OPCODE_DUP);
+          let index = self.get_string_constant_index(&member.property.name);
+          self.emit_operand(opcode::OPCODE_GET_PROPERTY, index);
+        }
+        ast::MemberExpression::ComputedMemberExpression(member) => {
+          self.generate_expression(&member.object);
+          self.emit(opcode::OPCODE_DUP);
+          self.generate_expression(&member.expression);
+          self.emit(opcode::OPCODE_GET_ELEMENT);
+        }
+        ast::MemberExpression::PrivateFieldExpression(_) => panic!("[Compiler] private fields are not supported yet"),
+      }
+      self.generate_arguments(&call.arguments);
+      self.emit_operand(opcode::OPCODE_CALL_METHOD, call.arguments.len());
+      return;
+    }
+    self.generate_expression(&call.callee);
+    self.generate_arguments(&call.arguments);
+    self.emit_operand(opcode::OPCODE_CALL, call.arguments.len());
+  }
+
+  // laid out like a call, `OPCODE_NEW` creates the object the constructor sees as `this`
+  fn generate_new_expression(&mut self, new: &ast::NewExpression) {
+    self.generate_expression(&new.callee);
+    self.generate_arguments(&new.arguments);
+    self.emit_operand(opcode::OPCODE_NEW, new.arguments.len());
+  }
+
+  fn generate_arguments(&mut self, arguments: &[ast::Argument]) {
+    for argument in arguments {
+      match argument {
+        ast::Argument::Expression(expression) => self.generate_expression(expression),
+        ast::Argument::SpreadElement(_) => panic!("[Compiler] spread arguments are not supported yet"),
+      }
+    }
+  }
+
+  fn generate_member_expression(&mut self, member: &ast::MemberExpression) {
+    match member {
+      ast::MemberExpression::StaticMemberExpression(member) => {
+        self.generate_expression(&member.object);
+        let index = self.get_string_constant_index(&member.property.name);
+        self.emit_operand(opcode::OPCODE_GET_PROPERTY, index);
+      }
+      ast::MemberExpression::ComputedMemberExpression(member) => {
+        self.generate_expression(&member.object);
+        self.generate_expression(&member.expression);
+        self.emit(opcode::OPCODE_GET_ELEMENT);
+      }
+      ast::MemberExpression::PrivateFieldExpression(_) => panic!("[Compiler] private fields are not supported yet"),
+    }
+  }
+
+  // NEW_OBJECT, then one INIT_PROPERTY per property in source order, which is the key order
+  fn generate_object_expression(&mut self, object: &ast::ObjectExpression) {
+    self.emit(opcode::OPCODE_NEW_OBJECT);
+    for property in &object.properties {
+      let ast::ObjectPropertyKind::ObjectProperty(property) = property else {
+        panic!("[Compiler] spread properties are not supported yet");
+      };
+      if property.computed || property.kind != ast::PropertyKind::Init {
+        panic!("[Compiler] computed keys and accessors are not supported yet");
+      }
+      let key = match &property.key {
+        ast::PropertyKey::Expression(ast::Expression::NumericLiteral(literal)) => {
+          NumberValue::new(literal.value).to_js_string()
+        }
+        key => match key.static_name() {
+          Some(name) => name.to_string(),
+          None => panic!("[Compiler] unsupported property key"),
+        },
+      };
+      self.generate_expression(&property.value);
+      let index = self.get_string_constant_index(&key);
+      self.emit_operand(opcode::OPCODE_INIT_PROPERTY, index);
+    }
+  }
+
+  // elements are pushed in order, then NEW_ARRAY collects them
+  fn generate_array_expression(&mut self, array: &ast::ArrayExpression) {
+    for element in &array.elements {
+      match element {
+        ast::ArrayExpressionElement::Expression(expression) => self.generate_expression(expression),
+        // holes read as undefined
+        ast::ArrayExpressionElement::Elision(_) => self.generate_undefined(),
+        ast::ArrayExpressionElement::SpreadElement(_) => panic!("[Compiler] spread elements are not supported yet"),
+      }
+    }
+    self.emit_operand(opcode::OPCODE_NEW_ARRAY, array.elements.len());
   }
 
   fn generate_variable_declaration(&mut self, declaration: &ast::VariableDeclaration) {
     match declaration.kind {
       ast::VariableDeclarationKind::Let => self.handle_variable_declaration(declaration, Kind::Let),
       ast::VariableDeclarationKind::Const => self.handle_variable_declaration(declaration, Kind::Const),
-      _ => panic!("Unknown variable declaration kind"),
+      ast::VariableDeclarationKind::Var => self.handle_variable_declaration(declaration, Kind::Var),
+    }
+  }
+
+  // every `var` of a function body belongs to the whole function, whatever block it's written in, so they're
+  // all declared before the first statement runs and hold undefined until assigned. the program's are globals
+  fn hoist_var_declarations(&mut self, statements: &[ast::Statement]) {
+    for name in hoisting::var_names(statements) {
+      // a `var` naming a parameter is that parameter, and repl lines can repeat the `var` of an earlier one
+      let declared = match self.scope_depth {
+        0 => self.ctx.get_kind_variable(&name) == Some(Kind::Var),
+        _ => self.resolve_parameter(&name).is_some(),
+      };
+      if !declared {
+        self.define_variable(&name, Kind::Var);
+      }
     }
   }
 
+  // the binding `hoist_var_declarations` declared for `name`
+  fn hoisted_variable(&self, name: &str) -> Variable {
+    self
+      .resolve_variable(name)
+      .unwrap_or_else(|| panic!("[Compiler] 'var {}' wasn't hoisted", name))
+  }
+
   fn handle_variable_declaration(&mut self, declaration: &ast::VariableDeclaration, kind: Kind) {
     for declarator in &declaration.declarations {
       self.handle_variable_declarator(&declarator.id, &declarator.init, &kind);
@@ -146,6 +1076,15 @@ impl<'ctx> Compiler<'ctx> {
 
   fn handle_variable_declarator(&mut self, pattern: &ast::BindingPattern, init: &Option<ast::Expression>, kind: &Kind) {
     match &pattern.kind {
+      // already declared, the declarator only assigns it. without an initializer the binding keeps its value
+      ast::BindingPatternKind::BindingIdentifier(ident) if kind == &Kind::Var => {
+        if init.is_some() {
+          let variable = self.hoisted_variable(&ident.name);
+          self.generate_initializer(init, ident.name.as_str());
+          self.emit_assignment(variable);
+          self.emit(opcode::OPCODE_POP);
+        }
+      }
       ast::BindingPatternKind::BindingIdentifier(ident) => {
         if kind == &Kind::Const && init.is_none() {
           panic!(
@@ -154,155 +1093,819 @@ impl<'ctx> Compiler<'ctx> {
           );
         }
         let idx = self.define_variable(ident.name.as_str(), kind.clone());
-        self.initialize_declarator(init, idx);
+        self.initialize_declarator(init, idx, ident.name.as_str());
+        self.leave_dead_zone(&ident.name);
+      }
+      // the initializer is evaluated once, then taken apart
+      _ => {
+        let Some(init) = init else {
+          panic!("[Compiler] SyntaxError: Missing initializer in destructuring declaration");
+        };
+        self.generate_expression(init);
+        self.bind_pattern(pattern, kind);
+      }
+    }
+  }
+
+  // binds `pattern` to the value on top of the stack and pops it. each element or property is read from
+  // a copy of the value, so nested patterns see only their part of it
+  fn bind_pattern(&mut self, pattern: &ast::BindingPattern, kind: &Kind) {
+    match &pattern.kind {
+      ast::BindingPatternKind::BindingIdentifier(ident) => {
+        if kind == &Kind::Var {
+          let variable = self.hoisted_variable(&ident.name);
+          self.emit_assignment(variable);
+        } else {
+          let idx = self.define_variable(ident.name.as_str(), kind.clone());
+          self.emit_store(idx);
+        }
+        self.emit(opcode::OPCODE_POP);
+        self.leave_dead_zone(&ident.name);
+        return;
       }
-      ast::BindingPatternKind::ArrayPattern(elem) => {
-        for element in &elem.elements {
-          if let Some(element) = element {
-            self.handle_variable_declarator(element, init, kind);
-          }
+      ast::BindingPatternKind::ArrayPattern(array) => {
+        if array.rest.is_some() {
+          panic!("[Compiler] rest elements are not supported yet");
+        }
+        for (position, element) in array.elements.iter().enumerate() {
+          // a hole like `[, b]` skips its element
+          let Some(element) = element else {
+            continue;
+          };
+          self.emit(opcode::OPCODE_DUP);
+          let index = self.constant_index(Value::create_number_value(position as f64));
+          self.emit_constant(index);
+          self.emit(opcode::OPCODE_GET_ELEMENT);
+          self.bind_pattern(element, kind);
         }
       }
-      ast::BindingPatternKind::ObjectPattern(objects) => {
-        for property in &objects.properties {
-          match &property.key {
-            ast::PropertyKey::Identifier(ident) => {
-              let idx = self.define_variable(ident.name.as_str(), kind.clone());
-              self.initialize_declarator(init, idx);
-            }
-            ast::PropertyKey::Expression(_) => panic!("Expression key not supported"),
-            _ => panic!("Unknown property key"),
-          }
+      ast::BindingPatternKind::ObjectPattern(object) => {
+        if object.rest.is_some() {
+          panic!("[Compiler] rest properties are not supported yet");
+        }
+        for property in &object.properties {
+          let Some(key) = property.key.static_name().filter(|_| !property.computed) else {
+            panic!("[Compiler] computed keys in patterns are not supported yet");
+          };
+          let key = key.to_string();
+          self.emit(opcode::OPCODE_DUP);
+          let index = self.get_string_constant_index(&key);
+          self.emit_operand(opcode::OPCODE_GET_PROPERTY, index);
+          // `{ a }` binds `a`, `{ a: b }` binds `b`
+          self.bind_pattern(&property.value, kind);
         }
       }
-      ast::BindingPatternKind::AssignmentPattern(_) => panic!("Assignment pattern not supported"),
+      ast::BindingPatternKind::AssignmentPattern(_) => panic!("[Compiler] default values are not supported yet"),
     }
+    self.emit(opcode::OPCODE_POP);
   }
 
-  fn initialize_declarator(&mut self, init: &Option<ast::Expression>, idx: usize) {
-    if let Some(init) = init {
-      self.generate_expression(init);
-    } else {
-      self.constants.push(Value::new_undefined())
+  fn initialize_declarator(&mut self, init: &Option<ast::Expression>, idx: usize, name: &str) {
+    self.generate_initializer(init, name);
+    self.emit_store(idx);
+    // SET leaves the value on the stack for assignment expressions, a declaration has no value
+    self.emit(opcode::OPCODE_POP);
+  }
+
+  // the value a declarator binds to `name`, undefined when it has no initializer
+  fn generate_initializer(&mut self, init: &Option<ast::Expression>, name: &str) {
+    match init {
+      // `const add = () => ...` names the function after its binding
+      Some(ast::Expression::ArrowFunctionExpression(arrow)) => {
+        self.generate_arrow_function_expression(arrow, name.to_string());
+      }
+      Some(ast::Expression::FunctionExpression(function)) if function.id.is_none() => {
+        self.generate_function(function, name.to_string());
+      }
+      Some(init) => self.generate_expression(init),
+      None => self.generate_undefined(),
     }
-    if self.ctx.is_global_scope() {
-      self.emit(opcode::OPCODE_SET_GLOBAL_SCOPE);
+  }
+
+  // stores the top of the stack into the variable just declared at `idx`
+  fn emit_store(&mut self, idx: usize) {
+    if self.scope_depth == 0 {
+      self.emit_assignment(Variable::Global(idx));
     } else {
-      self.emit(opcode::OPCODE_SET_LOCAL_SCOPE);
+      self.emit_assignment(Variable::Local(idx));
     }
-    self.emit(idx);
   }
 
-  fn generate_empty_statement(&mut self) {
-    self.emit(opcode::OPCODE_HALF);
+  // stores the top of the stack into `variable`, the value stays on the stack as the assignment's result
+  fn emit_assignment(&mut self, variable: Variable) {
+    match variable {
+      Variable::Local(slot) => self.emit_operand(opcode::OPCODE_SET_LOCAL, slot),
+      Variable::Argument(slot) => self.emit_operand(opcode::OPCODE_SET_ARGUMENT, slot),
+      Variable::Global(index) => self.emit_indexed(opcode::OPCODE_SET_GLOBAL_SCOPE, index),
+    }
   }
 
   fn generate_identifier(&mut self, identifier: &ast::IdentifierReference) {
-    if let Some(index) = self.ctx.get_variable_index(&identifier.name) {
-      self.emit(opcode::OPCODE_LOAD_GLOBAL_SCOPE);
-      self.emit(index);
-      return;
+    if self.in_dead_zone(&identifier.name) {
+      self.report(CompileError::UsedBeforeDeclaration { name: identifier.name.to_string() });
+      return self.generate_undefined();
+    }
+    match self.resolve_variable(&identifier.name) {
+      Some(Variable::Local(slot)) => return self.emit_operand(opcode::OPCODE_GET_LOCAL, slot),
+      Some(Variable::Argument(slot)) => return self.emit_operand(opcode::OPCODE_LOAD_ARGUMENT, slot),
+      Some(Variable::Global(index)) => return self.emit_indexed(opcode::OPCODE_LOAD_GLOBAL_SCOPE, index),
+      None => {}
     }
-    if !self.ctx.is_internal(&identifier.name) {
+    // a standard global the host didn't register (see `Context::with_builtins`)
+    if self.ctx.is_internal(&identifier.name) {
       panic!("[Compiler] {} is not implemented yet", identifier.name);
     }
     panic!("[Compiler] Reference Error: {} is not defined", identifier.name);
   }
 
-  fn get_variable_index(&mut self, identifier: &ast::IdentifierReference) -> usize {
-    if let Some(index) = self.ctx.get_variable_index(&identifier.name) {
-      return index;
+  // argument slot of `name`, the last parameter wins when names repeat (like js)
+  fn resolve_parameter(&self, name: &str) -> Option<usize> {
+    self.parameters.iter().rposition(|parameter| parameter == name)
+  }
+
+  // innermost first: the locals in scope, the parameters, then the globals of the context
+  fn resolve_variable(&self, name: &str) -> Option<Variable> {
+    if let Some(local) = self.locals.iter().rev().find(|local| local.name == name) {
+      return Some(Variable::Local(local.slot));
     }
-    panic!("[Compiler] Reference Error: {} is not defined", identifier.name);
+    if let Some(slot) = self.resolve_parameter(name) {
+      return Some(Variable::Argument(slot));
+    }
+    if self.enclosing.contains(name) {
+      panic!(
+        "[Compiler] '{}' is a variable of an enclosing function, closures are not supported yet",
+        name
+      );
+    }
+    self.ctx.get_variable_index(name).map(Variable::Global)
   }
 
   fn generate_numeric_literal(&mut self, literal: &ast::NumericLiteral) {
     let index = self.get_numeric_constant_index(literal);
-    self.emit(opcode::OPCODE_CONST);
-    self.emit(index);
+    self.emit_constant(index);
+  }
+
+  // `10n`, `0xffn`: the raw text without its `n`, read in the literal's base
+  fn generate_bigint_literal(&mut self, literal: &ast::BigIntLiteral) {
+    let raw = literal.raw.strip_suffix('n').unwrap_or(&literal.raw).replace('_', "");
+    let (digits, radix) = match literal.base {
+      BigintBase::Decimal => (&raw[..], 10),
+      BigintBase::Binary => (&raw[2..], 2),
+      BigintBase::Octal => (&raw[2..], 8),
+      BigintBase::Hex => (&raw[2..], 16),
+    };
+    let Ok(value) = i128::from_str_radix(digits, radix) else {
+      panic!(
+        "[Compiler] RangeError: {} does not fit in a 128 bit BigInt",
+        literal.raw
+      );
+    };
+    let index = self.constant_index(Value::create_bigint_value(value));
+    self.emit_constant(index);
+  }
+
+  // `/ab+c/g`, pooled like the other literals. the pattern isn't checked here, there's no matcher yet
+  fn generate_regexp_literal(&mut self, literal: &ast::RegExpLiteral) {
+    let flags = literal.regex.flags.to_string();
+    let index = self.constant_index(Value::create_regexp_value(&literal.regex.pattern, &flags));
+    self.emit_constant(index);
   }
 
-  // fn generate_boolean_literal(&mut self, literal: &ast::BooleanLiteral) {
-  //     self.constants.push(Value::new_boolean(literal.value));
-  //     let index = self.constants.len() - 1;
-  //     self.emit(op
   fn generate_boolean_literal(&mut self, literal: &ast::BooleanLiteral) {
-    self.constants.push(Value::new_boolean(literal.value));
-    let index = self.constants.len() - 1;
-    self.emit(opcode::OPCODE_CONST);
-    self.emit(index);
+    let index = self.constant_index(Value::create_boolean_value(literal.value));
+    self.emit_constant(index);
+  }
+
+  fn generate_null_literal(&mut self) {
+    let index = self.constant_index(Value::create_null_value());
+    self.emit_constant(index);
+  }
+
+  fn generate_undefined(&mut self) {
+    let index = self.constant_index(Value::create_undefined_value());
+    self.emit_constant(index);
   }
 
   fn generate_string_literal(&mut self, literal: &ast::StringLiteral) {
     let index = self.get_string_constant_index(&literal.value);
-    self.emit(opcode::OPCODE_CONST);
-    self.emit(index);
+    self.emit_constant(index);
   }
 
   fn generate_binary_expression(&mut self, binary: &ast::BinaryExpression) {
+    if let Some(folded) = folding::fold_binary_expression(binary) {
+      self.generate_folded(folded);
+      return;
+    }
     self.generate_expression(&binary.left);
     self.generate_expression(&binary.right);
+    let is_integral = self.integer_arithmetic
+      && integers::is_integral(&binary.left, &self.integer_variables)
+      && integers::is_integral(&binary.right, &self.integer_variables);
     match binary.operator.as_str() {
+      "+" if is_integral => self.emit(opcode::OPCODE_ADD_INT),
+      "-" if is_integral => self.emit(opcode::OPCODE_SUB_INT),
+      "*" if is_integral => self.emit(opcode::OPCODE_MUL_INT),
       "+" => self.emit(opcode::OPCODE_ADD),
       "-" => self.emit(opcode::OPCODE_SUB),
       "*" => self.emit(opcode::OPCODE_MUL),
       "/" => self.emit(opcode::OPCODE_DIV),
       "===" => self.emit(opcode::OPCODE_EQ),
+      "==" => self.emit(opcode::OPCODE_WEAK_EQ),
+      "!=" => self.emit(opcode::OPCODE_NE),
+      "<" => self.emit(opcode::OPCODE_LT),
+      "<=" => self.emit(opcode::OPCODE_LE),
+      ">" => self.emit(opcode::OPCODE_GT),
+      ">=" => self.emit(opcode::OPCODE_GE),
+      "instanceof" => self.emit(opcode::OPCODE_INSTANCEOF),
       _ => panic!("Unknown binary operator"),
     }
   }
 
-  fn emit(&mut self, byte: usize) {
-    self.code.push(byte);
+  // there are no unary opcodes yet, so only literal operands (folded at compile time) are supported
+  fn generate_unary_expression(&mut self, unary: &ast::UnaryExpression) {
+    if let Some(folded) = folding::fold_unary_expression(unary) {
+      self.generate_folded(folded);
+      return;
+    }
+    match unary.operator.as_str() {
+      "!" => {
+        self.generate_expression(&unary.argument);
+        self.emit(opcode::OPCODE_NOT);
+      }
+      operator => panic!("[Compiler] unary {} is not implemented yet", operator),
+    }
   }
 
+  fn generate_folded(&mut self, folded: Folded) {
+    let index = match folded {
+      Folded::Number(number) => self.constant_index(Value::create_number_value(number)),
+      Folded::String(string) => self.get_string_constant_index(&string),
+      Folded::Boolean(boolean) => self.constant_index(Value::create_boolean_value(boolean)),
+    };
+    self.emit_constant(index);
+  }
+
+  // emits a jump with a placeholder distance, returns the jump's offset for `patch_jump`
+  fn emit_jump(&mut self, opcode: u8) -> usize {
+    self.emit_operand(opcode, 0);
+    self.chunk.len() - 1 - opcode_operands(opcode)
+  }
+
+  // points the jump at `jump` to the current end of the code
+  fn patch_jump(&mut self, jump: usize) {
+    let target = self.chunk.len();
+    if !patch_jump_target(&mut self.chunk.code, jump, target) {
+      self.report(CompileError::JumpTooFar { offset: jump, target });
+    }
+  }
+
+  // jumps back to `start`, the top of a loop
+  fn emit_loop(&mut self, start: usize) {
+    let jump = self.emit_jump(opcode::OPCODE_JUMP);
+    if !patch_jump_target(&mut self.chunk.code, jump, start) {
+      self.report(CompileError::JumpTooFar { offset: jump, target: start });
+    }
+  }
+
+  fn emit_constant(&mut self, index: usize) {
+    self.emit_indexed(opcode::OPCODE_CONST, index);
+  }
+
+  // the narrow form takes a one byte index, bigger indices switch to the LONG form of `opcode`
+  fn emit_indexed(&mut self, opcode: u8, index: usize) {
+    match long_opcode(opcode) {
+      Some(long) if index > opcode::MAX_NARROW_OPERAND => self.emit_operand(long, index),
+      _ => self.emit_operand(opcode, index),
+    }
+  }
+
+  // emits `opcode` followed by its little-endian operand, the width comes from `opcode_operands`
+  fn emit_operand(&mut self, opcode: u8, operand: usize) {
+    let width = opcode_operands(opcode);
+    let mut operand = operand;
+    if operand.checked_shr(width as u32 * 8).unwrap_or(0) != 0 {
+      self.report(CompileError::OperandTooLarge { opcode, operand, width });
+      // the chunk is thrown away, zero keeps it decodable until then
+      operand = 0;
+    }
+    self.emit(opcode);
+    for &byte in &operand.to_le_bytes()[..width] {
+      self.emit(byte);
+    }
+  }
+
+  fn emit(&mut self, byte: u8) {
+    self.chunk.write(byte, self.current_line);
+  }
+
+  fn set_current_line(&mut self, span: Span) {
+    let position = span.start as usize;
+    let line = match self.line_starts.binary_search(&position) {
+      Ok(index) => index + 1,
+      Err(index) => index,
+    };
+    self.current_line = line as u32;
+  }
+
+  // the block's locals go out of scope, their slots aren't reused so nothing needs to run
   fn exit_scope(&mut self) {
-    let len_of_variable_exit = self.ctx.deallocate_variable_in_scope();
-    if len_of_variable_exit > 0 {
-      self.emit(opcode::OPCODE_SCOPE_EXIT);
-      self.emit(len_of_variable_exit);
+    while self.locals.last().is_some_and(|local| local.depth == self.scope_depth) {
+      self.locals.pop();
     }
-    self.ctx.exit_scope();
+    let depth = self.scope_depth;
+    self.uninitialized.retain(|(_, declared)| *declared < depth);
+    self.scope_depth -= 1;
   }
 
   fn enter_scope(&mut self) {
-    self.ctx.enter_scope();
+    self.scope_depth += 1;
   }
 
-  fn create_value(&mut self, value: &ast::NumericLiteral) -> Value {
-    match value.base {
-      NumberBase::Decimal => Value::new_integer(value.value as i64),
-      NumberBase::Float => Value::new_float(value.value),
-      _ => Value::new_integer(value.value as i64),
+  // the names `statements` declare with `let`, `const` or `class` can't be used in the current block until
+  // their declaration runs
+  fn enter_dead_zone(&mut self, statements: &[ast::Statement]) {
+    let mut names = vec![];
+    for statement in statements {
+      match statement {
+        ast::Statement::Declaration(ast::Declaration::VariableDeclaration(declaration))
+          if declaration.kind.is_lexical() =>
+        {
+          declaration.bound_names(&mut |identifier| names.push(identifier.name.to_string()))
+        }
+        ast::Statement::Declaration(ast::Declaration::ClassDeclaration(class)) => {
+          class.bound_names(&mut |identifier| names.push(identifier.name.to_string()))
+        }
+        _ => {}
+      }
     }
+    let depth = self.scope_depth;
+    self.uninitialized.extend(names.into_iter().map(|name| (name, depth)));
+  }
+
+  // the declaration of `name` in the current block has run
+  fn leave_dead_zone(&mut self, name: &str) {
+    let depth = self.scope_depth;
+    self
+      .uninitialized
+      .retain(|(uninitialized, declared)| !(uninitialized == name && *declared == depth));
+  }
+
+  // `name` resolves to a block whose declaration of it hasn't run yet, unless a deeper block declared it
+  fn in_dead_zone(&self, name: &str) -> bool {
+    let pending = self
+      .uninitialized
+      .iter()
+      .filter(|(uninitialized, _)| uninitialized == name);
+    let Some(depth) = pending.map(|(_, declared)| *declared).max() else {
+      return false;
+    };
+    !self
+      .locals
+      .iter()
+      .any(|local| local.name == name && local.depth > depth)
   }
 
+  // every js number is a double, whatever base the literal was written in. the parser already decodes
+  // `0xff`, `0o17` and `0b1010` (and separators like `1_000`) into `value`, `raw` is only the source text
   fn get_numeric_constant_index(&mut self, value: &ast::NumericLiteral) -> usize {
-    let new_value = self.create_value(value);
-    for (index, current_value) in self.constants.iter().enumerate() {
-      if current_value.is_number() && current_value.is_equal(&new_value) {
-        return index;
-      }
-    }
-    self.constants.push(new_value);
-    self.constants.len() - 1
+    self.constant_index(Value::create_number_value(value.value))
   }
 
   fn get_string_constant_index(&mut self, value: &str) -> usize {
-    let new_value = Value::new_string(value.to_owned());
-    for (index, current_value) in self.constants.iter().enumerate() {
-      if current_value.is_string() && current_value.get_string() == new_value.get_string() {
-        return index;
-      }
+    let value = self.ctx.intern_shared(value);
+    self.constant_index(Value::create_interned_string_value(value))
+  }
+
+  // index of `value` in the constant pool, equal constants share one slot
+  fn constant_index(&mut self, value: Value) -> usize {
+    let key = ConstantKey::from_value(&value);
+    let existing = match &key {
+      Some(key) => self.constant_indices.get(key).copied(),
+      None => self.chunk.constants.iter().position(|constant| constant == &value),
+    };
+    if let Some(index) = existing {
+      return index;
+    }
+    if self.chunk.constants.len() >= self.constant_limit {
+      self.report(CompileError::TooManyConstants { limit: self.constant_limit });
+      return 0;
+    }
+    let index = self.chunk.add_constant(value);
+    if let Some(key) = key {
+      self.constant_indices.insert(key, index);
+    }
+    index
+  }
+
+  fn report(&mut self, error: CompileError) {
+    if self.error.is_none() {
+      self.error = Some(error);
     }
-    self.constants.push(new_value);
-    self.constants.len() - 1
   }
 
+  // a global at the top level of the program, a new stack slot anywhere else
   fn define_variable(&mut self, name: &str, kind: Kind) -> usize {
-    if self.ctx.is_exist_variable(name) {
+    if self.scope_depth == 0 {
+      if self.ctx.is_exist_variable(name) {
+        panic!("[Compiler] SyntaxError: '{}' has already been declared.", name);
+      }
+      return self.ctx.define_variable(name.to_owned(), None, kind);
+    }
+    let depth = self.scope_depth;
+    if self
+      .locals
+      .iter()
+      .any(|local| local.depth == depth && local.name == name)
+    {
       panic!("[Compiler] SyntaxError: '{}' has already been declared.", name);
     }
-    self.ctx.define_variable(name.to_owned(), None, kind)
+    let slot = self.chunk.locals.len();
+    self.chunk.locals.push(name.to_owned());
+    self.locals.push(Local { name: name.to_owned(), kind, slot, depth });
+    slot
+  }
+}
+
+// optimizes a finished chunk as far as `level` allows and sets its stack bound. the bound is taken before
+// fusing, a superinstruction never needs more stack than the pair it replaces but doesn't show it in its effect
+fn finish_chunk(chunk: &mut Chunk, name: &str, level: OptimizationLevel) {
+  if level >= OptimizationLevel::Peephole {
+    peephole::optimize(chunk);
+  }
+  chunk.max_stack = compute_max_stack(chunk, name);
+  if level >= OptimizationLevel::Fused {
+    peephole::fuse(chunk);
+  }
+}
+
+// the compiler only emits balanced code, a failure here is a bug in code generation
+fn compute_max_stack(chunk: &Chunk, name: &str) -> usize {
+  match stack_depth::max_stack(chunk) {
+    Ok(max_stack) => max_stack,
+    Err(error) => panic!("[Compiler] {} in '{}'", error, name),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_support::run;
+  use oxc_allocator::Allocator;
+  use oxc_span::SourceType;
+
+  use crate::bytecode::chunk::Chunk;
+  use crate::bytecode::opcode;
+  use crate::compiler::compile;
+  use crate::compiler::compile_repl;
+  use crate::compiler::compiler::Compiler;
+  use crate::context::Context;
+  use crate::errors::CompileError;
+  use crate::utils::opcode_operands;
+  use crate::values::Value;
+  use crate::vm::core::Engine;
+
+  fn compile_error(source: String) -> CompileError {
+    let mut ctx = Context::new();
+    match compile(&Allocator::default(), &source, &mut ctx) {
+      Ok(_) => panic!("expected a compile error"),
+      Err(error) => error,
+    }
+  }
+
+  fn repeat(item: &str, count: usize) -> String {
+    vec![item; count].join(", ")
+  }
+
+  #[test]
+  fn too_many_arguments_is_a_compile_error() {
+    let error = compile_error(format!("function f() {{}} f({});", repeat("1", 256)));
+    assert_eq!(
+      error,
+      CompileError::OperandTooLarge { opcode: opcode::OPCODE_CALL, operand: 256, width: 1 }
+    );
+    let error = compile_error(format!("function F() {{}} new F({});", repeat("1", 256)));
+    assert!(matches!(
+      error,
+      CompileError::OperandTooLarge { opcode: opcode::OPCODE_NEW, .. }
+    ));
+    let error = compile_error(format!("let o = {{ f() {{}} }}; o.f({});", repeat("1", 256)));
+    assert!(matches!(
+      error,
+      CompileError::OperandTooLarge { opcode: opcode::OPCODE_CALL_METHOD, .. }
+    ));
+  }
+
+  #[test]
+  fn too_many_parameters_is_a_compile_error() {
+    let params = (0..300).map(|i| format!("p{i}")).collect::<Vec<_>>().join(", ");
+    let error = compile_error(format!("function f({params}) {{ return p299; }}"));
+    assert_eq!(
+      error,
+      CompileError::OperandTooLarge { opcode: opcode::OPCODE_LOAD_ARGUMENT, operand: 299, width: 1 }
+    );
+  }
+
+  #[test]
+  fn too_many_array_elements_is_a_compile_error() {
+    let error = compile_error(format!("let a = [{}];", repeat("0", 65536)));
+    assert_eq!(
+      error,
+      CompileError::OperandTooLarge { opcode: opcode::OPCODE_NEW_ARRAY, operand: 65536, width: 2 }
+    );
+  }
+
+  #[test]
+  fn a_return_out_of_a_try_block_runs_the_finally_block_first() {
+    let source = r#"
+      let log = "";
+      function f() { try { log = log + "t"; return log; } finally { log = log + "f"; } }
+      f() + " " + log
+    "#;
+    assert_eq!(run(source).to_js_string(), "t tf");
+    // a return in the finally block replaces the one it interrupted
+    assert_eq!(
+      run("function g() { try { return 1; } finally { return 2; } } g()").as_number(),
+      Some(2.0)
+    );
+    // from a catch block, and through every finally block out to the function
+    let source = r#"
+      let log = "";
+      function h() {
+        try { try { throw 1; } catch (e) { return "c" + e; } finally { log = log + "1"; } } finally { log = log + "2"; }
+      }
+      h() + log
+    "#;
+    assert_eq!(run(source).to_js_string(), "c112");
+  }
+
+  #[test]
+  fn a_break_out_of_a_try_block_runs_the_finally_block_first() {
+    let source = r#"
+      let log = "";
+      let i = 0;
+      while (true) { try { i = i + 1; if (i == 3) { break; } } finally { log = log + i; } }
+      log
+    "#;
+    assert_eq!(run(source).to_js_string(), "123");
+    let source =
+      r#"let log = ""; for (let x of [1, 2]) { try { break; } catch (e) {} finally { log = log + x; } } log"#;
+    assert_eq!(run(source).to_js_string(), "1");
+  }
+
+  #[test]
+  fn a_continue_out_of_a_try_block_runs_the_finally_block_first() {
+    let source = r#"
+      let log = "";
+      for (let x of [1, 2, 3]) { try { if (x == 2) { continue; } log = log + x; } finally { log = log + "f"; } }
+      log
+    "#;
+    assert_eq!(run(source).to_js_string(), "1ff3f");
+    let source = r#"
+      let log = "";
+      for (let x of [1, 2]) { try { throw x; } catch (e) { continue; } finally { log = log + x; } }
+      log
+    "#;
+    assert_eq!(run(source).to_js_string(), "12");
+  }
+
+  #[test]
+  fn a_finally_block_can_leave_the_loop_around_a_return() {
+    // the finally block breaks out of the outer loop with the inner loop's iterator and the return value
+    // still on the stack
+    let source = r#"
+      function f() {
+        let log = "";
+        for (let x of [1, 2]) {
+          try { for (let y of [3, 4]) { return "r"; } } finally { log = log + x; break; }
+        }
+        return log;
+      }
+      f()
+    "#;
+    assert_eq!(run(source).to_js_string(), "1");
+    // a break in the finally block of an uncaught error drops the error
+    let source = r#"let n = 0; while (true) { try { throw 1; } finally { n = n + 1; break; } } n"#;
+    assert_eq!(run(source).as_number(), Some(1.0));
+  }
+
+  #[test]
+  fn a_jump_over_too_much_code_is_a_compile_error() {
+    let body = "a;".repeat(20000);
+    let error = compile_error(format!("let a = 1; if (a) {{ {body} }}"));
+    assert!(matches!(error, CompileError::JumpTooFar { .. }), "{error}");
+  }
+
+  fn compile_chunk(source: &str) -> Chunk {
+    let mut ctx = Context::new();
+    compile(&Allocator::default(), &source.to_string(), &mut ctx)
+      .unwrap()
+      .chunk
+  }
+
+  #[test]
+  fn code_after_a_return_is_not_compiled() {
+    // `foo` isn't defined, compiling the call would fail
+    let chunk = compile_chunk("return 1; foo();");
+    assert_eq!(chunk.code, vec![opcode::OPCODE_CONST, 0, opcode::OPCODE_HALT]);
+  }
+
+  #[test]
+  fn unused_literal_statements_are_removed() {
+    let chunk = compile_chunk("1; 2; 3;");
+    assert_eq!(chunk.code, vec![opcode::OPCODE_HALT]);
+  }
+
+  #[test]
+  fn each_expression_statement_pops_its_value() {
+    let chunk = compile_chunk("let a = 1; let b = 2; a; b;");
+    let tail = &chunk.code[chunk.code.len() - 7..];
+    assert_eq!(
+      tail,
+      [
+        opcode::OPCODE_LOAD_GLOBAL_SCOPE,
+        1,
+        opcode::OPCODE_POP,
+        opcode::OPCODE_LOAD_GLOBAL_SCOPE,
+        2,
+        opcode::OPCODE_POP,
+        opcode::OPCODE_HALT
+      ]
+    );
+  }
+
+  #[test]
+  fn patched_jumps_match_the_hand_written_bytecode() {
+    let chunk = compile_chunk("let a = true, b = false; if (a) { a; } else { b; }");
+    #[rustfmt::skip]
+    let expected = vec![
+      opcode::OPCODE_CONST, 0,
+      opcode::OPCODE_SET_GLOBAL_POP, 1,
+      opcode::OPCODE_CONST, 1,
+      opcode::OPCODE_SET_GLOBAL_POP, 2,
+      opcode::OPCODE_LOAD_GLOBAL_SCOPE, 1,
+      // to the else branch at 19
+      opcode::OPCODE_JUMP_IF_FALSE, 6, 0,
+      opcode::OPCODE_LOAD_GLOBAL_SCOPE, 1,
+      opcode::OPCODE_POP,
+      // over the else branch to the HALT at 22
+      opcode::OPCODE_JUMP, 3, 0,
+      opcode::OPCODE_LOAD_GLOBAL_SCOPE, 2,
+      opcode::OPCODE_POP,
+      opcode::OPCODE_HALT,
+    ];
+    assert_eq!(chunk.code, expected);
+  }
+
+  #[test]
+  fn an_operator_split_over_lines_maps_to_the_second_line() {
+    let chunk = compile_chunk("let a = \"x\", b = \"y\"; a +\nb;");
+    let mut offset = 0;
+    while chunk.code[offset] != opcode::OPCODE_ADD {
+      offset += 1 + opcode_operands(chunk.code[offset]);
+    }
+    assert_eq!(chunk.line_for_offset(offset), Some(2));
+    assert_eq!(chunk.line_for_offset(0), Some(1));
+  }
+
+  #[test]
+  fn identical_strings_and_property_keys_share_one_constant() {
+    let chunk = compile_chunk(r#"let s = "key"; let t = "key"; let o = { key: 1 };"#);
+    let keys = chunk
+      .constants
+      .iter()
+      .filter(|constant| constant.as_string() == Some("key"));
+    assert_eq!(keys.count(), 1);
+  }
+
+  #[test]
+  fn outgrowing_the_constant_limit_is_a_compile_error() {
+    let source = format!(
+      "let a = [{}];",
+      (0..101).map(|n| n.to_string()).collect::<Vec<_>>().join(", ")
+    );
+    let allocator = Allocator::default();
+    let program = oxc_parser::Parser::new(&allocator, &source, SourceType::default())
+      .parse()
+      .program;
+    let mut ctx = Context::new();
+    let error = Compiler::new("main".to_string(), &source, &mut ctx)
+      .with_constant_limit(100)
+      .compile_program(&program)
+      .err();
+    assert_eq!(error, Some(CompileError::TooManyConstants { limit: 100 }));
+  }
+
+  #[test]
+  fn ten_thousand_literals_compile() {
+    let source = format!(
+      "let a = [{}];",
+      (0..10000).map(|n| n.to_string()).collect::<Vec<_>>().join(", ")
+    );
+    let chunk = compile_chunk(&source);
+    assert_eq!(chunk.constants.len(), 10000);
+  }
+
+  #[test]
+  fn nan_loads_the_builtin_global() {
+    let mut ctx = Context::with_builtins();
+    let chunk = compile(&Allocator::default(), &"NaN;".to_string(), &mut ctx)
+      .unwrap()
+      .chunk;
+    assert_eq!(chunk.code[0], opcode::OPCODE_LOAD_GLOBAL_SCOPE);
+    assert_eq!(ctx.get_variable_name(chunk.code[1] as usize), "NaN");
+  }
+
+  #[test]
+  fn this_compiles_to_load_this() {
+    let chunk = compile_chunk("let o = this;");
+    assert_eq!(chunk.code[0], opcode::OPCODE_LOAD_THIS);
+  }
+
+  #[test]
+  fn expression_statements_keep_the_stack_bounded() {
+    let chunk = compile_chunk(&format!("let a = 1; {}", "a;".repeat(10000)));
+    assert!(chunk.max_stack <= 2, "max_stack is {}", chunk.max_stack);
+  }
+
+  #[test]
+  fn a_sequence_evaluates_to_its_last_expression() {
+    let mut ctx = Context::new();
+    assert_eq!(
+      Engine::bootstrap_repl(&mut ctx, &"(1, 2, 3)".to_string()).unwrap(),
+      Value::create_number_value(3.0)
+    );
+    let mut ctx = Context::new();
+    let source = "let a = 1, b = 2; (a, b, 3)".to_string();
+    let chunk = compile_repl(&Allocator::default(), &source, &mut ctx).unwrap().chunk;
+    #[rustfmt::skip]
+    let expected = vec![
+      opcode::OPCODE_CONST, 0,
+      opcode::OPCODE_SET_GLOBAL_POP, 1,
+      opcode::OPCODE_CONST, 1,
+      opcode::OPCODE_SET_GLOBAL_POP, 2,
+      opcode::OPCODE_LOAD_GLOBAL_SCOPE, 1,
+      opcode::OPCODE_POP,
+      opcode::OPCODE_LOAD_GLOBAL_SCOPE, 2,
+      opcode::OPCODE_POP,
+      opcode::OPCODE_CONST, 2,
+      opcode::OPCODE_HALT,
+    ];
+    assert_eq!(chunk.code, expected);
+  }
+
+  #[test]
+  fn parentheses_compile_like_the_inner_expression() {
+    let parenthesized = compile_chunk("let a = 1, b = 2; let c = (a + b);");
+    let bare = compile_chunk("let a = 1, b = 2; let c = a + b;");
+    assert_eq!(parenthesized.code, bare.code);
+  }
+
+  #[test]
+  fn number_constants_dedup_by_bit_pattern() {
+    let chunk = compile_chunk("let a = 0 / 0; let b = 0 / 0;");
+    let nans = chunk
+      .constants
+      .iter()
+      .filter(|constant| constant.as_number().is_some_and(f64::is_nan));
+    assert_eq!(nans.count(), 1);
+    let chunk = compile_chunk("let a = 0; let b = -0;");
+    let zeros = chunk
+      .constants
+      .iter()
+      .filter(|constant| constant.as_number() == Some(0.0));
+    assert_eq!(zeros.count(), 2);
+  }
+
+  #[test]
+  fn hex_and_binary_literals_are_decoded() {
+    let chunk = compile_chunk("let a = 0xff; let b = 0b1010;");
+    assert_eq!(chunk.constants[0], Value::create_number_value(255.0));
+    assert_eq!(chunk.constants[1], Value::create_number_value(10.0));
+  }
+
+  #[test]
+  fn using_a_binding_before_its_declaration_is_a_compile_error() {
+    let used_before = |name: &str| CompileError::UsedBeforeDeclaration { name: name.to_string() };
+    assert_eq!(compile_error("x; let x = 1;".to_string()), used_before("x"));
+    assert_eq!(
+      compile_error("let x = 1; { x; let x = 2; }".to_string()),
+      used_before("x")
+    );
+    assert_eq!(compile_error("let x = x;".to_string()), used_before("x"));
+  }
+
+  #[test]
+  fn a_declarator_sees_the_ones_before_it() {
+    assert_eq!(run("let a = 1, b = a + 1; b"), Value::create_number_value(2.0));
+  }
+
+  #[test]
+  fn a_var_in_a_block_is_visible_after_it() {
+    assert_eq!(run("{ var x = 1; } x"), Value::create_number_value(1.0));
+  }
+
+  #[test]
+  #[should_panic(expected = "y is not defined")]
+  fn a_let_in_a_block_is_not_visible_after_it() {
+    run("{ let y = 1; } y");
   }
 }
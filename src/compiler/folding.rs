@@ -0,0 +1,124 @@
+use oxc_ast::ast;
+
+use crate::values::{NumberValue, Value};
+
+// a literal the compiler could evaluate ahead of time
+#[derive(Debug, Clone, PartialEq)]
+pub enum Folded {
+  Number(f64),
+  String(String),
+  Boolean(bool),
+}
+
+// evaluates expressions built only from number/string/boolean literals,
+// anything touching an identifier or a call is left to the vm (returns None)
+pub fn fold_expression(expression: &ast::Expression) -> Option<Folded> {
+  match expression {
+    ast::Expression::NumericLiteral(literal) => Some(Folded::Number(literal.value)),
+    ast::Expression::StringLiteral(literal) => Some(Folded::String(literal.value.to_string())),
+    ast::Expression::BooleanLiteral(literal) => Some(Folded::Boolean(literal.value)),
+    ast::Expression::BinaryExpression(binary) => fold_binary_expression(binary),
+    ast::Expression::UnaryExpression(unary) => fold_unary_expression(unary),
+    ast::Expression::ParenthesizedExpression(parenthesized) => fold_expression(&parenthesized.expression),
+    _ => None,
+  }
+}
+
+pub fn fold_binary_expression(binary: &ast::BinaryExpression) -> Option<Folded> {
+  let left = fold_expression(&binary.left)?;
+  let right = fold_expression(&binary.right)?;
+  match binary.operator.as_str() {
+    "+" => fold_addition(left, right),
+    "-" => Some(Folded::Number(to_number(&left)? - to_number(&right)?)),
+    "*" => Some(Folded::Number(to_number(&left)? * to_number(&right)?)),
+    // division by zero is Infinity (or NaN for 0 / 0) in js, f64 already does that
+    "/" => Some(Folded::Number(to_number(&left)? / to_number(&right)?)),
+    "===" => Some(Folded::Boolean(strict_equals(&left, &right))),
+    _ => None,
+  }
+}
+
+pub fn fold_unary_expression(unary: &ast::UnaryExpression) -> Option<Folded> {
+  let argument = fold_expression(&unary.argument)?;
+  match unary.operator.as_str() {
+    "-" => Some(Folded::Number(-to_number(&argument)?)),
+    "+" => Some(Folded::Number(to_number(&argument)?)),
+    "!" => Some(Folded::Boolean(!is_truthy(&argument))),
+    _ => None,
+  }
+}
+
+// `+` concatenates as soon as one side is a string, otherwise it adds
+fn fold_addition(left: Folded, right: Folded) -> Option<Folded> {
+  match (&left, &right) {
+    (Folded::String(_), _) | (_, Folded::String(_)) => Some(Folded::String(to_string(&left)? + &to_string(&right)?)),
+    _ => Some(Folded::Number(to_number(&left)? + to_number(&right)?)),
+  }
+}
+
+// parsing strings as numbers is left to the vm
+fn to_number(value: &Folded) -> Option<f64> {
+  match value {
+    Folded::Number(number) => Some(*number),
+    Folded::Boolean(boolean) => Some(if *boolean { 1.0 } else { 0.0 }),
+    Folded::String(_) => None,
+  }
+}
+
+fn to_string(value: &Folded) -> Option<String> {
+  match value {
+    Folded::String(string) => Some(string.clone()),
+    Folded::Boolean(boolean) => Some(boolean.to_string()),
+    Folded::Number(number) => Some(NumberValue::new(*number).to_js_string()),
+  }
+}
+
+fn strict_equals(left: &Folded, right: &Folded) -> bool {
+  match (left, right) {
+    (Folded::Number(left), Folded::Number(right)) => left == right,
+    (Folded::String(left), Folded::String(right)) => left == right,
+    (Folded::Boolean(left), Folded::Boolean(right)) => left == right,
+    _ => false,
+  }
+}
+
+// same answer the vm gives at runtime
+fn is_truthy(value: &Folded) -> bool {
+  let value = match value {
+    Folded::Number(number) => Value::create_number_value(*number),
+    Folded::String(string) => Value::create_string_value(string.clone()),
+    Folded::Boolean(boolean) => Value::create_boolean_value(*boolean),
+  };
+  value.to_boolean()
+}
+
+#[cfg(test)]
+mod tests {
+  use oxc_allocator::Allocator;
+
+  use crate::bytecode::opcode;
+  use crate::compiler::compile;
+  use crate::context::Context;
+  use crate::values::Value;
+
+  #[test]
+  fn string_concatenation_folds_to_one_constant() {
+    let mut ctx = Context::new();
+    let source = r#"let s = "a" + "b" + "c";"#.to_string();
+    let compiled = compile(&Allocator::default(), &source, &mut ctx).unwrap();
+    assert_eq!(
+      compiled.chunk.constants,
+      vec![Value::create_string_value("abc".to_string())]
+    );
+    assert_eq!(compiled.chunk.code[0], opcode::OPCODE_CONST);
+    assert!(!compiled.chunk.code.contains(&opcode::OPCODE_ADD));
+  }
+
+  #[test]
+  fn concatenation_with_an_identifier_is_left_to_the_vm() {
+    let mut ctx = Context::new();
+    let source = r#"let a = "a"; let s = a + "b";"#.to_string();
+    let compiled = compile(&Allocator::default(), &source, &mut ctx).unwrap();
+    assert!(compiled.chunk.code.contains(&opcode::OPCODE_ADD));
+  }
+}
@@ -0,0 +1,40 @@
+use oxc_ast::ast;
+use oxc_ast::syntax_directed_operations::BoundNames;
+use oxc_ast::Visit;
+use oxc_syntax::scope::ScopeFlags;
+
+// the names `var` declares in `statements`, in any block but not inside nested functions and classes, which
+// hoist their own. every one of them is a binding of the function (or program) the statements are the body of
+pub fn var_names(statements: &[ast::Statement]) -> Vec<String> {
+  let mut names = VarNames { names: vec![] };
+  for statement in statements {
+    names.visit_statement(statement);
+  }
+  names.names
+}
+
+struct VarNames {
+  names: Vec<String>,
+}
+
+impl<'a> Visit<'a> for VarNames {
+  // an initializer can't declare a `var` outside a function, so it isn't walked
+  fn visit_variable_declaration(&mut self, declaration: &ast::VariableDeclaration<'a>) {
+    if !declaration.kind.is_var() {
+      return;
+    }
+    let names = &mut self.names;
+    declaration.bound_names(&mut |identifier| {
+      // `var x` can be repeated, it's still one binding
+      if !names.iter().any(|name| name == identifier.name.as_str()) {
+        names.push(identifier.name.to_string());
+      }
+    });
+  }
+
+  fn visit_function(&mut self, _: &ast::Function<'a>, _: Option<ScopeFlags>) {}
+
+  fn visit_arrow_expression(&mut self, _: &ast::ArrowFunctionExpression<'a>) {}
+
+  fn visit_class(&mut self, _: &ast::Class<'a>) {}
+}
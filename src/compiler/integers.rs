@@ -0,0 +1,150 @@
+use std::collections::{HashMap, HashSet};
+
+use oxc_ast::ast::{self, Program};
+use oxc_ast::visit::walk;
+use oxc_ast::Visit;
+
+// integer literals beyond this stay on the float path, so the sums and products of a few of them
+// are still far from overflowing i64
+const MAX_INTEGER_LITERAL: f64 = i32::MAX as f64;
+
+// the variables every write in the program sets to an integer: a declaration or `=` whose value is an
+// integer literal, or `+`, `-` or `*` of integers. a variable declared without a value, bound as a
+// parameter or loop variable, or assigned anything else isn't one. names aren't resolved to scopes,
+// so a name is only integral when it is everywhere it's written.
+// the vm checks the operands of the integer opcodes anyway, this only decides when emitting them pays off
+pub fn integer_variables(program: &Program) -> HashSet<String> {
+  // optimistic start: every written name, then drop the ones with a write that isn't integral
+  // under the current assumption until nothing changes
+  let mut integral: HashSet<String> = collect_writes(program, &HashSet::new()).into_keys().collect();
+  loop {
+    let writes = collect_writes(program, &integral);
+    let next: HashSet<String> = writes
+      .into_iter()
+      .filter(|(_, all_integral)| *all_integral)
+      .map(|(name, _)| name)
+      .collect();
+    if next == integral {
+      return integral;
+    }
+    integral = next;
+  }
+}
+
+// whether `expression` evaluates to an integer when the variables in `integral` hold integers
+pub fn is_integral(expression: &ast::Expression, integral: &HashSet<String>) -> bool {
+  match expression {
+    ast::Expression::NumericLiteral(literal) => literal.value.fract() == 0.0 && literal.value <= MAX_INTEGER_LITERAL,
+    ast::Expression::Identifier(identifier) => integral.contains(identifier.name.as_str()),
+    ast::Expression::ParenthesizedExpression(parenthesized) => is_integral(&parenthesized.expression, integral),
+    ast::Expression::BinaryExpression(binary) => {
+      matches!(binary.operator.as_str(), "+" | "-" | "*")
+        && is_integral(&binary.left, integral)
+        && is_integral(&binary.right, integral)
+    }
+    _ => false,
+  }
+}
+
+// every written name, and whether all of its writes are integral assuming `integral`
+fn collect_writes(program: &Program, integral: &HashSet<String>) -> HashMap<String, bool> {
+  let mut writes = Writes { integral, names: HashMap::new() };
+  writes.visit_program(program);
+  writes.names
+}
+
+struct Writes<'s> {
+  integral: &'s HashSet<String>,
+  names: HashMap<String, bool>,
+}
+
+impl<'s> Writes<'s> {
+  fn write(&mut self, name: &str, integral: bool) {
+    let all_integral = self.names.entry(name.to_string()).or_insert(true);
+    *all_integral &= integral;
+  }
+}
+
+impl<'a, 's> Visit<'a> for Writes<'s> {
+  fn visit_variable_declarator(&mut self, declarator: &ast::VariableDeclarator<'a>) {
+    let ast::BindingPatternKind::BindingIdentifier(identifier) = &declarator.id.kind else {
+      return walk::walk_variable_declarator(self, declarator);
+    };
+    let integral = declarator
+      .init
+      .as_ref()
+      .is_some_and(|init| is_integral(init, self.integral));
+    self.write(&identifier.name, integral);
+    if let Some(init) = &declarator.init {
+      self.visit_expression(init);
+    }
+  }
+
+  fn visit_assignment_expression(&mut self, assignment: &ast::AssignmentExpression<'a>) {
+    if let ast::AssignmentTarget::SimpleAssignmentTarget(ast::SimpleAssignmentTarget::AssignmentTargetIdentifier(
+      identifier,
+    )) = &assignment.left
+    {
+      if assignment.operator.as_str() == "=" {
+        let integral = is_integral(&assignment.right, self.integral);
+        self.write(&identifier.name, integral);
+        return self.visit_expression(&assignment.right);
+      }
+    }
+    walk::walk_assignment_expression(self, assignment);
+  }
+
+  // compound assignments, `for (x of ...)` and the like
+  fn visit_simple_assignment_target(&mut self, target: &ast::SimpleAssignmentTarget<'a>) {
+    if let ast::SimpleAssignmentTarget::AssignmentTargetIdentifier(identifier) = target {
+      self.write(&identifier.name, false);
+    }
+    walk::walk_simple_assignment_target(self, target);
+  }
+
+  // parameters, function names and other bindings that don't come from an integer initializer
+  fn visit_binding_identifier(&mut self, identifier: &ast::BindingIdentifier<'a>) {
+    self.write(&identifier.name, false);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::integer_variables;
+  use crate::bytecode::opcode;
+  use crate::compiler::compile;
+  use crate::context::Context;
+  use crate::disassembler::Disassembler;
+  use oxc_allocator::Allocator;
+  use oxc_span::SourceType;
+  use std::collections::HashSet;
+
+  fn integers_of(source: &str) -> HashSet<String> {
+    let allocator = Allocator::default();
+    let program = oxc_parser::Parser::new(&allocator, source, SourceType::default())
+      .parse()
+      .program;
+    integer_variables(&program)
+  }
+
+  #[test]
+  fn only_names_every_write_keeps_integral_are_integers() {
+    let source = "let a = 1; let b = a + 2; let c = 1.5; let d = 1; d = d * c; let e = b * 3; let f;";
+    let expected: HashSet<String> = ["a", "b", "e"].iter().map(|name| name.to_string()).collect();
+    assert_eq!(integers_of(source), expected);
+  }
+
+  #[test]
+  fn integral_operands_compile_to_add_int() {
+    let mut ctx = Context::new();
+    let source = "let a = 1, b = 2, h = 0.5; let c = a + b; let d = a + h;".to_string();
+    let chunk = compile(&Allocator::default(), &source, &mut ctx).unwrap().chunk;
+    let opcodes: Vec<u8> = Disassembler::new(&chunk, "main", &ctx)
+      .instructions()
+      .iter()
+      .map(|instruction| instruction.opcode as u8)
+      .collect();
+    assert!(opcodes.contains(&opcode::OPCODE_ADD_INT), "{opcodes:?}");
+    assert!(opcodes.contains(&opcode::OPCODE_ADD), "{opcodes:?}");
+  }
+}
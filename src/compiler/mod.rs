@@ -1,14 +1,40 @@
 use oxc_allocator::Allocator;
 use oxc_span::SourceType;
 pub mod compiler;
+mod folding;
+mod hoisting;
+mod integers;
+mod peephole;
+mod stack_depth;
 use crate::context::Context;
+use crate::errors::CompileError;
 use compiler::Compiler;
 
 use self::compiler::CompilerReturn;
 
-pub fn compile(arena_allocator: &Allocator, source: &String, ctx: &mut Context) -> CompilerReturn {
+pub fn compile(
+  arena_allocator: &Allocator,
+  source: &String,
+  ctx: &mut Context,
+) -> Result<CompilerReturn, CompileError> {
   let source_type = SourceType::default().with_module(true).with_typescript(true);
   let parser = oxc_parser::Parser::new(&arena_allocator, source, source_type);
   let result = parser.parse();
-  Compiler::compile(&result.program, ctx)
+  Compiler::compile(&result.program, source, ctx)
+}
+
+// like `compile`, but a final expression statement isn't popped, so the program halts with its value.
+// every line of the repl is compiled this way against the same context
+pub fn compile_repl(
+  arena_allocator: &Allocator,
+  source: &String,
+  ctx: &mut Context,
+) -> Result<CompilerReturn, CompileError> {
+  let source_type = SourceType::default().with_module(true).with_typescript(true);
+  let parser = oxc_parser::Parser::new(&arena_allocator, source, source_type);
+  let result = parser.parse();
+  Compiler::new("main".to_string(), source, ctx)
+    .with_last_value(true)
+    .with_integer_arithmetic(true)
+    .compile_program(&result.program)
 }
@@ -0,0 +1,241 @@
+use crate::bytecode::chunk::Chunk;
+use crate::bytecode::opcode;
+use crate::utils::{is_jump, jump_target, opcode_operands, patch_jump_target};
+use std::collections::HashSet;
+
+// removes `CONST n; POP` pairs and jumps to the very next instruction,
+// fixing up jump targets and the line table after every round.
+pub fn optimize(chunk: &mut Chunk) {
+  while remove_noops(&mut chunk.code, &mut chunk.lines) {}
+}
+
+// replaces pairs of instructions that keep showing up together with the superinstruction doing both, so the
+// vm dispatches once for them. the operand of the first instruction becomes the operand of the fused one
+pub fn fuse(chunk: &mut Chunk) {
+  let code = &chunk.code;
+  let offsets = instruction_offsets(code);
+  let targets = jump_targets(code, &offsets);
+  let mut replacements = vec![None; offsets.len()];
+  let mut changed = false;
+  let mut index = 0;
+  while index + 1 < offsets.len() {
+    let (offset, next) = (offsets[index], offsets[index + 1]);
+    // a jump to the second instruction still needs it on its own
+    let fused = if targets.contains(&next) {
+      None
+    } else {
+      fused_opcode(code[offset], code[next])
+    };
+    let Some(fused) = fused else {
+      index += 1;
+      continue;
+    };
+    let mut instruction = vec![fused];
+    instruction.extend_from_slice(&code[offset + 1..next]);
+    replacements[index] = Some(instruction);
+    replacements[index + 1] = Some(vec![]);
+    changed = true;
+    index += 2;
+  }
+  if changed {
+    rewrite(&mut chunk.code, &mut chunk.lines, &offsets, &replacements);
+  }
+}
+
+// the superinstruction for `first` followed by `second`, only narrow operands are fused
+fn fused_opcode(first: u8, second: u8) -> Option<u8> {
+  match (first, second) {
+    // ADD_CONST takes the integer path first, like ADD_INT, and ends up adding like ADD otherwise
+    (opcode::OPCODE_CONST, opcode::OPCODE_ADD | opcode::OPCODE_ADD_INT) => Some(opcode::OPCODE_ADD_CONST),
+    (opcode::OPCODE_CONST, opcode::OPCODE_LT) => Some(opcode::OPCODE_LT_CONST),
+    (opcode::OPCODE_SET_LOCAL, opcode::OPCODE_POP) => Some(opcode::OPCODE_SET_LOCAL_POP),
+    (opcode::OPCODE_SET_GLOBAL_SCOPE, opcode::OPCODE_POP) => Some(opcode::OPCODE_SET_GLOBAL_POP),
+    _ => None,
+  }
+}
+
+fn remove_noops(code: &mut Vec<u8>, lines: &mut Vec<(usize, u32)>) -> bool {
+  let offsets = instruction_offsets(code);
+  let targets = jump_targets(code, &offsets);
+  let mut replacements = vec![None; offsets.len()];
+  let mut changed = false;
+  let mut index = 0;
+  while index < offsets.len() {
+    let offset = offsets[index];
+    let opcode = code[offset];
+    if is_constant(opcode) && index + 1 < offsets.len() {
+      let next = offsets[index + 1];
+      // a pop that something jumps to is still needed by that path
+      if code[next] == opcode::OPCODE_POP && !targets.contains(&next) {
+        replacements[index] = Some(vec![]);
+        replacements[index + 1] = Some(vec![]);
+        changed = true;
+        index += 2;
+        continue;
+      }
+    }
+    if opcode == opcode::OPCODE_JUMP && jump_target(code, offset) == Some(offset + 1 + opcode_operands(opcode)) {
+      replacements[index] = Some(vec![]);
+      changed = true;
+    }
+    index += 1;
+  }
+  if changed {
+    rewrite(code, lines, &offsets, &replacements);
+  }
+  changed
+}
+
+// copies `code` with every instruction that has a replacement swapped for it (an empty one removes it),
+// then moves jump targets and the line table to the new offsets
+fn rewrite(code: &mut Vec<u8>, lines: &mut Vec<(usize, u32)>, offsets: &[usize], replacements: &[Option<Vec<u8>>]) {
+  // old offset -> new offset, removed instructions relocate to whatever follows them
+  let mut relocations = vec![0; code.len() + 1];
+  let mut optimized = Vec::with_capacity(code.len());
+  // (new offset, old target) of every kept jump, distances are recomputed once everything moved
+  let mut jumps = vec![];
+  for (index, &offset) in offsets.iter().enumerate() {
+    let end = (offset + 1 + opcode_operands(code[offset])).min(code.len());
+    relocations[offset..end].fill(optimized.len());
+    match &replacements[index] {
+      Some(replacement) => optimized.extend_from_slice(replacement),
+      None => {
+        if is_jump(code[offset]) {
+          if let Some(target) = jump_target(code, offset) {
+            jumps.push((optimized.len(), target));
+          }
+        }
+        optimized.extend_from_slice(&code[offset..end]);
+      }
+    }
+  }
+  relocations[code.len()] = optimized.len();
+
+  // code only shrinks, so a distance that fit before still fits
+  for (offset, target) in jumps {
+    if target < relocations.len() && offset + 1 + opcode_operands(optimized[offset]) <= optimized.len() {
+      patch_jump_target(&mut optimized, offset, relocations[target]);
+    }
+  }
+
+  let mut relocated_lines: Vec<(usize, u32)> = Vec::with_capacity(lines.len());
+  for &(offset, line) in lines.iter() {
+    let offset = relocations[offset.min(code.len())];
+    if let Some(last) = relocated_lines.last_mut() {
+      if last.0 == offset {
+        *last = (offset, line);
+        continue;
+      }
+    }
+    relocated_lines.push((offset, line));
+  }
+  relocated_lines.dedup_by(|current, previous| current.1 == previous.1);
+
+  *code = optimized;
+  *lines = relocated_lines;
+}
+
+fn instruction_offsets(code: &[u8]) -> Vec<usize> {
+  let mut offsets = vec![];
+  let mut offset = 0;
+  while offset < code.len() {
+    offsets.push(offset);
+    offset += 1 + opcode_operands(code[offset]);
+  }
+  offsets
+}
+
+fn jump_targets(code: &[u8], offsets: &[usize]) -> HashSet<usize> {
+  let mut targets = HashSet::new();
+  for &offset in offsets {
+    if is_jump(code[offset]) {
+      if let Some(target) = jump_target(code, offset) {
+        targets.insert(target);
+      }
+    }
+  }
+  targets
+}
+
+fn is_constant(opcode: u8) -> bool {
+  opcode == opcode::OPCODE_CONST || opcode == opcode::OPCODE_CONST_LONG
+}
+
+#[cfg(test)]
+mod tests {
+  use oxc_allocator::Allocator;
+
+  use super::fuse;
+  use crate::bytecode::chunk::Chunk;
+  use crate::bytecode::opcode;
+  use crate::compiler::compile;
+  use crate::context::Context;
+  use crate::utils::patch_jump_target;
+  use crate::values::Value;
+
+  fn compile_chunk(source: &str) -> Chunk {
+    let mut ctx = Context::new();
+    compile(&Allocator::default(), &source.to_string(), &mut ctx)
+      .unwrap()
+      .chunk
+  }
+
+  #[test]
+  fn an_increment_of_a_global_fuses_to_add_const_and_set_global_pop() {
+    let chunk = compile_chunk("let x = 0; x = x + 1;");
+    let tail = &chunk.code[chunk.code.len() - 7..];
+    assert_eq!(tail[0], opcode::OPCODE_LOAD_GLOBAL_SCOPE);
+    assert_eq!(tail[2], opcode::OPCODE_ADD_CONST);
+    assert_eq!(tail[4], opcode::OPCODE_SET_GLOBAL_POP);
+    assert_eq!(tail[6], opcode::OPCODE_HALT);
+  }
+
+  #[test]
+  fn an_increment_of_a_local_fuses_to_add_const_and_set_local_pop() {
+    let chunk = compile_chunk("function f() { let x = 0; x = x + 1; }");
+    let Some(Value::Function(function)) = chunk.constants.first() else {
+      panic!("expected the function as the first constant");
+    };
+    let code = &function.get_chunk().code;
+    assert!(code.contains(&opcode::OPCODE_ADD_CONST));
+    assert!(code.contains(&opcode::OPCODE_SET_LOCAL_POP));
+    assert!(!code.contains(&opcode::OPCODE_POP));
+  }
+
+  #[test]
+  fn a_comparison_with_a_constant_fuses_to_lt_const() {
+    let chunk = compile_chunk("let i = 0; i < 10;");
+    assert!(chunk.code.contains(&opcode::OPCODE_LT_CONST));
+    assert!(!chunk.code.contains(&opcode::OPCODE_LT));
+  }
+
+  #[test]
+  fn a_pair_whose_second_instruction_is_a_jump_target_stays_apart() {
+    // CONST #0, JUMP_IF_FALSE to the ADD, CONST #1, ADD
+    let mut chunk = Chunk::new();
+    for byte in [
+      opcode::OPCODE_CONST,
+      0,
+      opcode::OPCODE_JUMP_IF_FALSE,
+      0,
+      0,
+      opcode::OPCODE_CONST,
+      1,
+      opcode::OPCODE_ADD,
+    ] {
+      chunk.write(byte, 1);
+    }
+    assert!(patch_jump_target(&mut chunk.code, 2, 7));
+    let unfused = chunk.code.clone();
+    fuse(&mut chunk);
+    assert_eq!(chunk.code, unfused);
+
+    // the same pair without the jump is fused
+    let mut chunk = Chunk::new();
+    for byte in [opcode::OPCODE_CONST, 0, opcode::OPCODE_CONST, 1, opcode::OPCODE_ADD] {
+      chunk.write(byte, 1);
+    }
+    fuse(&mut chunk);
+    assert_eq!(chunk.code, vec![opcode::OPCODE_CONST, 0, opcode::OPCODE_ADD_CONST, 1]);
+  }
+}
@@ -0,0 +1,165 @@
+use crate::bytecode::chunk::Chunk;
+use crate::bytecode::opcode;
+use crate::errors::StackError;
+use crate::utils::{decode_operand, jump_target, opcode_operands};
+
+// deepest the operand stack gets while running `chunk`, counted from the first slot the chunk owns
+// (after the arguments of a function). every path reaching an instruction must agree on the depth there.
+pub fn max_stack(chunk: &Chunk) -> Result<usize, StackError> {
+  let code = &chunk.code;
+  let mut depths: Vec<Option<usize>> = vec![None; code.len()];
+  let mut pending = vec![(0, 0)];
+  let mut max = 0;
+  while let Some((offset, depth)) = pending.pop() {
+    if offset >= code.len() {
+      continue;
+    }
+    match depths[offset] {
+      Some(known) if known == depth => continue,
+      Some(known) => return Err(StackError::Unbalanced { offset, expected: known, found: depth }),
+      None => depths[offset] = Some(depth),
+    }
+    let instruction = code[offset];
+    let width = opcode_operands(instruction);
+    let operand = decode_operand(code, offset + 1, width).unwrap_or(0);
+    let (pops, pushes) = stack_effect(instruction, operand);
+    let Some(remaining) = depth.checked_sub(pops) else {
+      return Err(StackError::Underflow { offset, depth, pops });
+    };
+    let depth = remaining + pushes;
+    max = max.max(depth);
+    match instruction {
+      opcode::OPCODE_HALT | opcode::OPCODE_RETURN | opcode::OPCODE_THROW => {}
+      opcode::OPCODE_JUMP => pending.extend(jump_target(code, offset).map(|target| (target, depth))),
+      opcode::OPCODE_JUMP_IF_FALSE => {
+        pending.extend(jump_target(code, offset).map(|target| (target, depth)));
+        pending.push((offset + 1 + width, depth));
+      }
+      // the catch block starts with the caught value on top of the stack the try block started with
+      opcode::OPCODE_SETUP_TRY => {
+        pending.extend(jump_target(code, offset).map(|target| (target, depth + 1)));
+        pending.push((offset + 1 + width, depth));
+      }
+      // the element is only pushed when there is one, the jump out leaves the stack as it was
+      opcode::OPCODE_ITERATE_NEXT => {
+        pending.extend(jump_target(code, offset).map(|target| (target, depth - 1)));
+        pending.push((offset + 1 + width, depth));
+      }
+      _ => pending.push((offset + 1 + width, depth)),
+    }
+  }
+  Ok(max)
+}
+
+// (values popped, values pushed) by one instruction
+fn stack_effect(instruction: u8, operand: usize) -> (usize, usize) {
+  match instruction {
+    opcode::OPCODE_CONST
+    | opcode::OPCODE_CONST_LONG
+    | opcode::OPCODE_LOAD_GLOBAL_SCOPE
+    | opcode::OPCODE_LOAD_GLOBAL_SCOPE_LONG
+    | opcode::OPCODE_GET_LOCAL
+    | opcode::OPCODE_LOAD_ARGUMENT
+    | opcode::OPCODE_LOAD_THIS
+    | opcode::OPCODE_NEW_OBJECT => (0, 1),
+    opcode::OPCODE_DUP => (1, 2),
+    opcode::OPCODE_SWAP => (2, 2),
+    // the iterated value stays, the index goes on top of it
+    opcode::OPCODE_ITERATE => (1, 2),
+    opcode::OPCODE_ITERATE_NEXT => (0, 1),
+    opcode::OPCODE_ADD
+    | opcode::OPCODE_SUB
+    | opcode::OPCODE_MUL
+    | opcode::OPCODE_ADD_INT
+    | opcode::OPCODE_SUB_INT
+    | opcode::OPCODE_MUL_INT
+    | opcode::OPCODE_DIV
+    | opcode::OPCODE_MOD
+    | opcode::OPCODE_AND
+    | opcode::OPCODE_OR
+    | opcode::OPCODE_XOR
+    | opcode::OPCODE_SHL
+    | opcode::OPCODE_SHR
+    | opcode::OPCODE_LT
+    | opcode::OPCODE_LE
+    | opcode::OPCODE_GT
+    | opcode::OPCODE_GE
+    | opcode::OPCODE_EQ
+    | opcode::OPCODE_WEAK_EQ
+    | opcode::OPCODE_NE
+    | opcode::OPCODE_INSTANCEOF
+    | opcode::OPCODE_GET_ELEMENT
+    | opcode::OPCODE_INIT_PROPERTY
+    | opcode::OPCODE_SET_PROPERTY => (2, 1),
+    opcode::OPCODE_SET_ELEMENT => (3, 1),
+    opcode::OPCODE_NEG | opcode::OPCODE_NOT | opcode::OPCODE_GET_PROPERTY | opcode::OPCODE_ENUMERATE => (1, 1),
+    // the constant is pushed while adding, the chunk's bound is taken before fusing to cover it
+    opcode::OPCODE_ADD_CONST | opcode::OPCODE_LT_CONST => (1, 1),
+    // stores leave the assigned value on the stack
+    opcode::OPCODE_SET_GLOBAL_SCOPE
+    | opcode::OPCODE_SET_GLOBAL_SCOPE_LONG
+    | opcode::OPCODE_SET_LOCAL
+    | opcode::OPCODE_SET_ARGUMENT => (1, 1),
+    opcode::OPCODE_POP
+    | opcode::OPCODE_SET_LOCAL_POP
+    | opcode::OPCODE_SET_GLOBAL_POP
+    | opcode::OPCODE_JUMP_IF_FALSE
+    | opcode::OPCODE_RETURN
+    | opcode::OPCODE_THROW => (1, 0),
+    // the callee and its arguments are replaced by the result
+    opcode::OPCODE_CALL | opcode::OPCODE_NEW => (operand + 1, 1),
+    // the receiver sits below the callee
+    opcode::OPCODE_CALL_METHOD => (operand + 2, 1),
+    opcode::OPCODE_NEW_ARRAY => (operand, 1),
+    _ => (0, 0),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::max_stack;
+  use crate::bytecode::chunk::Chunk;
+  use crate::bytecode::opcode;
+  use crate::errors::StackError;
+
+  fn chunk(code: Vec<u8>) -> Chunk {
+    let mut chunk = Chunk::new();
+    chunk.code = code;
+    chunk
+  }
+
+  #[test]
+  fn max_stack_is_the_deepest_point() {
+    #[rustfmt::skip]
+    let code = vec![
+      opcode::OPCODE_CONST, 0,
+      opcode::OPCODE_CONST, 0,
+      opcode::OPCODE_CONST, 0,
+      opcode::OPCODE_ADD,
+      opcode::OPCODE_ADD,
+      opcode::OPCODE_HALT,
+    ];
+    assert_eq!(max_stack(&chunk(code)), Ok(3));
+  }
+
+  #[test]
+  fn branches_reaching_one_offset_with_different_depths_are_unbalanced() {
+    #[rustfmt::skip]
+    let code = vec![
+      opcode::OPCODE_CONST, 0,
+      // to the HALT at 7 with nothing on the stack
+      opcode::OPCODE_JUMP_IF_FALSE, 2, 0,
+      opcode::OPCODE_CONST, 0,
+      opcode::OPCODE_HALT,
+    ];
+    assert!(matches!(
+      max_stack(&chunk(code)),
+      Err(StackError::Unbalanced { offset: 7, .. })
+    ));
+    let code = vec![opcode::OPCODE_POP, opcode::OPCODE_HALT];
+    assert_eq!(
+      max_stack(&chunk(code)),
+      Err(StackError::Underflow { offset: 0, depth: 0, pops: 1 })
+    );
+  }
+}
@@ -0,0 +1,60 @@
+use std::cell::RefCell;
+
+// an inline cache for each LOAD_GLOBAL_SCOPE of a chunk: the global slot the load resolved to and the
+// generation of the context's variable table it was resolved in. defining a variable starts a new
+// generation, so every entry has to be resolved again before it's trusted
+// entries are indexed by the offset of the load, so a hit costs no hashing
+#[derive(Debug, Default)]
+pub struct GlobalCache {
+  entries: RefCell<Vec<Option<(u64, usize)>>>,
+}
+
+impl GlobalCache {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  // the cached slot when the load at `offset` was resolved in this `generation`
+  pub fn get(&self, offset: usize, generation: u64) -> Option<usize> {
+    let (cached, slot) = (*self.entries.borrow().get(offset)?)?;
+    (cached == generation).then_some(slot)
+  }
+
+  pub fn set(&self, offset: usize, generation: u64, slot: usize) {
+    let mut entries = self.entries.borrow_mut();
+    if entries.len() <= offset {
+      entries.resize(offset + 1, None);
+    }
+    entries[offset] = Some((generation, slot));
+  }
+}
+
+// like the property cache, a copy starts empty and two chunks don't differ by what they've cached
+impl Clone for GlobalCache {
+  fn clone(&self) -> Self {
+    Self::default()
+  }
+}
+
+impl PartialEq for GlobalCache {
+  fn eq(&self, _: &Self) -> bool {
+    true
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::GlobalCache;
+
+  #[test]
+  fn the_global_cache_hits_only_in_the_generation_it_saw() {
+    let cache = GlobalCache::new();
+    assert_eq!(cache.get(3, 0), None);
+    cache.set(3, 0, 7);
+    assert_eq!(cache.get(3, 0), Some(7));
+    assert_eq!(cache.get(3, 1), None);
+    assert_eq!(cache.get(5, 0), None);
+    cache.set(3, 1, 7);
+    assert_eq!(cache.get(3, 1), Some(7));
+  }
+}
@@ -0,0 +1,38 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// one shared allocation per distinct string, for literals, keys and variable names
+#[derive(Default)]
+pub struct StringInterner {
+  strings: Vec<Rc<str>>,
+  indices: HashMap<Rc<str>, usize>,
+}
+
+impl StringInterner {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  // returns the interned index of `value`, adding it the first time it's seen
+  pub fn intern(&mut self, value: &str) -> usize {
+    if let Some(&index) = self.indices.get(value) {
+      return index;
+    }
+    let string: Rc<str> = Rc::from(value);
+    self.strings.push(string.clone());
+    self.indices.insert(string, self.strings.len() - 1);
+    self.strings.len() - 1
+  }
+
+  pub fn get(&self, index: usize) -> Option<&Rc<str>> {
+    self.strings.get(index)
+  }
+
+  pub fn len(&self) -> usize {
+    self.strings.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.strings.is_empty()
+  }
+}
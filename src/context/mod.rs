@@ -1,5 +1,19 @@
 #![allow(dead_code)]
-use crate::{utils::is_internal_variable, values::Value};
+mod global_cache;
+mod interner;
+
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use crate::{
+  builtins,
+  errors::DecodeError,
+  gc::Heap,
+  utils::is_internal_variable,
+  values::{native::NativeFn, Value},
+};
+pub use global_cache::GlobalCache;
+pub use interner::StringInterner;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Kind {
@@ -9,7 +23,7 @@ pub enum Kind {
 }
 
 pub struct Store {
-  pub name: String,
+  pub name: Rc<str>,
   pub value: Value,
   pub kind: Kind,
   pub level: usize,
@@ -19,13 +33,26 @@ pub struct Context {
   global: Vec<Store>,
   local: Vec<Store>,
   current_scope: usize,
+  strings: StringInterner,
+  // where console.log/print and console.error/warn write, swapped out by embedders and tests
+  output: Box<dyn Write>,
+  error_output: Box<dyn Write>,
+  // outlives each run like the globals, so a repl session keeps collecting what earlier entries left
+  heap: Heap,
+  // bumped every time a variable is added, the guard of the global load caches
+  generation: u64,
 }
 
 impl Default for Context {
   fn default() -> Self {
-    let global =
-      vec![Store { name: "undefined".to_string(), value: Value::new_undefined(), level: 0, kind: Kind::Const }];
-    Self { global, current_scope: 0, local: vec![] }
+    let mut strings = StringInterner::new();
+    let index = strings.intern("undefined");
+    let name = strings.get(index).unwrap().clone();
+    let global = vec![Store { name, value: Value::create_undefined_value(), level: 0, kind: Kind::Const }];
+    let output = Box::new(io::stdout());
+    let error_output = Box::new(io::stderr());
+    let heap = Heap::new();
+    Self { global, current_scope: 0, local: vec![], strings, output, error_output, heap, generation: 0 }
   }
 }
 
@@ -34,6 +61,97 @@ impl Context {
     Self::default()
   }
 
+  // a context with the standard globals every script can see
+  pub fn with_builtins() -> Self {
+    let mut ctx = Self::default();
+    ctx.register_builtin("NaN", Value::create_number_value(f64::NAN), Kind::Const);
+    ctx.register_builtin("Infinity", Value::create_number_value(f64::INFINITY), Kind::Const);
+    ctx.register_global("Object", builtins::object::create_object());
+    for (name, constructor) in builtins::error::create_error_constructors() {
+      ctx.register_global(name, constructor);
+    }
+    ctx.register_global("Map", builtins::collection::create_map());
+    ctx.register_global("Set", builtins::collection::create_set());
+    ctx.register_global("Math", builtins::math::create_math());
+    ctx.register_global("JSON", builtins::json::create_json());
+    ctx.register_global("console", builtins::console::create_console());
+    ctx.define_native("print", 0, builtins::print);
+    ctx
+  }
+
+  pub fn with_output(mut self, output: Box<dyn Write>) -> Self {
+    self.output = output;
+    self
+  }
+
+  pub fn with_error_output(mut self, error_output: Box<dyn Write>) -> Self {
+    self.error_output = error_output;
+    self
+  }
+
+  pub fn output(&mut self) -> &mut dyn Write {
+    &mut *self.output
+  }
+
+  pub fn error_output(&mut self) -> &mut dyn Write {
+    &mut *self.error_output
+  }
+
+  pub fn heap(&self) -> &Heap {
+    &self.heap
+  }
+
+  pub fn heap_mut(&mut self) -> &mut Heap {
+    &mut self.heap
+  }
+
+  // the values of every global and local variable, roots for the collector
+  pub fn variable_values(&self) -> impl Iterator<Item = &Value> {
+    self.global.iter().chain(self.local.iter()).map(|store| &store.value)
+  }
+
+  // exposes a rust function to scripts as the global `name`
+  pub fn define_native(&mut self, name: &str, arity: usize, function: NativeFn) -> usize {
+    self.register_global(name, Value::create_native_function_value(name, arity, function))
+  }
+
+  // binds a host-provided global, registering the same name again replaces its value
+  pub fn register_global(&mut self, name: &str, value: Value) -> usize {
+    self.register_builtin(name, value, Kind::Var)
+  }
+
+  // rebuilds the globals a deserialized chunk was compiled against, so its operands index the same slots
+  pub fn restore_globals(&mut self, names: &[String]) -> Result<(), DecodeError> {
+    for (index, name) in names.iter().enumerate() {
+      match self.global.get(index) {
+        Some(store) if &*store.name == name.as_str() => {}
+        Some(store) => {
+          return Err(DecodeError::GlobalMismatch { index, expected: name.clone(), found: store.name.to_string() })
+        }
+        None => {
+          let name = self.intern_shared(name);
+          self
+            .global
+            .push(Store { name, value: Value::create_undefined_value(), kind: Kind::Var, level: 0 });
+          self.generation += 1;
+        }
+      }
+    }
+    Ok(())
+  }
+
+  fn register_builtin(&mut self, name: &str, value: Value, kind: Kind) -> usize {
+    if let Some(index) = self.global.iter().position(|s| &*s.name == name) {
+      self.global[index].value = value;
+      self.global[index].kind = kind;
+      return index;
+    }
+    let name = self.intern_shared(name);
+    self.global.push(Store { name, value, kind, level: 0 });
+    self.generation += 1;
+    self.global.len() - 1
+  }
+
   pub fn set_variable(&mut self, index: usize, value: Value) {
     if self.is_global_scope() && index >= self.global.len() {
       panic!("[Context]: {} does not exist in global scope.", index);
@@ -80,7 +198,26 @@ impl Context {
     count
   }
 
-  pub fn get_variable_name(&self, index: usize) -> &String {
+  // interned index of `value`, identical strings across the program share one entry
+  pub fn intern(&mut self, value: &str) -> usize {
+    self.strings.intern(value)
+  }
+
+  pub fn get_interned(&self, index: usize) -> Rc<str> {
+    self
+      .strings
+      .get(index)
+      .expect("[Context]: unknown interned string")
+      .clone()
+  }
+
+  // the shared allocation for `value`
+  pub fn intern_shared(&mut self, value: &str) -> Rc<str> {
+    let index = self.intern(value);
+    self.get_interned(index)
+  }
+
+  pub fn get_variable_name(&self, index: usize) -> &str {
     &self.get_variable(index).name
   }
 
@@ -88,17 +225,33 @@ impl Context {
     &self.get_variable(index).value
   }
 
+  pub fn get_global_variables(&self) -> &Vec<Store> {
+    &self.global
+  }
+
+  pub fn get_local_variables(&self) -> &Vec<Store> {
+    &self.local
+  }
+
+  // the value of the global `name`, what natives use to find the other builtins
+  pub fn get_global(&self, name: &str) -> Option<&Value> {
+    self.global.iter().find(|s| &*s.name == name).map(|s| &s.value)
+  }
+
   pub fn get_global_variable(&self, index: usize) -> Option<&Store> {
-    if index > self.global.len() {
-      return None;
-    }
-    return Some(&self.global[index]);
+    self.global.get(index)
+  }
+
+  // the value in a slot a global load cache resolved, slots stay put for as long as the generation does
+  pub fn get_global_value(&self, index: usize) -> &Value {
+    &self.global[index].value
+  }
+
+  pub fn generation(&self) -> u64 {
+    self.generation
   }
   pub fn get_local_variable(&self, index: usize) -> Option<&Store> {
-    if index > self.local.len() {
-      return None;
-    }
-    return Some(&self.local[index]);
+    self.local.get(index)
   }
 
   pub fn get_variable(&self, index: usize) -> &Store {
@@ -114,17 +267,17 @@ impl Context {
 
   pub fn is_exist_variable(&self, name: &str) -> bool {
     if self.is_global_scope() {
-      self.global.iter().any(|s| s.name == name)
+      self.global.iter().any(|s| &*s.name == name)
     } else {
-      self.local.iter().any(|s| s.name == name)
+      self.local.iter().any(|s| &*s.name == name)
     }
   }
 
   pub fn get_kind_variable(&self, name: &str) -> Option<Kind> {
     if self.is_global_scope() {
-      self.global.iter().find(|s| s.name == name).map(|s| s.kind.clone())
+      self.global.iter().find(|s| &*s.name == name).map(|s| s.kind.clone())
     } else {
-      self.local.iter().find(|s| s.name == name).map(|s| s.kind.clone())
+      self.local.iter().find(|s| &*s.name == name).map(|s| s.kind.clone())
     }
   }
 
@@ -138,13 +291,13 @@ impl Context {
 
   pub fn get_variable_index(&self, name: &str) -> Option<usize> {
     if self.is_global_scope() {
-      self.global.iter().position(|s| s.name == name)
+      self.global.iter().position(|s| &*s.name == name)
     } else {
       self
         .local
         .iter()
-        .position(|s| s.name == name && s.level == self.current_scope)
-        .or_else(|| self.global.iter().position(|s| s.name == name))
+        .position(|s| &*s.name == name && s.level == self.current_scope)
+        .or_else(|| self.global.iter().position(|s| &*s.name == name))
     }
   }
 
@@ -163,6 +316,7 @@ impl Context {
   }
 
   pub fn define_variable(&mut self, name: String, value: Option<Value>, kind: Kind) -> usize {
+    self.generation += 1;
     if self.is_global_scope() {
       self.define_global_variable(name, value, kind)
     } else {
@@ -174,11 +328,12 @@ impl Context {
     if let Some(index) = self.get_variable_index(&name) {
       index
     } else {
+      let name = self.intern_shared(&name);
       self.global.push(Store {
         name,
         kind,
         level: self.get_current_scope(),
-        value: value.unwrap_or_else(Value::new_undefined),
+        value: value.unwrap_or_else(Value::create_undefined_value),
       });
       self.global.len() - 1
     }
@@ -188,11 +343,12 @@ impl Context {
     if let Some(index) = self.get_variable_index(&name) {
       index
     } else {
+      let name = self.intern_shared(&name);
       self.local.push(Store {
         name,
         kind,
         level: self.get_current_scope(),
-        value: value.unwrap_or_else(Value::new_undefined),
+        value: value.unwrap_or_else(Value::create_undefined_value),
       });
       self.local.len() - 1
     }
@@ -0,0 +1,106 @@
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+
+use super::Disassembler;
+use crate::bytecode::opcode;
+use crate::utils::{is_jump, jump_target, opcode_operands};
+
+impl<'ctx> Disassembler<'ctx> {
+  // control-flow graph in graphviz format, render it with `dot -Tpng`
+  pub fn to_dot(&mut self) -> String {
+    let mut output = Vec::new();
+    self
+      .write_dot(&mut output)
+      .expect("could not write the graph to a buffer");
+    String::from_utf8(output).expect("graph is not valid utf-8")
+  }
+
+  pub fn write_dot(&mut self, writer: &mut impl Write) -> io::Result<()> {
+    let leaders = self.find_block_leaders();
+    let starts: Vec<usize> = leaders.into_iter().collect();
+    // escape codes would end up inside the labels
+    let color = self.color;
+    self.color = false;
+    self.last_line = 0;
+    writeln!(writer, "digraph {} {{", escape_dot_id(&self.name))?;
+    writeln!(writer, "  node [shape=box, fontname=\"monospace\"];")?;
+    for (index, &start) in starts.iter().enumerate() {
+      let end = starts.get(index + 1).copied().unwrap_or(self.code.len());
+      let mut label = String::new();
+      let mut offset = start;
+      let mut last_offset = start;
+      while offset < end {
+        last_offset = offset;
+        offset = self.disassemble_instruction(offset);
+        label.push_str(&escape_dot_label(&self.take_line()));
+        label.push_str("\\l");
+      }
+      writeln!(writer, "  block_{:04X} [label=\"{}\"];", start, label)?;
+      self.write_dot_edges(writer, start, last_offset, end)?;
+    }
+    writeln!(writer, "}}")?;
+    self.color = color;
+    Ok(())
+  }
+
+  // edges leaving the block `start..end` whose last instruction is at `last_offset`
+  fn write_dot_edges(&self, writer: &mut impl Write, start: usize, last_offset: usize, end: usize) -> io::Result<()> {
+    let Some(&opcode) = self.code.get(last_offset) else {
+      return Ok(());
+    };
+    let from = format!("block_{:04X}", start);
+    let target = jump_target(self.code, last_offset);
+    match (opcode, target) {
+      (opcode::OPCODE_HALT | opcode::OPCODE_RETURN | opcode::OPCODE_THROW, _) => {}
+      (opcode::OPCODE_JUMP, Some(target)) => {
+        writeln!(writer, "  {} -> block_{:04X} [label=\"taken\"];", from, target)?;
+      }
+      (opcode::OPCODE_JUMP_IF_FALSE | opcode::OPCODE_ITERATE_NEXT | opcode::OPCODE_SETUP_TRY, Some(target)) => {
+        writeln!(writer, "  {} -> block_{:04X} [label=\"taken\"];", from, target)?;
+        if end < self.code.len() {
+          writeln!(writer, "  {} -> block_{:04X} [label=\"fallthrough\"];", from, end)?;
+        }
+      }
+      _ if end < self.code.len() => {
+        writeln!(writer, "  {} -> block_{:04X} [label=\"fallthrough\"];", from, end)?;
+      }
+      _ => {}
+    }
+    Ok(())
+  }
+
+  // a block starts at offset 0, at every jump target and right after every jump, halt or return
+  fn find_block_leaders(&self) -> BTreeSet<usize> {
+    let mut leaders = BTreeSet::new();
+    leaders.insert(0);
+    let mut offset = 0;
+    while offset < self.code.len() {
+      let opcode = self.code[offset];
+      let next = offset + 1 + opcode_operands(opcode);
+      if is_jump(opcode) {
+        if let Some(target) = jump_target(self.code, offset) {
+          if target < self.code.len() {
+            leaders.insert(target);
+          }
+        }
+      }
+      let is_exit = matches!(
+        opcode,
+        opcode::OPCODE_HALT | opcode::OPCODE_RETURN | opcode::OPCODE_THROW
+      );
+      if (is_jump(opcode) || is_exit) && next < self.code.len() {
+        leaders.insert(next);
+      }
+      offset = next;
+    }
+    leaders
+  }
+}
+
+fn escape_dot_label(text: &str) -> String {
+  text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn escape_dot_id(name: &str) -> String {
+  format!("\"{}\"", escape_dot_label(name))
+}
@@ -0,0 +1,121 @@
+use serde::Serialize;
+
+use super::{describe_constant, Disassembler};
+use crate::bytecode::opcode;
+use crate::utils::{decode_operand, jump_target, opcode_operands, opcode_to_string};
+
+#[derive(Debug, Serialize)]
+pub struct DisassembledInstruction {
+  pub offset: usize,
+  pub opcode: String,
+  pub bytes: Vec<u8>,
+  pub operand: Option<usize>,
+  // what the operand points to: a constant value, a variable name or a jump target
+  pub resolved: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DisassembledConstant {
+  pub index: usize,
+  pub kind: String,
+  pub value: String,
+}
+
+#[derive(Debug, Serialize)]
+struct DisassembledProgram<'a> {
+  name: &'a str,
+  instructions: Vec<DisassembledInstruction>,
+  constants: Vec<DisassembledConstant>,
+}
+
+impl<'ctx> Disassembler<'ctx> {
+  // machine readable disassembly, e.g. for a web visualizer
+  pub fn to_json(&self) -> String {
+    let program = DisassembledProgram {
+      name: &self.name,
+      instructions: self.json_instructions(),
+      constants: self.json_constants(),
+    };
+    serde_json::to_string(&program).expect("could not serialize the disassembly")
+  }
+
+  fn json_instructions(&self) -> Vec<DisassembledInstruction> {
+    let mut instructions = vec![];
+    let mut offset = 0;
+    while offset < self.code.len() {
+      let opcode = self.code[offset];
+      let end = (offset + 1 + opcode_operands(opcode)).min(self.code.len());
+      let operand = if opcode_operands(opcode) > 0 {
+        decode_operand(self.code, offset + 1, opcode_operands(opcode))
+      } else {
+        None
+      };
+      let resolved = operand.and_then(|operand| self.resolve_operand(offset, opcode, operand));
+      let bytes = self.code[offset..end].to_vec();
+      instructions.push(DisassembledInstruction { offset, opcode: opcode_to_string(opcode), bytes, operand, resolved });
+      offset += 1 + opcode_operands(opcode);
+    }
+    instructions
+  }
+
+  fn json_constants(&self) -> Vec<DisassembledConstant> {
+    let mut constants = vec![];
+    for (index, value) in self.constants.iter().enumerate() {
+      let (kind, value) = describe_constant(value);
+      constants.push(DisassembledConstant { index, kind: kind.to_string(), value });
+    }
+    constants
+  }
+
+  fn resolve_operand(&self, offset: usize, opcode: u8, operand: usize) -> Option<String> {
+    match opcode {
+      opcode::OPCODE_CONST
+      | opcode::OPCODE_CONST_LONG
+      | opcode::OPCODE_ADD_CONST
+      | opcode::OPCODE_LT_CONST
+      | opcode::OPCODE_GET_PROPERTY
+      | opcode::OPCODE_INIT_PROPERTY
+      | opcode::OPCODE_SET_PROPERTY => self.constants.get(operand).map(|value| value.to_string()),
+      opcode::OPCODE_SET_GLOBAL_SCOPE
+      | opcode::OPCODE_LOAD_GLOBAL_SCOPE
+      | opcode::OPCODE_SET_GLOBAL_SCOPE_LONG
+      | opcode::OPCODE_LOAD_GLOBAL_SCOPE_LONG
+      | opcode::OPCODE_SET_GLOBAL_POP => self.ctx.get_global_variable(operand).map(|var| var.name.to_string()),
+      opcode::OPCODE_SET_LOCAL | opcode::OPCODE_GET_LOCAL | opcode::OPCODE_SET_LOCAL_POP => {
+        self.locals.get(operand).cloned()
+      }
+      // jump operands are relative, resolved to the absolute target
+      opcode::OPCODE_JUMP | opcode::OPCODE_JUMP_IF_FALSE | opcode::OPCODE_ITERATE_NEXT | opcode::OPCODE_SETUP_TRY => {
+        jump_target(self.code, offset).map(|target| format!("{:04X}", target))
+      }
+      opcode::OPCODE_CALL
+      | opcode::OPCODE_NEW
+      | opcode::OPCODE_CALL_METHOD
+      | opcode::OPCODE_LOAD_ARGUMENT
+      | opcode::OPCODE_SET_ARGUMENT
+      | opcode::OPCODE_NEW_ARRAY => Some(operand.to_string()),
+      _ => None,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::compiler::compile;
+  use crate::context::Context;
+  use crate::disassembler::Disassembler;
+  use oxc_allocator::Allocator;
+
+  #[test]
+  fn the_json_disassembly_parses_back() {
+    let mut ctx = Context::new();
+    let compiled = compile(&Allocator::default(), &"let a = 1;".to_string(), &mut ctx).unwrap();
+    let json = Disassembler::new(&compiled.chunk, "main", &ctx).to_json();
+    let program: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(program["name"], "main");
+    assert_eq!(program["instructions"][0]["opcode"], "CONST");
+    assert_eq!(program["instructions"][0]["operand"], 0);
+    assert_eq!(program["instructions"][1]["resolved"], "a");
+    assert_eq!(program["constants"][0]["value"], "1");
+  }
+}
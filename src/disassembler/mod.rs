@@ -1,129 +1,556 @@
 #![allow(dead_code)]
+use std::collections::HashSet;
+use std::io::{self, Write};
+
+use crate::bytecode::chunk::Chunk;
 use crate::bytecode::opcode;
 use crate::context::Context;
-use crate::utils::opcode_to_string;
+use crate::utils::{decode_operand, is_jump, jump_target, line_for_offset, opcode_operands, opcode_to_string};
 use crate::values::Value;
 
+mod dot;
+mod json;
+mod stats;
+pub use json::{DisassembledConstant, DisassembledInstruction};
+pub use stats::DisassemblyStats;
+
+// ansi styles used when color is enabled
+const STYLE_DIM: &str = "2";
+const STYLE_BOLD: &str = "1";
+const STYLE_YELLOW: &str = "1;33";
+const STYLE_GREEN: &str = "32";
+const STYLE_CYAN: &str = "36";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+  pub offset: usize,
+  pub opcode: usize,
+  pub operands: Vec<usize>,
+  // the rendered listing line, colored when color is enabled
+  pub text: String,
+}
+
 pub struct Disassembler<'ctx> {
   constants: &'ctx Vec<Value>,
-  code: &'ctx Vec<usize>,
+  code: &'ctx Vec<u8>,
+  lines: &'ctx Vec<(usize, u32)>,
+  // names of the chunk's local slots
+  locals: &'ctx Vec<String>,
+  last_line: u32,
+  jump_targets: HashSet<usize>,
+  // problems found in malformed bytecode, the dump keeps going instead of panicking
+  warnings: Vec<String>,
+  show_constants: bool,
+  show_variables: bool,
+  show_stats: bool,
+  color: bool,
+  // the instruction a debugger is paused on, marked with an arrow
+  highlight_ip: Option<usize>,
   instructions: Vec<Vec<String>>,
   name: String,
   line: Vec<String>,
-  ctx: &'ctx mut Context,
+  ctx: &'ctx Context,
 }
 
 impl<'ctx> Disassembler<'ctx> {
-  pub fn new(code: &'ctx Vec<usize>, name: &str, constants: &'ctx Vec<Value>, ctx: &'ctx mut Context) -> Self {
+  pub fn new(chunk: &'ctx Chunk, name: &str, ctx: &'ctx Context) -> Self {
     let instructions = vec![];
     let line = vec![];
-    Self { code, constants, instructions, ctx, line, name: name.to_owned() }
+    let jump_targets = HashSet::new();
+    let warnings = vec![];
+    Self {
+      code: &chunk.code,
+      lines: &chunk.lines,
+      locals: &chunk.locals,
+      last_line: 0,
+      jump_targets,
+      warnings,
+      show_constants: false,
+      show_variables: false,
+      color: false,
+      show_stats: false,
+      highlight_ip: None,
+      constants: &chunk.constants,
+      instructions,
+      ctx,
+      line,
+      name: name.to_owned(),
+    }
   }
-  pub fn disassemble(&mut self) -> () {
-    let header = format!("{:<10} {:<12} {:<14} {}", "Offset", "Bytes", "Opcode", "Operand");
-    println!("----------------- Disassembler -----------------");
-    println!("{}", header);
-    println!("------------------------------------------------");
+  // lists every constant of the pool before the instructions
+  pub fn with_constants(mut self, show_constants: bool) -> Self {
+    self.show_constants = show_constants;
+    self
+  }
+
+  // lists the variables known to the context before the instructions
+  pub fn with_variables(mut self, show_variables: bool) -> Self {
+    self.show_variables = show_variables;
+    self
+  }
+
+  // prints a summary of the code after the instructions
+  pub fn with_stats(mut self, show_stats: bool) -> Self {
+    self.show_stats = show_stats;
+    self
+  }
+
+  // wraps offsets, opcodes and operands in ansi colors, keep it off when the output is piped
+  pub fn with_color(mut self, color: bool) -> Self {
+    self.color = color;
+    self
+  }
+
+  // same as `with_color`, off by default so plain output stays stable
+  pub fn colored(self, color: bool) -> Self {
+    self.with_color(color)
+  }
+
+  // marks the instruction at `ip` with `-> `, every other line gets the same width of padding
+  pub fn with_highlight(mut self, highlight_ip: Option<usize>) -> Self {
+    self.highlight_ip = highlight_ip;
+    self
+  }
+
+  // prints the disassembly to stdout, used by the cli
+  pub fn print(&mut self) {
+    let mut stdout = io::stdout();
+    self
+      .disassemble_to(&mut stdout)
+      .expect("could not write the disassembly to stdout");
+  }
+
+  // returns the whole disassembly, so callers can interleave it with vm traces
+  pub fn disassemble(&mut self) -> String {
+    let mut output = Vec::new();
+    self
+      .disassemble_to(&mut output)
+      .expect("could not write the disassembly to a buffer");
+    String::from_utf8(output).expect("disassembly is not valid utf-8")
+  }
+
+  pub fn disassemble_to(&mut self, writer: &mut impl Write) -> io::Result<()> {
+    if self.show_constants {
+      self.write_constants(writer)?;
+    }
+    if self.show_variables {
+      self.write_variables(writer)?;
+    }
+    let header = format!(
+      "{}{:<10} {:<6} {:<15} {:<14} {}",
+      self.margin(None),
+      "Offset",
+      "Line",
+      "Bytes",
+      "Opcode",
+      "Operand"
+    );
+    writeln!(writer, "----------------- Disassembler -----------------")?;
+    writeln!(writer, "{}", header)?;
+    writeln!(writer, "------------------------------------------------")?;
     // ------------------------------------------------
+    self.collect_jump_targets();
+    for instruction in self.instructions() {
+      if self.jump_targets.contains(&instruction.offset) {
+        writeln!(writer, "{:04X}:", instruction.offset)?;
+      }
+      writeln!(writer, "{}", instruction.text)?;
+    }
+    if self.show_stats {
+      write!(writer, "{}", self.stats())?;
+    }
+    writeln!(writer, "{}", self.summary())?;
+    Ok(())
+  }
+
+  // every instruction with its decoded operands and the line the listing prints for it
+  pub fn instructions(&mut self) -> Vec<Instruction> {
+    self.last_line = 0;
+    self.warnings.clear();
+    let mut instructions = vec![];
     let mut offset = 0;
     while offset < self.code.len() {
-      offset = self.disassemble_instruction(offset);
-      self.print_line();
+      let opcode = self.code[offset];
+      let width = opcode_operands(opcode);
+      let operands = if width > 0 {
+        decode_operand(self.code, offset + 1, width).into_iter().collect()
+      } else {
+        vec![]
+      };
+      let next = self.disassemble_instruction(offset);
+      let text = self.take_line();
+      instructions.push(Instruction { offset, opcode: opcode as usize, operands, text });
+      offset = next;
     }
+    instructions
+  }
+
+  // renders the instruction at `ip` and returns it with the offset of the next one, for step debuggers.
+  // the listing state (last line, warnings, pending line) is left as it was, and the line is always printed
+  pub fn disassemble_one(&mut self, ip: usize) -> (String, usize) {
+    let last_line = std::mem::replace(&mut self.last_line, 0);
+    let pending = std::mem::take(&mut self.line);
+    let warnings = self.warnings.len();
+    let next = self.disassemble_instruction(ip);
+    let text = self.take_line();
+    self.last_line = last_line;
+    self.line = pending;
+    self.warnings.truncate(warnings);
+    (text, next)
   }
 
   fn disassemble_instruction(&mut self, offset: usize) -> usize {
     self.print_offset(offset);
+    self.print_source_line(offset);
     let opcode = self.code[offset];
     match opcode {
-      opcode::OPCODE_HALF
+      opcode::OPCODE_HALT
       | opcode::OPCODE_SUB
       | opcode::OPCODE_MUL
       | opcode::OPCODE_DIV
       | opcode::OPCODE_ADD
       | opcode::OPCODE_POP
-      | opcode::OPCODE_EQ => {
+      | opcode::OPCODE_LOAD_THIS
+      | opcode::OPCODE_RETURN
+      | opcode::OPCODE_NOT
+      | opcode::OPCODE_GET_ELEMENT
+      | opcode::OPCODE_NEW_OBJECT
+      | opcode::OPCODE_SET_ELEMENT
+      | opcode::OPCODE_DUP
+      | opcode::OPCODE_SWAP
+      | opcode::OPCODE_ADD_INT
+      | opcode::OPCODE_SUB_INT
+      | opcode::OPCODE_MUL_INT
+      | opcode::OPCODE_INSTANCEOF
+      | opcode::OPCODE_ITERATE
+      | opcode::OPCODE_ENUMERATE
+      | opcode::OPCODE_THROW
+      | opcode::OPCODE_POP_TRY
+      | opcode::OPCODE_EQ
+      | opcode::OPCODE_WEAK_EQ
+      | opcode::OPCODE_NE
+      | opcode::OPCODE_LT
+      | opcode::OPCODE_LE
+      | opcode::OPCODE_GT
+      | opcode::OPCODE_GE => {
         return self.disassemble_simple(opcode, offset);
       }
-      opcode::OPCODE_CONST => {
+      // the property name is a string constant
+      opcode::OPCODE_CONST
+      | opcode::OPCODE_CONST_LONG
+      | opcode::OPCODE_ADD_CONST
+      | opcode::OPCODE_LT_CONST
+      | opcode::OPCODE_GET_PROPERTY
+      | opcode::OPCODE_INIT_PROPERTY
+      | opcode::OPCODE_SET_PROPERTY => {
         return self.disassemble_const(offset, opcode);
       }
-      opcode::OPCODE_SET_GLOBAL_SCOPE | opcode::OPCODE_LOAD_GLOBAL_SCOPE => {
+      opcode::OPCODE_SET_GLOBAL_SCOPE
+      | opcode::OPCODE_LOAD_GLOBAL_SCOPE
+      | opcode::OPCODE_SET_GLOBAL_SCOPE_LONG
+      | opcode::OPCODE_LOAD_GLOBAL_SCOPE_LONG
+      | opcode::OPCODE_SET_GLOBAL_POP => {
         return self.disassemble_global(offset, opcode);
       }
-      opcode::OPCODE_SET_LOCAL_SCOPE | opcode::OPCODE_LOAD_LOCAL_SCOPE => {
+      opcode::OPCODE_SET_LOCAL | opcode::OPCODE_GET_LOCAL | opcode::OPCODE_SET_LOCAL_POP => {
         return self.disassemble_local(offset, opcode);
       }
-      opcode::OPCODE_JUMP_IF_FALSE | opcode::OPCODE_JUMP => {
+      opcode::OPCODE_JUMP_IF_FALSE | opcode::OPCODE_JUMP | opcode::OPCODE_ITERATE_NEXT | opcode::OPCODE_SETUP_TRY => {
         return self.disassemble_jump(offset, opcode);
       }
+      opcode::OPCODE_CALL
+      | opcode::OPCODE_NEW
+      | opcode::OPCODE_CALL_METHOD
+      | opcode::OPCODE_LOAD_ARGUMENT
+      | opcode::OPCODE_SET_ARGUMENT
+      | opcode::OPCODE_NEW_ARRAY => {
+        return self.disassemble_count(offset, opcode);
+      }
       _ => {
-        print!("[Disassemble] Unknown opcode: {}", opcode_to_string(opcode));
+        self
+          .line
+          .push(format!("[Disassemble] Unknown opcode: {}", opcode_to_string(opcode)));
         return offset + 1;
       }
     }
   }
 
-  pub fn disassemble_jump(&mut self, offset: usize, opcode: usize) -> usize {
-    self.dumb_bytecode(offset, 3);
+  fn write_constants(&self, writer: &mut impl Write) -> io::Result<()> {
+    writeln!(writer, "------------------ Constants -------------------")?;
+    for (index, value) in self.constants.iter().enumerate() {
+      let (kind, value) = describe_constant(value);
+      writeln!(writer, "{:<6} {:<10} {}", format!("#{}", index), kind, value)?;
+    }
+    Ok(())
+  }
+
+  fn write_variables(&self, writer: &mut impl Write) -> io::Result<()> {
+    writeln!(writer, "------------------ Variables -------------------")?;
+    for (index, store) in self.ctx.get_global_variables().iter().enumerate() {
+      writeln!(writer, "{:<6} {:<10} {}", format!("#{}", index), "global", store.name)?;
+    }
+    for (slot, name) in self.locals.iter().enumerate() {
+      writeln!(writer, "{:<6} {:<10} {}", format!("#{}", slot), "local", name)?;
+    }
+    Ok(())
+  }
+
+  pub fn warnings(&self) -> &Vec<String> {
+    &self.warnings
+  }
+
+  // the operand is relative, the listing shows the absolute target
+  pub fn disassemble_jump(&mut self, offset: usize, opcode: u8) -> usize {
+    self.dumb_bytecode(offset, 1 + opcode_operands(opcode));
+    self.print_opcode(opcode);
+    if let Some(distance) = self.read_operand(offset) {
+      match jump_target(self.code, offset) {
+        Some(target) if target <= self.code.len() => {
+          let target = self.paint(format!("{:04X}", target), STYLE_CYAN);
+          self.line.push(format!("-> {}", target))
+        }
+        _ => self.print_warning(offset, format!("<bad jump {:+}>", distance as u16 as i16)),
+      }
+    }
+    return offset + 1 + opcode_operands(opcode);
+  }
+
+  // offsets that some jump lands on, printed as labels so branches are easy to follow
+  fn collect_jump_targets(&mut self) {
+    self.jump_targets.clear();
+    let mut offset = 0;
+    while offset < self.code.len() {
+      let opcode = self.code[offset];
+      if is_jump(opcode) {
+        if let Some(target) = jump_target(self.code, offset) {
+          self.jump_targets.insert(target);
+        }
+      }
+      offset += 1 + opcode_operands(opcode);
+    }
+  }
+  pub fn disassemble_global(&mut self, offset: usize, opcode: u8) -> usize {
+    self.dumb_bytecode(offset, 1 + opcode_operands(opcode));
     self.print_opcode(opcode);
-    let index = self.code[offset + 1];
-    let jump = self.code[offset + 2];
-    self.line.push(format!("{:<8}", index));
-    self.line.push(format!("{:<8}", jump));
-    return offset + 3;
-  }
-  pub fn disassemble_global(&mut self, offset: usize, opcode: usize) -> usize {
-    self.dumb_bytecode(offset, 2);
+    if let Some(index) = self.read_operand(offset) {
+      match self.ctx.get_global_variable(index) {
+        Some(var) => self.print_operand(var.name.to_string(), STYLE_CYAN),
+        None => self.print_warning(offset, format!("<bad var #{}>", index)),
+      }
+    }
+    return offset + 1 + opcode_operands(opcode);
+  }
+  pub fn disassemble_local(&mut self, offset: usize, opcode: u8) -> usize {
+    self.dumb_bytecode(offset, 1 + opcode_operands(opcode));
     self.print_opcode(opcode);
-    let index = self.code[offset + 1];
-    let var = &self.ctx.get_global_variable(index).unwrap().name;
-    self.print_operand(var.to_owned());
-    return offset + 2;
+    // the slot, then the name the compiler recorded for it
+    if let Some(slot) = self.read_operand(offset) {
+      match self.locals.get(slot) {
+        Some(name) => self.print_operand(format!("#{} {}", slot, name), STYLE_CYAN),
+        None => self.print_warning(offset, format!("<bad local #{}>", slot)),
+      }
+    }
+    return offset + 1 + opcode_operands(opcode);
   }
-  pub fn disassemble_local(&mut self, offset: usize, opcode: usize) -> usize {
-    self.dumb_bytecode(offset, 2);
+  pub fn disassemble_const(&mut self, offset: usize, opcode: u8) -> usize {
+    self.dumb_bytecode(offset, 1 + opcode_operands(opcode));
     self.print_opcode(opcode);
-    let index = self.code[offset + 1];
-    let var = &self.ctx.get_local_variable(index).unwrap().name;
-    self.print_operand(var.to_owned());
-    return offset + 2;
+    // the pool index goes first, so a deduplicated constant shows the same `#index` at every use
+    if let Some(index) = self.read_operand(offset) {
+      match self.constants.get(index) {
+        Some(Value::Function(function)) => {
+          let operand = format!("#{} Function: {}", index, function.get_name());
+          self.print_operand(operand, STYLE_GREEN)
+        }
+        Some(value) => {
+          let operand = format!("#{} {}", index, describe_constant(value).1);
+          self.print_operand(operand, STYLE_GREEN)
+        }
+        None => self.print_warning(offset, format!("<bad const #{}>", index)),
+      }
+    }
+    return offset + 1 + opcode_operands(opcode);
   }
-  pub fn disassemble_const(&mut self, offset: usize, opcode: usize) -> usize {
-    self.dumb_bytecode(offset, 2);
+
+  // operands that are plain numbers (argument count, argument slot, element count)
+  pub fn disassemble_count(&mut self, offset: usize, opcode: u8) -> usize {
+    self.dumb_bytecode(offset, 1 + opcode_operands(opcode));
     self.print_opcode(opcode);
-    let index = self.code[offset + 1];
-    self.print_operand(self.constants[index].to_string());
-    return offset + 2;
+    if let Some(count) = self.read_operand(offset) {
+      self.print_operand(count.to_string(), STYLE_CYAN);
+    }
+    return offset + 1 + opcode_operands(opcode);
+  }
+
+  // reads the operand of the instruction at `offset`, the code may end before it
+  fn read_operand(&mut self, offset: usize) -> Option<usize> {
+    let operand = decode_operand(self.code, offset + 1, opcode_operands(self.code[offset]));
+    if operand.is_none() {
+      self.print_warning(offset, "<truncated>".to_string());
+    }
+    operand
   }
 
-  pub fn disassemble_simple(&mut self, opcode: usize, offset: usize) -> usize {
+  fn print_warning(&mut self, offset: usize, warning: String) {
+    self.warnings.push(format!("{:08X}: {}", offset, warning));
+    self.line.push(warning);
+  }
+
+  pub fn disassemble_simple(&mut self, opcode: u8, offset: usize) -> usize {
     self.dumb_bytecode(offset, 1);
     self.print_opcode(opcode);
     return offset + 1;
   }
 
-  pub fn dumb_bytecode(&mut self, offset: usize, count: usize) -> () {
+  pub fn dumb_bytecode(&mut self, offset: usize, count: usize) {
     let mut output = String::new();
-    for i in 0..count {
-      output += format!("{:02X} ", self.code[offset + i] & 0xFF).as_str();
+    for byte in self.code.iter().skip(offset).take(count) {
+      output += format!("{:02X} ", byte).as_str();
     }
-    self.line.push(format!("{:<12} ", output.trim()));
+    self.line.push(format!("{:<15} ", output.trim()));
   }
-  pub fn print_opcode(&mut self, opcode: usize) -> () {
-    self.line.push(format!("{:<14} ", opcode_to_string(opcode).trim()));
+  pub fn print_opcode(&mut self, opcode: u8) {
+    let opcode = format!("{:<14} ", opcode_to_string(opcode).trim());
+    self.line.push(self.paint(opcode, STYLE_YELLOW));
   }
-  pub fn print_operand(&mut self, operand: String) -> () {
+  pub fn print_operand(&mut self, operand: String, style: &str) {
+    let operand = self.paint(operand, style);
     self.line.push(format!("({})", operand));
   }
 
-  pub fn print_offset(&mut self, offset: usize) -> () {
+  pub fn print_offset(&mut self, offset: usize) {
+    self.line.push(self.margin(Some(offset)));
     let formatted = format!("{:08X} ", offset);
-    self.line.push(format!("{:<10} ", formatted.trim()));
+    let offset = format!("{:<10} ", formatted.trim());
+    self.line.push(self.paint(offset, STYLE_DIM));
+  }
+
+  // room for the highlight arrow, nothing when no instruction is highlighted
+  fn margin(&self, offset: Option<usize>) -> String {
+    match self.highlight_ip {
+      None => String::new(),
+      Some(ip) if offset == Some(ip) => self.paint("-> ".to_string(), STYLE_BOLD),
+      Some(_) => "   ".to_string(),
+    }
   }
 
-  pub fn print_line(&mut self) -> () {
-    println!("{}", format!("{}", self.line.join("")));
+  // padding is applied before painting, so escape codes never shift the columns
+  fn paint(&self, text: String, style: &str) -> String {
+    if !self.color {
+      return text;
+    }
+    format!("\x1b[{}m{}\x1b[0m", style, text)
+  }
+
+  // like clox, only print the line number when it changes
+  pub fn print_source_line(&mut self, offset: usize) {
+    let current_line = self.get_source_line(offset);
+    if current_line == self.last_line {
+      self.line.push(format!("{:<6} ", "|"));
+      return;
+    }
+    self.last_line = current_line;
+    self.line.push(format!("{:<6} ", current_line));
+  }
+
+  fn get_source_line(&self, offset: usize) -> u32 {
+    line_for_offset(self.lines, offset).unwrap_or(0)
+  }
+
+  // returns the current instruction line and resets it for the next one
+  pub fn take_line(&mut self) -> String {
+    let line = self.line.join("");
     self.line.clear();
+    line
+  }
+}
+
+// the type name and a printable form of a constant, strings are quoted
+fn describe_constant(value: &Value) -> (&'static str, String) {
+  match value {
+    Value::Undefined(_) => ("Undefined", "undefined".to_string()),
+    Value::Null(_) => ("Null", "null".to_string()),
+    Value::Boolean(_) => ("Boolean", value.to_string()),
+    Value::String(_) => ("String", format!("\"{}\"", value)),
+    Value::Symbol(_) => ("Symbol", value.to_string()),
+    Value::Number(_) => ("Number", value.to_string()),
+    Value::BigInt(_) => ("BigInt", value.debug_repr()),
+    Value::Object(_) => ("Object", value.debug_repr()),
+    Value::Function(function) => ("Function", function.get_name().to_string()),
+    Value::NativeFunction(function) => ("Native", function.get_name().to_string()),
+    Value::Array(_) => ("Array", value.debug_repr()),
+    Value::RegExp(_) => ("RegExp", value.to_string()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use oxc_allocator::Allocator;
+
+  use super::Disassembler;
+  use crate::compiler::compile;
+  use crate::context::Context;
+
+  #[test]
+  fn disassembly_is_written_to_any_writer() {
+    let mut ctx = Context::new();
+    let source = "let a = 1;\nlet b = a + 2;\n".to_string();
+    let compiled = compile(&Allocator::default(), &source, &mut ctx).unwrap();
+    let mut output = Vec::new();
+    Disassembler::new(&compiled.chunk, "main", &ctx)
+      .disassemble_to(&mut output)
+      .unwrap();
+    let expected = concat!(
+      "----------------- Disassembler -----------------\n",
+      "Offset     Line   Bytes           Opcode         Operand\n",
+      "------------------------------------------------\n",
+      "00000000   1      01 00           CONST          (#0 1)\n",
+      "00000002   |      39 01           SET_GLOBAL_POP (a)\n",
+      "00000004   2      17 01           LOAD_GLOBAL    (a)\n",
+      "00000006   |      37 01           ADD_CONST      (#1 2)\n",
+      "00000008   |      39 02           SET_GLOBAL_POP (b)\n",
+      "0000000A   |      00              HALT           \n",
+      "6 instructions, 2 constants, 3 variables\n",
+    );
+    assert_eq!(String::from_utf8(output).unwrap(), expected);
+  }
+
+  #[test]
+  fn loose_equality_disassembles_by_name() {
+    let mut ctx = Context::new();
+    let source = "let a = 1;\na == 2;\na != 2;\n".to_string();
+    let compiled = compile(&Allocator::default(), &source, &mut ctx).unwrap();
+    let lines: Vec<String> = Disassembler::new(&compiled.chunk, "main", &ctx)
+      .instructions()
+      .into_iter()
+      .map(|instruction| instruction.text)
+      .collect();
+    assert!(lines.iter().any(|line| line.contains(" WEAK_EQ ")), "{lines:#?}");
+    assert!(lines.iter().any(|line| line.contains(" NE ")), "{lines:#?}");
+    assert!(!lines.iter().any(|line| line.contains("Unknown opcode")), "{lines:#?}");
+  }
+
+  #[test]
+  fn only_a_colored_disassembly_has_escape_codes() {
+    let mut ctx = Context::new();
+    let compiled = compile(&Allocator::default(), &"let a = 1;".to_string(), &mut ctx).unwrap();
+    let listing = |color: bool| {
+      let mut output = Vec::new();
+      Disassembler::new(&compiled.chunk, "main", &ctx)
+        .with_color(color)
+        .disassemble_to(&mut output)
+        .unwrap();
+      String::from_utf8(output).unwrap()
+    };
+    assert!(!listing(false).contains('\x1b'));
+    assert!(listing(true).contains("\x1b["));
+  }
+
+  #[test]
+  fn an_integral_float_constant_disassembles_without_a_fraction() {
+    let mut ctx = Context::new();
+    let compiled = compile(&Allocator::default(), &"let a = 3.0;".to_string(), &mut ctx).unwrap();
+    let instructions = Disassembler::new(&compiled.chunk, "main", &ctx).instructions();
+    assert!(instructions[0].text.contains("(#0 3)"), "{}", instructions[0].text);
   }
 }
@@ -0,0 +1,88 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use super::Disassembler;
+use crate::bytecode::opcode;
+use crate::utils::{decode_operand, opcode_operands, opcode_to_string};
+
+#[derive(Debug)]
+pub struct DisassemblyStats {
+  pub instructions: usize,
+  // size of the code vector, in bytes
+  pub code_size: usize,
+  // opcode name and how many times it appears, most frequent first
+  pub histogram: Vec<(String, usize)>,
+  pub constants: usize,
+  // distinct variables loaded or stored by the code
+  pub variables: usize,
+}
+
+impl<'ctx> Disassembler<'ctx> {
+  // computed in its own pass, so it works without rendering the listing
+  pub fn stats(&self) -> DisassemblyStats {
+    let mut instructions = 0;
+    let mut frequencies: HashMap<u8, usize> = HashMap::new();
+    let mut variables = HashSet::new();
+    let mut offset = 0;
+    while offset < self.code.len() {
+      let opcode = self.code[offset];
+      instructions += 1;
+      *frequencies.entry(opcode).or_insert(0) += 1;
+      if let Some(index) = decode_operand(self.code, offset + 1, opcode_operands(opcode)) {
+        match opcode {
+          opcode::OPCODE_LOAD_GLOBAL_SCOPE
+          | opcode::OPCODE_SET_GLOBAL_SCOPE
+          | opcode::OPCODE_LOAD_GLOBAL_SCOPE_LONG
+          | opcode::OPCODE_SET_GLOBAL_SCOPE_LONG
+          | opcode::OPCODE_SET_GLOBAL_POP => {
+            variables.insert(("global", index));
+          }
+          opcode::OPCODE_GET_LOCAL | opcode::OPCODE_SET_LOCAL | opcode::OPCODE_SET_LOCAL_POP => {
+            variables.insert(("local", index));
+          }
+          _ => {}
+        }
+      }
+      offset += 1 + opcode_operands(opcode);
+    }
+    let mut histogram: Vec<(String, usize)> = frequencies
+      .into_iter()
+      .map(|(opcode, count)| (opcode_to_string(opcode), count))
+      .collect();
+    histogram.sort_by(|left, right| right.1.cmp(&left.1).then_with(|| left.0.cmp(&right.0)));
+    DisassemblyStats {
+      instructions,
+      code_size: self.code.len(),
+      histogram,
+      constants: self.constants.len(),
+      variables: variables.len(),
+    }
+  }
+}
+
+impl<'ctx> Disassembler<'ctx> {
+  // the one line footer of the listing, handy for comparing codegen sizes
+  pub fn summary(&self) -> String {
+    let variables = self.ctx.get_global_variables().len() + self.ctx.get_local_variables().len();
+    format!(
+      "{} instructions, {} constants, {} variables",
+      self.stats().instructions,
+      self.constants.len(),
+      variables
+    )
+  }
+}
+
+impl fmt::Display for DisassemblyStats {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    writeln!(f, "------------------- Summary --------------------")?;
+    writeln!(f, "{:<14} {}", "instructions", self.instructions)?;
+    writeln!(f, "{:<14} {} bytes", "code size", self.code_size)?;
+    writeln!(f, "{:<14} {}", "constants", self.constants)?;
+    writeln!(f, "{:<14} {}", "variables", self.variables)?;
+    for (opcode, count) in &self.histogram {
+      writeln!(f, "{:<14} {}", opcode, count)?;
+    }
+    Ok(())
+  }
+}
@@ -2,6 +2,8 @@
 use core::fmt;
 use std::error::Error;
 
+use crate::{utils::opcode_to_string, values::Value};
+
 #[derive(Debug)]
 #[allow(dead_code)]
 pub enum EngineError {
@@ -23,3 +25,281 @@ impl fmt::Display for EngineError {
     }
   }
 }
+
+#[derive(Debug, PartialEq)]
+pub enum DecodeError {
+  InvalidMagic,
+  VersionMismatch {
+    found: u16,
+    expected: u16,
+  },
+  UnexpectedEnd,
+  InvalidTag(u8),
+  InvalidVarint,
+  InvalidUtf8,
+  TrailingBytes(usize),
+  // the context already binds the slot a global of the bytecode needs to another name
+  GlobalMismatch {
+    index: usize,
+    expected: String,
+    found: String,
+  },
+}
+
+impl Error for DecodeError {}
+
+impl fmt::Display for DecodeError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      DecodeError::InvalidMagic => write!(f, "not a core-engine bytecode file (bad magic)"),
+      DecodeError::VersionMismatch { found, expected } => {
+        write!(
+          f,
+          "bytecode version {found} does not match the engine version {expected}"
+        )
+      }
+      DecodeError::UnexpectedEnd => write!(f, "unexpected end of bytecode, the buffer is truncated"),
+      DecodeError::InvalidTag(tag) => write!(f, "unknown constant tag 0x{tag:02X}"),
+      DecodeError::InvalidVarint => write!(f, "varint is too long"),
+      DecodeError::InvalidUtf8 => write!(f, "string is not valid utf-8"),
+      DecodeError::TrailingBytes(count) => write!(f, "{count} unexpected byte(s) after the end of the bytecode"),
+      DecodeError::GlobalMismatch { index, expected, found } => {
+        write!(f, "global {index} is '{found}' here but '{expected}' in the bytecode")
+      }
+    }
+  }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CompileError {
+  TooManyConstants { limit: usize },
+  // the distance doesn't fit in the signed 16 bit operand of a jump
+  JumpTooFar { offset: usize, target: usize },
+  // a `let`, `const` or `class` binding read or assigned before its declaration ran (its temporal dead zone)
+  UsedBeforeDeclaration { name: String },
+  // e.g. a call with more arguments than the one byte count of CALL holds
+  OperandTooLarge { opcode: u8, operand: usize, width: usize },
+}
+
+impl Error for CompileError {}
+
+impl fmt::Display for CompileError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      CompileError::TooManyConstants { limit } => write!(f, "too many constants in one program, the limit is {limit}"),
+      CompileError::JumpTooFar { offset, target } => {
+        write!(
+          f,
+          "jump from {offset:04X} to {target:04X} is too far, the limit is {} bytes",
+          i16::MAX
+        )
+      }
+      CompileError::UsedBeforeDeclaration { name } => write!(f, "Cannot access '{name}' before initialization"),
+      CompileError::OperandTooLarge { opcode, operand, width } => {
+        write!(
+          f,
+          "operand {operand} of {} doesn't fit in {width} bytes",
+          opcode_to_string(*opcode)
+        )
+      }
+    }
+  }
+}
+
+// every variant carries the offset of the instruction that failed
+#[derive(Debug, PartialEq)]
+pub enum VerifyError {
+  UnknownOpcode { offset: usize, opcode: u8 },
+  TruncatedOperand { offset: usize, opcode: u8 },
+  ConstantOutOfRange { offset: usize, index: usize, len: usize },
+  GlobalOutOfRange { offset: usize, index: usize, len: usize },
+  ArgumentOutOfRange { offset: usize, index: usize, arity: usize },
+  LocalOutOfRange { offset: usize, index: usize, len: usize },
+  JumpOutOfBounds { offset: usize },
+  JumpIntoOperand { offset: usize, target: usize },
+  MissingTerminator { offset: usize },
+}
+
+impl VerifyError {
+  pub fn offset(&self) -> usize {
+    match self {
+      VerifyError::UnknownOpcode { offset, .. }
+      | VerifyError::TruncatedOperand { offset, .. }
+      | VerifyError::ConstantOutOfRange { offset, .. }
+      | VerifyError::GlobalOutOfRange { offset, .. }
+      | VerifyError::ArgumentOutOfRange { offset, .. }
+      | VerifyError::LocalOutOfRange { offset, .. }
+      | VerifyError::JumpOutOfBounds { offset }
+      | VerifyError::JumpIntoOperand { offset, .. }
+      | VerifyError::MissingTerminator { offset } => *offset,
+    }
+  }
+}
+
+impl Error for VerifyError {}
+
+impl fmt::Display for VerifyError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "invalid bytecode at {:04X}: ", self.offset())?;
+    match self {
+      VerifyError::UnknownOpcode { opcode, .. } => write!(f, "unknown opcode 0x{opcode:02X}"),
+      VerifyError::TruncatedOperand { opcode, .. } => {
+        write!(f, "the code ends inside the operand of opcode 0x{opcode:02X}")
+      }
+      VerifyError::ConstantOutOfRange { index, len, .. } => {
+        write!(f, "constant {index} is out of range, the pool has {len} constant(s)")
+      }
+      VerifyError::GlobalOutOfRange { index, len, .. } => {
+        write!(f, "global {index} is out of range, the context has {len} global(s)")
+      }
+      VerifyError::ArgumentOutOfRange { index, arity, .. } => {
+        write!(f, "argument {index} is out of range, the function takes {arity}")
+      }
+      VerifyError::LocalOutOfRange { index, len, .. } => {
+        write!(f, "local {index} is out of range, the chunk has {len} local(s)")
+      }
+      VerifyError::JumpOutOfBounds { .. } => write!(f, "jump lands outside the code"),
+      VerifyError::JumpIntoOperand { target, .. } => {
+        write!(
+          f,
+          "jump lands at {target:04X}, which is not the start of an instruction"
+        )
+      }
+      VerifyError::MissingTerminator { .. } => write!(f, "the code does not end with HALT or RETURN"),
+    }
+  }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum StackError {
+  Unbalanced {
+    offset: usize,
+    expected: usize,
+    found: usize,
+  },
+  Underflow {
+    offset: usize,
+    depth: usize,
+    pops: usize,
+  },
+}
+
+impl Error for StackError {}
+
+impl fmt::Display for StackError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      StackError::Unbalanced { offset, expected, found } => {
+        write!(
+          f,
+          "unbalanced stack at {offset:04X}: one path reaches it with depth {expected}, another with {found}"
+        )
+      }
+      StackError::Underflow { offset, depth, pops } => {
+        write!(
+          f,
+          "stack underflow at {offset:04X}: pops {pops} value(s) with only {depth} on the stack"
+        )
+      }
+    }
+  }
+}
+
+// what went wrong while executing, without the position
+#[derive(Debug, PartialEq)]
+pub enum RuntimeErrorKind {
+  TypeError(String),
+  RangeError(String),
+  ReferenceError(String),
+  SyntaxError(String),
+  StackUnderflow,
+  UnknownOpcode,
+  UnexpectedEnd,
+  InvalidConstant(usize),
+  ReturnOutsideFunction,
+  // the instruction budget given with `Engine::with_fuel` ran out
+  OutOfFuel,
+  // the engine's `InterruptHandle` was used, from this thread or another
+  Interrupted,
+  // a `throw` nothing caught, with the value it threw
+  Thrown(Value),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct RuntimeError {
+  pub kind: RuntimeErrorKind,
+  // the instruction that failed and where it starts in its function's code
+  pub opcode: u8,
+  pub offset: usize,
+  pub line: Option<u32>,
+  // `name (line N)` of every active call, from the failing one out to `main`
+  pub call_stack: Vec<String>,
+}
+
+impl Error for RuntimeError {}
+
+impl RuntimeErrorKind {
+  // errors a script's `catch` sees. running out of fuel, an interrupt or a broken chunk stop the engine
+  // whatever the script does
+  pub fn is_catchable(&self) -> bool {
+    matches!(
+      self,
+      RuntimeErrorKind::TypeError(_)
+        | RuntimeErrorKind::RangeError(_)
+        | RuntimeErrorKind::ReferenceError(_)
+        | RuntimeErrorKind::SyntaxError(_)
+        | RuntimeErrorKind::Thrown(_)
+    )
+  }
+
+  // the `name` of the error object scripts see
+  pub fn name(&self) -> &'static str {
+    match self {
+      RuntimeErrorKind::TypeError(_) => "TypeError",
+      RuntimeErrorKind::RangeError(_) => "RangeError",
+      RuntimeErrorKind::ReferenceError(_) => "ReferenceError",
+      RuntimeErrorKind::SyntaxError(_) => "SyntaxError",
+      _ => "InternalError",
+    }
+  }
+
+  pub fn message(&self) -> String {
+    match self {
+      RuntimeErrorKind::TypeError(message)
+      | RuntimeErrorKind::RangeError(message)
+      | RuntimeErrorKind::ReferenceError(message)
+      | RuntimeErrorKind::SyntaxError(message) => message.clone(),
+      RuntimeErrorKind::StackUnderflow => "stack underflow".to_string(),
+      RuntimeErrorKind::UnknownOpcode => "unknown opcode".to_string(),
+      RuntimeErrorKind::UnexpectedEnd => "bytecode ends inside an instruction".to_string(),
+      RuntimeErrorKind::InvalidConstant(index) => format!("constant {index} does not exist"),
+      RuntimeErrorKind::ReturnOutsideFunction => "return outside of a function".to_string(),
+      RuntimeErrorKind::OutOfFuel => "out of fuel, the instruction budget is exhausted".to_string(),
+      RuntimeErrorKind::Interrupted => "execution was interrupted".to_string(),
+      RuntimeErrorKind::Thrown(value) => value.to_string(),
+    }
+  }
+}
+
+impl fmt::Display for RuntimeErrorKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if let RuntimeErrorKind::Thrown(value) = self {
+      return write!(f, "Uncaught {}", value);
+    }
+    write!(f, "{}: {}", self.name(), self.message())
+  }
+}
+
+impl fmt::Display for RuntimeError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.kind)?;
+    // without a line table (a hand-built chunk) the offset is all there is
+    if self.line.is_none() {
+      write!(f, " at offset {:04X} (opcode 0x{:02X})", self.offset, self.opcode)?;
+    }
+    for frame in &self.call_stack {
+      write!(f, "\n    at {frame}")?;
+    }
+    Ok(())
+  }
+}
@@ -1,57 +1,39 @@
 use crate::values::boolean::BooleanValue;
+use crate::values::function::FunctionValue;
 use crate::values::number::NumberValue;
-use crate::values::object::ObjectValue;
 use crate::values::string::StringValue;
 use crate::values::Value;
 use std::fmt;
 use std::fmt::Display;
 
+// values print the way js would convert them to a string, functions the way node shows them
 impl Display for Value {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-    match &self {
-      Value::String(s) => write!(f, "{}", s),
-      Value::Boolean(b) => write!(f, "{}", b),
-      Value::Number(n) => write!(f, "{}", n),
-      Value::Undefined => write!(f, "undefined"),
-      Value::Object(obj) => write!(f, "{}", obj),
-      Value::EOL => write!(f, "End of Line"),
-      // _ => write!(f, "Unknown Value"),
+    match self {
+      Value::Function(function) => write!(f, "{}", function),
+      Value::NativeFunction(function) => write!(f, "[Function: {}]", function.get_name()),
+      _ => write!(f, "{}", self.to_js_string()),
     }
   }
 }
-impl Display for NumberValue {
+impl Display for FunctionValue {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-    match &self {
-      NumberValue::Integer(i) => write!(f, "{}", i),
-      NumberValue::Float(fl) => write!(f, "{}", fl),
-      _ => write!(f, "Unknown Number"),
-    }
+    write!(f, "[Function: {}]", self.get_name())
   }
 }
-impl Display for BooleanValue {
+impl Display for NumberValue {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-    match &self {
-      BooleanValue::True => write!(f, "Boolean(true)"),
-      BooleanValue::False => write!(f, "Boolean(false)"),
-    }
+    write!(f, "{}", self.to_js_string())
   }
 }
-
-impl Display for ObjectValue {
+impl Display for BooleanValue {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-    match &self {
-      // ObjectValue::Object(obj) => write!(f, "{}", obj),
-      // ObjectValue::Array(arr) => write!(f, "{}", arr),
-      // ObjectValue::Function(func) => write!(f, "{}", func),
-      ObjectValue::Null => write!(f, "undefined"),
-    }
+    write!(f, "{}", self.get_value())
   }
 }
 
 impl Display for StringValue {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-    match &self {
-      StringValue::String(s) => write!(f, "{}", s),
-    }
+    write!(f, "{}", self.get_value())
   }
 }
@@ -4,66 +4,298 @@ Copyright 2024 Yazalde Filimone <yazaldefilimon@gmail.com>
 
 */
 
+/*
+The heap does not own what it collects, and this departs on purpose from a heap that owns objects behind
+handle indices. `Value::Object`, `Value::Array` and `Value::Function` keep their `Rc` ownership, so every
+existing holder of a value (natives, the constant pools, the host) works unchanged and acyclic garbage is
+still freed the moment its last reference goes. The heap only adds a side table of weak handles, keyed
+by identity, and breaks the cycles reference counting can't free by clearing the unreachable ones.
+Natives build objects in rust without going through the vm, so the side table can't be filled only at the
+allocation opcodes. Every object and array constructor records itself in the thread-local `CONSTRUCTED`
+list while an engine runs, and the heap adopts that list after each allocation.
+Functions aren't tracked: they are created at compile time and reached through the constant pools.
+*/
+
+use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
-use std::sync::{Arc, Mutex};
+use std::mem::size_of;
 
-#[derive(Debug)]
-pub struct Object {
-  pub value: i32,
-  pub marked: bool,
-}
+use crate::values::array::WeakArray;
+use crate::values::collection::InternalSlot;
+use crate::values::object::WeakObject;
+use crate::values::{ArrayValue, ObjectValue, Value};
 
-#[derive(Debug)]
+// bytes allocated before the first collection, later ones wait until the heap doubles
+const INITIAL_THRESHOLD: usize = 1 << 20;
+
+// objects and arrays share their contents through reference counting, which frees them as soon as nothing
+// points at them but never frees a cycle (`a.b = b; b.a = a`). the heap keeps a weak handle to each one the
+// vm allocates, keyed by identity, and a collection marks everything reachable from the roots: whatever is
+// tracked but unmarked can only be reached from other garbage, so it's cleared and the cycle falls apart.
+// functions aren't allocated at runtime, they're reached through the constant pools.
+// objects and arrays are recorded when they're constructed, so the ones natives build in rust (the nested
+// values of `JSON.parse`, the pairs of `Object.entries`) are tracked too, not only what the vm pushes
+#[derive(Debug, Default)]
 pub struct Heap {
-  objects: HashMap<usize, Arc<Mutex<Object>>>,
-  next_id: usize,
+  allocations: HashMap<usize, Allocation>,
+  // estimated size of the tracked values, measured when they were tracked and again at each collection
+  bytes_allocated: usize,
+  next_collection: usize,
+  collections: usize,
+  freed: usize,
 }
 
-impl Heap {
-  pub fn new() -> Self {
-    Heap { objects: HashMap::new(), next_id: 0 }
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeapStats {
+  // tracked values that were still alive after the last collection, plus the ones tracked since
+  pub live: usize,
+  pub bytes_allocated: usize,
+  pub collections: usize,
+  // values reclaimed so far, by a collection or by their last reference going away
+  pub freed: usize,
+}
+
+#[derive(Debug, Clone)]
+enum Allocation {
+  Object(WeakObject),
+  Array(WeakArray),
+}
+
+thread_local! {
+  // objects and arrays constructed on this thread while an engine runs, until its heap adopts them.
+  // None outside a run, values built by the host belong to no heap
+  static CONSTRUCTED: RefCell<Option<Vec<Allocation>>> = const { RefCell::new(None) };
+}
+
+// starts recording constructed values, false when a run further up already is
+pub fn start_recording() -> bool {
+  CONSTRUCTED.with(|constructed| {
+    let mut constructed = constructed.borrow_mut();
+    if constructed.is_some() {
+      return false;
+    }
+    *constructed = Some(Vec::new());
+    true
+  })
+}
+
+// stops recording, what was recorded and not adopted yet is dropped
+pub fn stop_recording() {
+  CONSTRUCTED.with(|constructed| *constructed.borrow_mut() = None);
+}
+
+pub(crate) fn record_object(object: &ObjectValue) {
+  record(|| Allocation::Object(object.downgrade()));
+}
+
+pub(crate) fn record_array(array: &ArrayValue) {
+  record(|| Allocation::Array(array.downgrade()));
+}
+
+fn record(allocation: impl FnOnce() -> Allocation) {
+  CONSTRUCTED.with(|constructed| {
+    if let Some(constructed) = constructed.borrow_mut().as_mut() {
+      constructed.push(allocation());
+    }
+  });
+}
+
+impl Allocation {
+  fn upgrade(&self) -> Option<Value> {
+    match self {
+      Allocation::Object(object) => object.upgrade().map(Value::Object),
+      Allocation::Array(array) => array.upgrade().map(Value::Array),
+    }
   }
+}
 
-  pub fn allocate(&mut self, value: i32) -> usize {
-    let id = self.next_id;
-    self.next_id += 1;
-    let object = Arc::new(Mutex::new(Object { value, marked: false }));
-    self.objects.insert(id, object);
-    id
+impl Heap {
+  pub fn new() -> Self {
+    Self::default()
   }
 
-  pub fn get(&self, id: usize) -> Option<Arc<Mutex<Object>>> {
-    self.objects.get(&id).cloned()
+  // starts tracking an object or array, primitives and values already tracked are ignored
+  pub fn track(&mut self, value: &Value) {
+    let allocation = match value {
+      Value::Object(object) => Allocation::Object(object.downgrade()),
+      Value::Array(array) => Allocation::Array(array.downgrade()),
+      _ => return,
+    };
+    let id = reference_id(value).unwrap();
+    // an address is reused once its value is freed, a dead entry there is replaced
+    if self
+      .allocations
+      .get(&id)
+      .is_some_and(|tracked| tracked.upgrade().is_some())
+    {
+      return;
+    }
+    self.allocations.insert(id, allocation);
+    self.bytes_allocated += allocation_size(value);
   }
 
-  pub fn mark(&self, id: usize) {
-    if let Some(object) = self.objects.get(&id) {
-      let mut object = object.lock().unwrap();
-      if !object.marked {
-        object.marked = true;
-        // todo: mark recursively objects referenced here, if any
+  // tracks every value recorded since the last call that's still alive
+  pub fn adopt_constructed(&mut self) {
+    let constructed = CONSTRUCTED.with(|constructed| constructed.borrow_mut().as_mut().map(std::mem::take));
+    for allocation in constructed.into_iter().flatten() {
+      if let Some(value) = allocation.upgrade() {
+        self.track(&value);
       }
     }
   }
 
-  pub fn sweep(&mut self) {
-    self.objects.retain(|_, object| {
-      let mut object = object.lock().unwrap();
-      if object.marked {
-        object.marked = false;
-        return true;
+  // a small heap still waits for the initial threshold, so it isn't collected on every allocation
+  pub fn should_collect(&self) -> bool {
+    self.bytes_allocated >= self.next_collection.max(INITIAL_THRESHOLD)
+  }
+
+  // mark and sweep from `roots`, returns how many tracked values were reclaimed
+  pub fn collect(&mut self, roots: &[Value]) -> usize {
+    self.adopt_constructed();
+    let marked = mark(roots);
+    let mut freed = 0;
+    let mut bytes_allocated = 0;
+    self.allocations.retain(|id, allocation| match allocation.upgrade() {
+      // its last reference went away on its own
+      None => {
+        freed += 1;
+        false
+      }
+      Some(value) if marked.contains(id) => {
+        bytes_allocated += allocation_size(&value);
+        true
+      }
+      Some(value) => {
+        clear(&value);
+        freed += 1;
+        false
       }
-      return false;
     });
+    self.bytes_allocated = bytes_allocated;
+    self.next_collection = bytes_allocated * 2;
+    self.collections += 1;
+    self.freed += freed;
+    freed
+  }
+
+  pub fn stats(&self) -> HeapStats {
+    HeapStats {
+      live: self.allocations.len(),
+      bytes_allocated: self.bytes_allocated,
+      collections: self.collections,
+      freed: self.freed,
+    }
   }
+}
 
-  pub fn collect_garbage(&mut self, roots: &HashSet<usize>) {
-    // Marcar a partir das raízes
-    for &root in roots {
-      self.mark(root);
+// the identity of every value reachable from `roots`, following properties, prototypes, elements,
+// collection entries, bound receivers, captured `this` values and the constants of functions
+fn mark(roots: &[Value]) -> HashSet<usize> {
+  let mut marked = HashSet::new();
+  let mut pending = roots.to_vec();
+  while let Some(value) = pending.pop() {
+    // bound copies of a native share its identity but not their receiver, so each one is followed
+    if let Value::NativeFunction(native) = &value {
+      pending.push(native.get_this().clone());
+      pending.extend(native.get_prototype().map(Value::Object));
+      continue;
+    }
+    // same for the `this` an arrow captured
+    if let Value::Function(function) = &value {
+      pending.extend(function.get_this().cloned());
+    }
+    let Some(id) = reference_id(&value) else {
+      continue;
+    };
+    if !marked.insert(id) {
+      continue;
+    }
+    match value {
+      Value::Object(object) => {
+        pending.extend(object.get_properties().into_iter().map(|(_, property)| property));
+        pending.extend(object.get_prototype().map(Value::Object));
+        if let Some(InternalSlot::Map(data) | InternalSlot::Set(data)) = &*object.internal_slot() {
+          pending.extend(data.values().cloned());
+        }
+      }
+      Value::Array(array) => pending.extend(array.get_elements().iter().cloned()),
+      Value::Function(function) => {
+        pending.extend(function.get_chunk().constants.iter().cloned());
+        pending.extend(function.peek_prototype_object().map(Value::Object));
+      }
+      _ => {}
     }
+  }
+  marked
+}
+
+// breaks every reference the value holds, the rest of its cycle is then freed by reference counting
+fn clear(value: &Value) {
+  match value {
+    Value::Object(object) => object.clear(),
+    Value::Array(array) => array.get_elements_mut().clear(),
+    _ => {}
+  }
+}
+
+fn reference_id(value: &Value) -> Option<usize> {
+  match value {
+    Value::Object(object) => Some(object.id()),
+    Value::Array(array) => Some(array.id()),
+    Value::Function(function) => Some(function.id()),
+    _ => None,
+  }
+}
+
+// a rough size: the value itself and its properties or elements, not what they point to
+fn allocation_size(value: &Value) -> usize {
+  match value {
+    Value::Object(object) => size_of::<ObjectValue>() + object.len() * size_of::<Value>(),
+    Value::Array(array) => size_of::<ArrayValue>() + array.len() * size_of::<Value>(),
+    _ => 0,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use oxc_allocator::Allocator;
+
+  use crate::compiler::compile;
+  use crate::context::Context;
+  use crate::stack::Stack;
+  use crate::vm::core::Engine;
+
+  // runs `source`, then collects with whatever it left reachable as the roots
+  fn run_and_collect(source: &str) -> (usize, super::HeapStats) {
+    let mut ctx = Context::with_builtins();
+    let compiled = compile(&Allocator::default(), &source.to_string(), &mut ctx).unwrap();
+    let mut stack = Stack::new(64);
+    let mut vm = Engine::new(&mut ctx, &mut stack, &compiled);
+    vm.run().unwrap();
+    let freed = vm.collect_garbage();
+    drop(vm);
+    (freed, ctx.heap().stats())
+  }
+
+  #[test]
+  fn a_large_cycle_is_freed_once_its_root_is_dropped() {
+    let zeros = vec!["0"; 999].join(", ");
+    let source = format!(
+      "let root = {{ next: null }}; let last = root;
+      for (let i of [{zeros}]) {{ last.next = {{ next: null }}; last = last.next; }}
+      last.next = root; root = null; last = null;"
+    );
+    let (freed, stats) = run_and_collect(&source);
+    assert!(freed >= 1000, "freed {freed}");
+    assert_eq!(stats.live, 0);
+    assert_eq!(stats.collections, 1);
+  }
 
-    // Limpar objetos não marcados
-    self.sweep();
+  #[test]
+  fn a_cycle_between_values_a_native_built_is_freed() {
+    let source = r#"let inner = JSON.parse('{"a": {"b": {}}}').a; inner.b.up = inner; inner = null;"#;
+    let (_, stats) = run_and_collect(source);
+    assert_eq!(stats.live, 0);
+    assert!(stats.freed >= 3, "freed {}", stats.freed);
   }
 }
@@ -9,5 +9,6 @@ mod heap;
 mod stack_frame;
 
 pub use self::gc_value::GCValue;
-pub use self::heap::Heap;
+pub(crate) use self::heap::{record_array, record_object};
+pub use self::heap::{start_recording, stop_recording, Heap, HeapStats};
 pub use self::stack_frame::StackFrame;
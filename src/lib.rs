@@ -0,0 +1,23 @@
+/*
+Copyright 2024 Yazalde Filimone <yazaldefilimon@gmail.com>
+
+*/
+
+// the engine as a library, the binary and the benchmarks build on it
+pub mod assembler;
+pub mod builtins;
+pub mod bytecode;
+pub mod compiler;
+pub mod context;
+pub mod diagnostics;
+pub mod disassembler;
+pub mod errors;
+pub mod formatting;
+pub mod gc;
+pub mod parser;
+pub mod stack;
+#[cfg(test)]
+mod test_support;
+pub mod utils;
+pub mod values;
+pub mod vm;
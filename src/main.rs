@@ -4,29 +4,88 @@ Copyright 2024 Yazalde Filimone <yazaldefilimon@gmail.com>
 */
 
 // modules
-mod assembler;
-mod bytecode;
 mod cli;
-mod compiler;
-mod context;
-mod diagnostics;
-mod disassembler;
-mod errors;
-mod formatting;
-mod gc;
-mod parser;
-mod stack;
-mod utils;
-mod values;
-mod vm;
 //  uses
 use cli::command_line;
-use vm::core;
+use core_engine::bytecode::chunk::Chunk;
+use core_engine::disassembler::Disassembler;
+use core_engine::vm::core;
+use core_engine::{compiler, context};
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+// extension of the files written by `compile`
+const BYTECODE_EXTENSION: &str = "cbc";
 
 fn run(source: String, is_debug: bool) {
-  let mut ctx = context::Context::new();
-  let result = core::Engine::bootstrap(&mut ctx, &source, is_debug);
-  println!("{:?}", result);
+  let mut ctx = context::Context::with_builtins();
+  match core::Engine::bootstrap(&mut ctx, &source, is_debug) {
+    Ok(result) => println!("{:?}", result),
+    Err(error) => {
+      eprintln!("{}", error);
+      std::process::exit(1);
+    }
+  }
+}
+
+fn run_bytecode(file: &str, is_debug: bool) {
+  let bytes = std::fs::read(file).expect("could not read file");
+  let chunk = Chunk::deserialize(&bytes).unwrap_or_else(|error| {
+    eprintln!("error: {}: {}", file, error);
+    std::process::exit(1);
+  });
+  let mut ctx = context::Context::with_builtins();
+  match core::Engine::bootstrap_chunk(&mut ctx, chunk, is_debug) {
+    Ok(result) => println!("{:?}", result),
+    Err(error) => {
+      eprintln!("error: {}: {}", file, error);
+      std::process::exit(1);
+    }
+  }
+}
+
+// reads one entry per line and prints its value, errors are reported and the session goes on
+fn repl() {
+  let mut ctx = context::Context::with_builtins();
+  let stdin = io::stdin();
+  let mut lines = stdin.lock().lines();
+  loop {
+    print!("> ");
+    io::stdout().flush().expect("could not flush stdout");
+    let Some(Ok(line)) = lines.next() else {
+      break;
+    };
+    if line.trim().is_empty() {
+      continue;
+    }
+    match core::Engine::bootstrap_repl(&mut ctx, &line) {
+      Ok(result) => println!("{}", result.debug_repr()),
+      Err(error) => eprintln!("{}", error),
+    }
+  }
+}
+
+fn compile(file: &str, output: &str, is_debug: bool) {
+  let source = std::fs::read_to_string(file).expect("could not read file");
+  let mut ctx = context::Context::with_builtins();
+  let arena_allocator = oxc_allocator::Allocator::default();
+  let compiled = compiler::compile(&arena_allocator, &source, &mut ctx).unwrap_or_else(|error| {
+    eprintln!("error: {}: {}", file, error);
+    std::process::exit(1);
+  });
+  if is_debug {
+    Disassembler::new(&compiled.chunk, file, &ctx)
+      .with_constants(true)
+      .with_variables(true)
+      .print();
+  }
+  std::fs::write(output, compiled.chunk.serialize()).expect("could not write bytecode file");
+}
+
+fn is_bytecode_file(file: &str) -> bool {
+  Path::new(file)
+    .extension()
+    .is_some_and(|extension| extension == BYTECODE_EXTENSION)
 }
 
 fn main() {
@@ -36,14 +95,25 @@ fn main() {
     Some(("run", matches)) => {
       let file = matches.get_one::<String>("file").unwrap();
       let debug = matches.get_flag("debug");
+      if is_bytecode_file(file) {
+        run_bytecode(file, debug);
+        return;
+      }
       let source = std::fs::read_to_string(file).expect("could not read file");
       run(source, debug);
     }
+    Some(("repl", _)) => repl(),
     Some(("compile", matches)) => {
       let file = matches.get_one::<String>("file").unwrap();
       let debug = matches.get_flag("debug");
-      let source = std::fs::read_to_string(file).expect("could not read file");
-      run(source, debug);
+      let output = match matches.get_one::<String>("output") {
+        Some(output) => output.clone(),
+        None => Path::new(file)
+          .with_extension(BYTECODE_EXTENSION)
+          .to_string_lossy()
+          .into_owned(),
+      };
+      compile(file, &output, debug);
     }
     _ => {
       panic!("Unknown command");
@@ -36,6 +36,39 @@ impl Stack {
   pub fn is_empty(&self) -> bool {
     self.stack.is_empty()
   }
+  pub fn len(&self) -> usize {
+    self.stack.len()
+  }
+
+  pub fn values(&self) -> &[Value] {
+    &self.stack
+  }
+
+  // absolute access, used for the argument slots of a call frame
+  #[inline(always)]
+  pub fn get(&self, index: usize) -> Result<&Value, EngineError> {
+    self.stack.get(index).ok_or(EngineError::StackUnderflow)
+  }
+  #[inline(always)]
+  pub fn set(&mut self, index: usize, value: Value) -> Result<(), EngineError> {
+    let slot = self.stack.get_mut(index).ok_or(EngineError::StackUnderflow)?;
+    *slot = value;
+    Ok(())
+  }
+  // the `count` values on top, oldest first (the arguments of a native call)
+  pub fn top(&self, count: usize) -> &[Value] {
+    &self.stack[self.stack.len() - count..]
+  }
+  // takes a value out from under the top, used to lift the receiver of a method call
+  pub fn remove(&mut self, index: usize) -> Result<Value, EngineError> {
+    if index >= self.stack.len() {
+      return Err(EngineError::StackUnderflow);
+    }
+    Ok(self.stack.remove(index))
+  }
+  pub fn truncate(&mut self, len: usize) {
+    self.stack.truncate(len);
+  }
   pub fn push_in_global_scope(&mut self, value: Value, frame: usize) {
     self.stack.insert(frame, value);
   }
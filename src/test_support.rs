@@ -0,0 +1,48 @@
+//! Fixtures shared by the unit tests of several modules.
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use crate::context::Context;
+use crate::errors::{EngineError, RuntimeError};
+use crate::values::Value;
+use crate::vm::core::Engine;
+
+// runs `source` as one repl entry against the builtins, its last expression statement is the result
+pub fn run(source: &str) -> Value {
+  let mut ctx = Context::with_builtins();
+  Engine::bootstrap_repl(&mut ctx, &source.to_string()).unwrap()
+}
+
+// the error `source` stops with when it has to fail at run time
+pub fn runtime_error(source: &str) -> RuntimeError {
+  let mut ctx = Context::with_builtins();
+  let error = Engine::bootstrap_repl(&mut ctx, &source.to_string()).unwrap_err();
+  match error {
+    EngineError::Other(error) => *error.downcast::<RuntimeError>().unwrap(),
+    error => panic!("expected a runtime error, got {}", error),
+  }
+}
+
+// a writer whose clones all append to the same bytes, to hand one to a context and read it after
+#[derive(Clone, Default)]
+pub struct SharedBuffer {
+  bytes: Rc<RefCell<Vec<u8>>>,
+}
+
+impl SharedBuffer {
+  pub fn contents(&self) -> String {
+    String::from_utf8(self.bytes.borrow().clone()).unwrap()
+  }
+}
+
+impl Write for SharedBuffer {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.bytes.borrow_mut().extend_from_slice(buf);
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
@@ -1,30 +1,161 @@
 use crate::bytecode::opcode;
 pub const STACK_LIMIT: usize = 1024;
+pub const FRAMES_LIMIT: usize = 256;
 
-pub fn opcode_to_string(opcode: usize) -> String {
+pub fn opcode_to_string(opcode: u8) -> String {
   match opcode {
-    opcode::OPCODE_HALF => "HALF".to_string(),
+    opcode::OPCODE_HALT => "HALT".to_string(),
     opcode::OPCODE_CONST => "CONST".to_string(),
+    opcode::OPCODE_CONST_LONG => "CONST_LONG".to_string(),
     opcode::OPCODE_ADD => "ADD".to_string(),
     opcode::OPCODE_SUB => "SUB".to_string(),
     opcode::OPCODE_MUL => "MUL".to_string(),
     opcode::OPCODE_DIV => "DIV".to_string(),
     opcode::OPCODE_EQ => "EQ".to_string(),
+    opcode::OPCODE_WEAK_EQ => "WEAK_EQ".to_string(),
+    opcode::OPCODE_LT => "LT".to_string(),
+    opcode::OPCODE_LE => "LE".to_string(),
+    opcode::OPCODE_GT => "GT".to_string(),
+    opcode::OPCODE_GE => "GE".to_string(),
+    opcode::OPCODE_NE => "NE".to_string(),
+    opcode::OPCODE_NOT => "NOT".to_string(),
     opcode::OPCODE_JUMP => "JUMP".to_string(),
     opcode::OPCODE_JUMP_IF_FALSE => "JUMP_IF_FALSE".to_string(),
     opcode::OPCODE_LOAD_GLOBAL_SCOPE => "LOAD_GLOBAL".to_string(),
     opcode::OPCODE_SET_GLOBAL_SCOPE => "SET_GLOBAL".to_string(),
-    opcode::OPCODE_LOAD_LOCAL_SCOPE => "LOAD_LOCAL".to_string(),
-    opcode::OPCODE_SET_LOCAL_SCOPE => "SET_LOCAL".to_string(),
+    opcode::OPCODE_LOAD_GLOBAL_SCOPE_LONG => "LOAD_GLOBAL_LONG".to_string(),
+    opcode::OPCODE_SET_GLOBAL_SCOPE_LONG => "SET_GLOBAL_LONG".to_string(),
+    opcode::OPCODE_GET_LOCAL => "GET_LOCAL".to_string(),
+    opcode::OPCODE_SET_LOCAL => "SET_LOCAL".to_string(),
     opcode::OPCODE_POP => "POP".to_string(),
+    opcode::OPCODE_LOAD_THIS => "LOAD_THIS".to_string(),
+    opcode::OPCODE_CALL => "CALL".to_string(),
+    opcode::OPCODE_RETURN => "RETURN".to_string(),
+    opcode::OPCODE_LOAD_ARGUMENT => "LOAD_ARGUMENT".to_string(),
+    opcode::OPCODE_SET_ARGUMENT => "SET_ARGUMENT".to_string(),
+    opcode::OPCODE_NEW_ARRAY => "NEW_ARRAY".to_string(),
+    opcode::OPCODE_GET_PROPERTY => "GET_PROPERTY".to_string(),
+    opcode::OPCODE_GET_ELEMENT => "GET_ELEMENT".to_string(),
+    opcode::OPCODE_NEW_OBJECT => "NEW_OBJECT".to_string(),
+    opcode::OPCODE_INIT_PROPERTY => "INIT_PROPERTY".to_string(),
+    opcode::OPCODE_NEW => "NEW".to_string(),
+    opcode::OPCODE_SET_PROPERTY => "SET_PROPERTY".to_string(),
+    opcode::OPCODE_SET_ELEMENT => "SET_ELEMENT".to_string(),
+    opcode::OPCODE_DUP => "DUP".to_string(),
+    opcode::OPCODE_CALL_METHOD => "CALL_METHOD".to_string(),
+    opcode::OPCODE_INSTANCEOF => "INSTANCEOF".to_string(),
+    opcode::OPCODE_ITERATE => "ITERATE".to_string(),
+    opcode::OPCODE_ITERATE_NEXT => "ITERATE_NEXT".to_string(),
+    opcode::OPCODE_ENUMERATE => "ENUMERATE".to_string(),
+    opcode::OPCODE_SWAP => "SWAP".to_string(),
+    opcode::OPCODE_ADD_INT => "ADD_INT".to_string(),
+    opcode::OPCODE_SUB_INT => "SUB_INT".to_string(),
+    opcode::OPCODE_MUL_INT => "MUL_INT".to_string(),
+    opcode::OPCODE_ADD_CONST => "ADD_CONST".to_string(),
+    opcode::OPCODE_SET_LOCAL_POP => "SET_LOCAL_POP".to_string(),
+    opcode::OPCODE_SET_GLOBAL_POP => "SET_GLOBAL_POP".to_string(),
+    opcode::OPCODE_THROW => "THROW".to_string(),
+    opcode::OPCODE_SETUP_TRY => "SETUP_TRY".to_string(),
+    opcode::OPCODE_POP_TRY => "POP_TRY".to_string(),
+    opcode::OPCODE_LT_CONST => "LT_CONST".to_string(),
     _ => "UNKNOWN".to_string(),
   }
 }
 
+// number of operand bytes that follow the opcode in the code vector
+pub fn opcode_operands(opcode: u8) -> usize {
+  match opcode {
+    opcode::OPCODE_CONST
+    | opcode::OPCODE_LOAD_GLOBAL_SCOPE
+    | opcode::OPCODE_SET_GLOBAL_SCOPE
+    | opcode::OPCODE_ADD_CONST
+    | opcode::OPCODE_LT_CONST
+    | opcode::OPCODE_SET_GLOBAL_POP
+    | opcode::OPCODE_CALL
+    | opcode::OPCODE_NEW
+    | opcode::OPCODE_CALL_METHOD
+    | opcode::OPCODE_LOAD_ARGUMENT
+    | opcode::OPCODE_SET_ARGUMENT => 1,
+    opcode::OPCODE_JUMP
+    | opcode::OPCODE_JUMP_IF_FALSE
+    | opcode::OPCODE_ITERATE_NEXT
+    | opcode::OPCODE_SETUP_TRY
+    | opcode::OPCODE_GET_LOCAL
+    | opcode::OPCODE_SET_LOCAL
+    | opcode::OPCODE_SET_LOCAL_POP
+    | opcode::OPCODE_SCOPE_EXIT
+    | opcode::OPCODE_NEW_ARRAY => 2,
+    opcode::OPCODE_CONST_LONG
+    | opcode::OPCODE_LOAD_GLOBAL_SCOPE_LONG
+    | opcode::OPCODE_SET_GLOBAL_SCOPE_LONG
+    | opcode::OPCODE_GET_PROPERTY
+    | opcode::OPCODE_INIT_PROPERTY
+    | opcode::OPCODE_SET_PROPERTY => 4,
+    _ => 0,
+  }
+}
+
+// the wide form of an opcode that takes an index, used once the index doesn't fit in one byte
+pub fn long_opcode(opcode: u8) -> Option<u8> {
+  match opcode {
+    opcode::OPCODE_CONST => Some(opcode::OPCODE_CONST_LONG),
+    opcode::OPCODE_LOAD_GLOBAL_SCOPE => Some(opcode::OPCODE_LOAD_GLOBAL_SCOPE_LONG),
+    opcode::OPCODE_SET_GLOBAL_SCOPE => Some(opcode::OPCODE_SET_GLOBAL_SCOPE_LONG),
+    _ => None,
+  }
+}
+
+// little-endian operand of `width` bytes at `offset`, None when the code ends before it
+pub fn decode_operand(code: &[u8], offset: usize, width: usize) -> Option<usize> {
+  let bytes = code.get(offset..offset.checked_add(width)?)?;
+  Some(bytes.iter().rev().fold(0, |value, &byte| (value << 8) | byte as usize))
+}
+
+// overwrites the operand of `width` bytes at `offset`, used to patch jumps
+pub fn encode_operand(code: &mut [u8], offset: usize, width: usize, value: usize) {
+  code[offset..offset + width].copy_from_slice(&value.to_le_bytes()[..width]);
+}
+
+// instructions whose operand is a signed jump distance. the one of SETUP_TRY is where its catch block starts
+pub fn is_jump(opcode: u8) -> bool {
+  matches!(
+    opcode,
+    opcode::OPCODE_JUMP | opcode::OPCODE_JUMP_IF_FALSE | opcode::OPCODE_ITERATE_NEXT | opcode::OPCODE_SETUP_TRY
+  )
+}
+
+// absolute target of the jump at `offset`, its operand is a signed distance from the next instruction
+pub fn jump_target(code: &[u8], offset: usize) -> Option<usize> {
+  let width = opcode_operands(*code.get(offset)?);
+  let distance = decode_operand(code, offset + 1, width)? as u16 as i16;
+  (offset + 1 + width).checked_add_signed(distance as isize)
+}
+
+// points the jump at `offset` to `target`, false when the distance doesn't fit in the operand
+pub fn patch_jump_target(code: &mut [u8], offset: usize, target: usize) -> bool {
+  let width = opcode_operands(code[offset]);
+  let distance = target as isize - (offset + 1 + width) as isize;
+  let Ok(distance) = i16::try_from(distance) else {
+    return false;
+  };
+  encode_operand(code, offset + 1, width, distance as u16 as usize);
+  true
+}
+
+// binary search over the (offset, line) table, entries are sorted by offset
+pub fn line_for_offset(lines: &[(usize, u32)], offset: usize) -> Option<u32> {
+  let index = lines.partition_point(|&(start, _)| start <= offset);
+  if index == 0 {
+    return None;
+  }
+  Some(lines[index - 1].1)
+}
+
 pub fn is_internal_variable(name: &str) -> bool {
   match name {
-    "globalThis " | "undefined " | "NaN " | "Infinity " | "Object " | "Function " | "Array " | "String "
-    | "Number " | "Boolean " | "Math " | "Date " | "RegExp " | "Error " | "console " => true,
+    "globalThis" | "undefined" | "NaN" | "Infinity" | "Object" | "Function" | "Array" | "String" | "Number"
+    | "Boolean" | "Math" | "Date" | "RegExp" | "Error" | "TypeError" | "RangeError" | "Map" | "Set" | "console"
+    | "JSON" => true,
     _ => false,
   }
 }
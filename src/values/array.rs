@@ -0,0 +1,137 @@
+/*
+Copyright 2024 Yazalde Filimone <yazaldefilimon@gmail.com>
+
+
+*/
+
+/*
+An Array is an exotic object that gives special treatment to array index property keys.
+Here it's a list of values shared by every reference to the array, so changes made through one are seen by all.
+
+@links:
+- https://tc39.es/ecma262/#sec-array-exotic-objects
+*/
+use std::cell::{Ref, RefCell, RefMut};
+use std::rc::{Rc, Weak};
+
+use crate::gc;
+use crate::values::Value;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArrayValue {
+  elements: Rc<RefCell<Vec<Value>>>,
+}
+
+impl ArrayValue {
+  pub fn new(elements: Vec<Value>) -> Self {
+    let array = ArrayValue { elements: Rc::new(RefCell::new(elements)) };
+    gc::record_array(&array);
+    array
+  }
+
+  pub fn get_elements(&self) -> Ref<'_, Vec<Value>> {
+    self.elements.borrow()
+  }
+
+  pub fn get_elements_mut(&self) -> RefMut<'_, Vec<Value>> {
+    self.elements.borrow_mut()
+  }
+
+  pub fn len(&self) -> usize {
+    self.elements.borrow().len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.elements.borrow().is_empty()
+  }
+
+  // out of range reads are undefined, like in js
+  pub fn get(&self, index: usize) -> Value {
+    self
+      .elements
+      .borrow()
+      .get(index)
+      .cloned()
+      .unwrap_or_else(Value::create_undefined_value)
+  }
+
+  // identity, arrays compare by reference
+  pub fn is_same(&self, other: &ArrayValue) -> bool {
+    Rc::ptr_eq(&self.elements, &other.elements)
+  }
+
+  // the identity as a number, equal exactly when `is_same` is
+  pub fn id(&self) -> usize {
+    Rc::as_ptr(&self.elements) as usize
+  }
+
+  pub fn downgrade(&self) -> WeakArray {
+    WeakArray { elements: Rc::downgrade(&self.elements) }
+  }
+
+  // Array.prototype.join, null and undefined elements become empty strings
+  pub fn join(&self, separator: &str) -> String {
+    self.join_visiting(separator, &mut Vec::new())
+  }
+
+  pub fn to_js_string(&self) -> String {
+    self.join(",")
+  }
+
+  // `visiting` holds the arrays being joined further up, one that contains itself joins as the empty string
+  fn join_visiting(&self, separator: &str, visiting: &mut Vec<usize>) -> String {
+    if visiting.contains(&self.id()) {
+      return String::new();
+    }
+    visiting.push(self.id());
+    let elements = self.elements.borrow();
+    let parts: Vec<String> = elements
+      .iter()
+      .map(|element| match element {
+        Value::Undefined(_) | Value::Null(_) => String::new(),
+        Value::Array(array) => array.join_visiting(",", visiting),
+        _ => element.to_js_string(),
+      })
+      .collect();
+    visiting.pop();
+    parts.join(separator)
+  }
+}
+
+// an array the collector tracks without keeping it alive
+#[derive(Debug, Clone)]
+pub struct WeakArray {
+  elements: Weak<RefCell<Vec<Value>>>,
+}
+
+impl WeakArray {
+  // none once the last reference to the array is gone
+  pub fn upgrade(&self) -> Option<ArrayValue> {
+    self.elements.upgrade().map(|elements| ArrayValue { elements })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::values::Value;
+
+  #[test]
+  fn arrays_join_their_elements_with_commas() {
+    let number = Value::create_number_value;
+    assert_eq!(Value::create_array_value(vec![]).to_js_string(), "");
+    let inner = Value::create_array_value(vec![number(2.0), number(3.0)]);
+    let outer = Value::create_array_value(vec![number(1.0), inner, Value::create_array_value(vec![])]);
+    assert_eq!(outer.to_js_string(), "1,2,3,");
+  }
+
+  #[test]
+  fn an_array_inside_itself_joins_as_the_empty_string() {
+    use crate::test_support::run;
+    assert_eq!(run("let b = [1]; b[0] = b; b.join(\"-\")").to_js_string(), "");
+    assert_eq!(run("let c = [1, 2]; c.push(c); \"\" + c").to_js_string(), "1,2,");
+    assert_eq!(
+      run("let d = [1]; let e = [d, 2]; d[0] = e; e.join(\"-\")").to_js_string(),
+      "-2"
+    );
+  }
+}
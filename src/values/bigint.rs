@@ -7,7 +7,7 @@ Copyright 2024 Yazalde Filimone <yazaldefilimon@gmail.com>
 /*
 @links:
 */
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct BigIntValue {
   // todo: check if i128 is correct in t39 spec
   value: i128,
@@ -17,4 +17,8 @@ impl BigIntValue {
   pub fn new(value: i128) -> Self {
     BigIntValue { value }
   }
+
+  pub fn get_value(&self) -> i128 {
+    self.value
+  }
 }
@@ -0,0 +1,141 @@
+/*
+Copyright 2024 Yazalde Filimone <yazaldefilimon@gmail.com>
+
+
+*/
+
+/*
+Map and Set objects are collections of values (key/value pairs for a Map) where a key occurs only once.
+Keys compare with SameValueZero: NaN is the same as NaN, -0 the same as +0, and objects, arrays and functions
+are only the same when they're the same reference. Entries are visited in the order they were first inserted.
+
+@links:
+- https://tc39.es/ecma262/#sec-map-objects
+- https://tc39.es/ecma262/#sec-set-objects
+- https://tc39.es/ecma262/#sec-samevaluezero
+*/
+use std::collections::HashMap;
+
+use crate::values::Value;
+
+// the [[MapData]] or [[SetData]] of an object, a Set keeps each value as its own key
+#[derive(Debug, Clone, PartialEq)]
+pub enum InternalSlot {
+  Map(CollectionData),
+  Set(CollectionData),
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CollectionData {
+  // a deleted entry leaves a hole, so a forEach running while entries are deleted doesn't skip any
+  entries: Vec<Option<(Value, Value)>>,
+  positions: HashMap<CollectionKey, usize>,
+}
+
+// a key that hashes the way SameValueZero compares, references by their identity
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum CollectionKey {
+  Undefined,
+  Null,
+  Boolean(bool),
+  Number(u64),
+  BigInt(i128),
+  String(String),
+  Symbol(String),
+  Reference(usize),
+}
+
+impl CollectionKey {
+  fn new(value: &Value) -> Self {
+    match value {
+      Value::Undefined(_) => CollectionKey::Undefined,
+      Value::Null(_) => CollectionKey::Null,
+      Value::Boolean(boolean) => CollectionKey::Boolean(boolean.get_value()),
+      Value::Number(number) => {
+        let number = number.get_value();
+        // every NaN is the same key, and -0 is +0
+        let number = if number.is_nan() {
+          f64::NAN
+        } else if number == 0.0 {
+          0.0
+        } else {
+          number
+        };
+        CollectionKey::Number(number.to_bits())
+      }
+      Value::BigInt(bigint) => CollectionKey::BigInt(bigint.get_value()),
+      Value::String(string) => CollectionKey::String(string.get_value().to_string()),
+      Value::Symbol(symbol) => CollectionKey::Symbol(symbol.get_value().to_string()),
+      Value::Object(object) => CollectionKey::Reference(object.id()),
+      Value::Array(array) => CollectionKey::Reference(array.id()),
+      Value::Function(function) => CollectionKey::Reference(function.id()),
+      Value::NativeFunction(function) => CollectionKey::Reference(function.id()),
+      Value::RegExp(regexp) => CollectionKey::Reference(regexp.id()),
+    }
+  }
+}
+
+impl CollectionData {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn get(&self, key: &Value) -> Option<Value> {
+    let position = self.positions.get(&CollectionKey::new(key))?;
+    self.entries[*position].as_ref().map(|(_, value)| value.clone())
+  }
+
+  // a key already present keeps its position and only gets the new value
+  pub fn set(&mut self, key: Value, value: Value) {
+    let collection_key = CollectionKey::new(&key);
+    if let Some(position) = self.positions.get(&collection_key) {
+      self.entries[*position] = Some((key, value));
+      return;
+    }
+    // -0 is stored as +0, like the spec does
+    let key = match key {
+      Value::Number(number) if number.get_value() == 0.0 => Value::create_number_value(0.0),
+      key => key,
+    };
+    self.positions.insert(collection_key, self.entries.len());
+    self.entries.push(Some((key, value)));
+  }
+
+  pub fn has(&self, key: &Value) -> bool {
+    self.positions.contains_key(&CollectionKey::new(key))
+  }
+
+  pub fn delete(&mut self, key: &Value) -> bool {
+    match self.positions.remove(&CollectionKey::new(key)) {
+      Some(position) => {
+        self.entries[position] = None;
+        true
+      }
+      None => false,
+    }
+  }
+
+  pub fn size(&self) -> usize {
+    self.positions.len()
+  }
+
+  // every key and value still in the collection, what it keeps alive
+  pub fn values(&self) -> impl Iterator<Item = &Value> {
+    self.entries.iter().flatten().flat_map(|(key, value)| [key, value])
+  }
+
+  // the first live entry at or after `position`, with the position it was found at. walking with it sees
+  // entries added while walking and skips the ones deleted
+  pub fn next_entry(&self, position: usize) -> Option<(usize, Value, Value)> {
+    self
+      .entries
+      .iter()
+      .enumerate()
+      .skip(position)
+      .find_map(|(position, entry)| {
+        entry
+          .as_ref()
+          .map(|(key, value)| (position, key.clone(), value.clone()))
+      })
+  }
+}
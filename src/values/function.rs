@@ -0,0 +1,95 @@
+/*
+Copyright 2024 Yazalde Filimone <yazaldefilimon@gmail.com>
+
+
+*/
+
+/*
+A function object is an ordinary object that has a [[Call]] internal method.
+Here it holds the bytecode compiled from the function body, with its own constant pool and line table.
+Its `prototype` object, the [[Prototype]] of what `new` creates, is only made when first needed.
+
+@links:
+- https://tc39.es/ecma262/#sec-ecmascript-function-objects
+*/
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::bytecode::chunk::Chunk;
+use crate::values::{ObjectValue, Value};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionValue {
+  name: String,
+  arity: usize,
+  // arrows don't bind `this`, they see the one of the frame that created them
+  is_arrow: bool,
+  // that `this`, set when the arrow expression is evaluated
+  this: Option<Box<Value>>,
+  // shared, so copying the function around doesn't copy its code
+  chunk: Rc<Chunk>,
+  // shared like the chunk, every copy of the function sees the same `prototype`
+  prototype: Rc<RefCell<Option<ObjectValue>>>,
+}
+
+impl FunctionValue {
+  pub fn new(name: String, arity: usize, is_arrow: bool, chunk: Chunk) -> Self {
+    let prototype = Rc::new(RefCell::new(None));
+    FunctionValue { name, arity, is_arrow, this: None, chunk: Rc::new(chunk), prototype }
+  }
+
+  pub fn get_name(&self) -> &str {
+    &self.name
+  }
+
+  pub fn get_arity(&self) -> usize {
+    self.arity
+  }
+
+  pub fn is_arrow(&self) -> bool {
+    self.is_arrow
+  }
+
+  // a copy of an arrow that remembers the `this` around it, the chunk and prototype stay shared
+  pub fn bind_this(&self, this: Value) -> Self {
+    FunctionValue { this: Some(Box::new(this)), ..self.clone() }
+  }
+
+  // the `this` an arrow captured, None for other functions
+  pub fn get_this(&self) -> Option<&Value> {
+    self.this.as_deref()
+  }
+
+  pub fn get_chunk(&self) -> &Chunk {
+    &self.chunk
+  }
+
+  // the chunk itself, for a holder that outlives its borrow of the function
+  pub fn shared_chunk(&self) -> Rc<Chunk> {
+    Rc::clone(&self.chunk)
+  }
+
+  // `function.prototype`, created empty on first access
+  pub fn get_prototype_object(&self) -> ObjectValue {
+    self.prototype.borrow_mut().get_or_insert_with(ObjectValue::new).clone()
+  }
+
+  // `function.prototype` without creating it, none until something reads it
+  pub fn peek_prototype_object(&self) -> Option<ObjectValue> {
+    self.prototype.borrow().clone()
+  }
+
+  pub fn set_prototype_object(&self, prototype: ObjectValue) {
+    *self.prototype.borrow_mut() = Some(prototype);
+  }
+
+  // identity, copies of the same function share their chunk
+  pub fn is_same(&self, other: &FunctionValue) -> bool {
+    Rc::ptr_eq(&self.chunk, &other.chunk)
+  }
+
+  // the identity as a number, equal exactly when `is_same` is
+  pub fn id(&self) -> usize {
+    Rc::as_ptr(&self.chunk) as usize
+  }
+}
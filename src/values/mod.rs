@@ -13,27 +13,42 @@ An ECMAScript language value is a value that is characterized by an ECMAScript l
 */
 #![allow(dead_code)]
 
+use std::borrow::Cow;
+use std::rc::Rc;
+
+use self::native::NativeFn;
+
+pub mod array;
 pub mod bigint;
 pub mod boolean;
+pub mod collection;
+pub mod function;
+#[cfg(feature = "nan-boxing")]
+pub mod nan_box;
+pub mod native;
 pub mod null;
 pub mod number;
 pub mod object;
+pub mod regexp;
+pub mod shape;
 pub mod string;
 pub mod symbol;
 pub mod undefined;
 
-// pub use self::array::ArrayValue;
-// pub use self::function::FunctionValue;
+pub use self::array::ArrayValue;
 pub use self::bigint::BigIntValue;
 pub use self::boolean::BooleanValue;
+pub use self::function::FunctionValue;
+pub use self::native::NativeFunctionValue;
 pub use self::null::NullValue;
 pub use self::number::NumberValue;
 pub use self::object::ObjectValue;
+pub use self::regexp::RegExpValue;
 pub use self::string::StringValue;
 pub use self::symbol::SymbolValue;
 pub use self::undefined::UndefinedValue;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Value {
   Undefined(UndefinedValue),
   Null(NullValue),
@@ -43,6 +58,10 @@ pub enum Value {
   Number(NumberValue),
   BigInt(BigIntValue),
   Object(ObjectValue),
+  Function(FunctionValue),
+  NativeFunction(NativeFunctionValue),
+  Array(ArrayValue),
+  RegExp(RegExpValue),
 }
 
 impl Value {
@@ -62,11 +81,15 @@ impl Value {
     Value::String(StringValue::new(value))
   }
 
+  pub fn create_interned_string_value(value: Rc<str>) -> Self {
+    Value::String(StringValue::from_interned(value))
+  }
+
   pub fn create_symbol_value(value: String) -> Self {
     Value::Symbol(SymbolValue::new(value))
   }
 
-  pub fn create_number_value(value: i64) -> Self {
+  pub fn create_number_value(value: f64) -> Self {
     Value::Number(NumberValue::new(value))
   }
 
@@ -77,6 +100,438 @@ impl Value {
   pub fn create_object_value() -> Self {
     Value::Object(ObjectValue::new())
   }
+
+  pub fn create_function_value(value: FunctionValue) -> Self {
+    Value::Function(value)
+  }
+
+  pub fn create_array_value(elements: Vec<Value>) -> Self {
+    Value::Array(ArrayValue::new(elements))
+  }
+
+  pub fn create_native_function_value(name: &str, arity: usize, function: NativeFn) -> Self {
+    Value::NativeFunction(NativeFunctionValue::new(name.to_string(), arity, function))
+  }
+
+  pub fn create_regexp_value(pattern: &str, flags: &str) -> Self {
+    Value::RegExp(RegExpValue::new(pattern, flags))
+  }
+
+  /*
+  7.1.17 ToString ( argument )
+  The string a value turns into when concatenated or printed, objects still skip ToPrimitive.
+
+  @links:
+  - https://tc39.es/ecma262/#sec-tostring
+  */
+  pub fn to_js_string(&self) -> String {
+    match self {
+      Value::Undefined(_) => "undefined".to_string(),
+      Value::Null(_) => "null".to_string(),
+      Value::Boolean(boolean) => boolean.get_value().to_string(),
+      Value::String(string) => string.get_value().to_string(),
+      Value::Symbol(symbol) => format!("Symbol({})", symbol.get_value()),
+      Value::Number(number) => number.to_js_string(),
+      Value::BigInt(bigint) => bigint.get_value().to_string(),
+      Value::Object(_) => "[object Object]".to_string(),
+      Value::Array(array) => array.to_js_string(),
+      Value::RegExp(regexp) => regexp.to_js_string(),
+      // there's no source text to show, like a native function
+      Value::Function(function) => format!("function {}() {{ [native code] }}", function.get_name()),
+      Value::NativeFunction(function) => format!("function {}() {{ [native code] }}", function.get_name()),
+    }
+  }
+
+  // node-like rendering for the disassembler and console: `[ 1, 'a' ]`, `{ a: 1 }`, strings quoted
+  pub fn debug_repr(&self) -> String {
+    self.debug_repr_visiting(&mut Vec::new())
+  }
+
+  // `visiting` holds the ids of the arrays and objects being printed further up, a value that
+  // contains itself prints as `[Circular]` there
+  fn debug_repr_visiting(&self, visiting: &mut Vec<usize>) -> String {
+    match self {
+      Value::String(string) => format!("'{}'", string.get_value()),
+      // only here, `String(10n)` is "10"
+      Value::BigInt(bigint) => format!("{}n", bigint.get_value()),
+      Value::Array(array) if visiting.contains(&array.id()) => "[Circular]".to_string(),
+      Value::Object(object) if visiting.contains(&object.id()) => "[Circular]".to_string(),
+      Value::Array(array) => {
+        visiting.push(array.id());
+        let elements: Vec<String> = array
+          .get_elements()
+          .iter()
+          .map(|element| element.debug_repr_visiting(visiting))
+          .collect();
+        visiting.pop();
+        if elements.is_empty() {
+          return "[]".to_string();
+        }
+        format!("[ {} ]", elements.join(", "))
+      }
+      Value::Object(object) => {
+        visiting.push(object.id());
+        let properties: Vec<String> = object
+          .get_properties()
+          .iter()
+          .map(|(key, value)| format!("{}: {}", key, value.debug_repr_visiting(visiting)))
+          .collect();
+        visiting.pop();
+        if properties.is_empty() {
+          return "{}".to_string();
+        }
+        format!("{{ {} }}", properties.join(", "))
+      }
+      _ => self.to_string(),
+    }
+  }
+
+  /*
+  7.1.19 ToPropertyKey ( argument )
+  The string a value names a property with. Strings, like the interned keys in the constant pool,
+  are borrowed instead of copied.
+
+  @links:
+  - https://tc39.es/ecma262/#sec-topropertykey
+  */
+  pub fn to_property_key(&self) -> Cow<'_, str> {
+    match self {
+      Value::String(string) => Cow::Borrowed(string.get_value()),
+      _ => Cow::Owned(self.to_js_string()),
+    }
+  }
+
+  // what the `typeof` operator answers for this value, also how error messages name it
+  pub fn type_name(&self) -> &'static str {
+    match self {
+      Value::Undefined(_) => "undefined",
+      Value::Null(_) | Value::Object(_) | Value::Array(_) | Value::RegExp(_) => "object",
+      Value::Boolean(_) => "boolean",
+      Value::String(_) => "string",
+      Value::Symbol(_) => "symbol",
+      Value::Number(_) => "number",
+      Value::BigInt(_) => "bigint",
+      Value::Function(_) | Value::NativeFunction(_) => "function",
+    }
+  }
+
+  /*
+  7.2.15 IsStrictlyEqual ( x, y )
+  Values of different types are never equal, NaN is not equal to itself and +0 is equal to -0.
+
+  @links:
+  - https://tc39.es/ecma262/#sec-isstrictlyequal
+  */
+  pub fn strict_equals(&self, other: &Value) -> bool {
+    match (self, other) {
+      (Value::Undefined(_), Value::Undefined(_)) => true,
+      (Value::Null(_), Value::Null(_)) => true,
+      (Value::Boolean(left), Value::Boolean(right)) => left.get_value() == right.get_value(),
+      (Value::String(left), Value::String(right)) => left == right,
+      // f64 comparison already follows the spec for NaN and signed zeros
+      (Value::Number(left), Value::Number(right)) => left.get_value() == right.get_value(),
+      (Value::BigInt(left), Value::BigInt(right)) => left.get_value() == right.get_value(),
+      (Value::Symbol(left), Value::Symbol(right)) => left.get_value() == right.get_value(),
+      (Value::Function(left), Value::Function(right)) => left.is_same(right),
+      (Value::NativeFunction(left), Value::NativeFunction(right)) => left.is_same(right),
+      (Value::Array(left), Value::Array(right)) => left.is_same(right),
+      (Value::Object(left), Value::Object(right)) => left.is_same(right),
+      (Value::RegExp(left), Value::RegExp(right)) => left.is_same(right),
+      _ => false,
+    }
+  }
+
+  /*
+  7.1.2 ToBoolean ( argument )
+  undefined, null, false, +0, -0, NaN, 0n and the empty string are falsy, every other value
+  (including "0" and any object) is truthy.
+
+  @links:
+  - https://tc39.es/ecma262/#sec-toboolean
+  */
+  pub fn to_boolean(&self) -> bool {
+    match self {
+      Value::Undefined(_) | Value::Null(_) => false,
+      Value::Boolean(boolean) => boolean.get_value(),
+      Value::Number(number) => number.get_value() != 0.0 && !number.get_value().is_nan(),
+      Value::String(string) => !string.get_value().is_empty(),
+      Value::BigInt(bigint) => bigint.get_value() != 0,
+      Value::Symbol(_)
+      | Value::Object(_)
+      | Value::Array(_)
+      | Value::Function(_)
+      | Value::NativeFunction(_)
+      | Value::RegExp(_) => true,
+    }
+  }
+
+  pub fn is_undefined(&self) -> bool {
+    matches!(self, Value::Undefined(_))
+  }
+
+  pub fn is_null(&self) -> bool {
+    matches!(self, Value::Null(_))
+  }
+
+  pub fn is_boolean(&self) -> bool {
+    matches!(self, Value::Boolean(_))
+  }
+
+  pub fn get_boolean(&self) -> bool {
+    match self {
+      Value::Boolean(boolean) => boolean.get_value(),
+      _ => panic!("Value is not a boolean"),
+    }
+  }
+
+  pub fn is_number(&self) -> bool {
+    matches!(self, Value::Number(_))
+  }
+
+  pub fn as_number(&self) -> Option<f64> {
+    match self {
+      Value::Number(number) => Some(number.get_value()),
+      _ => None,
+    }
+  }
+
+  #[deprecated(note = "panics on other values, use `as_number`")]
+  pub fn get_number(&self) -> f64 {
+    self.as_number().expect("Value is not a number")
+  }
+
+  pub fn is_string(&self) -> bool {
+    matches!(self, Value::String(_))
+  }
+
+  pub fn as_string(&self) -> Option<&str> {
+    match self {
+      Value::String(string) => Some(string.get_value()),
+      _ => None,
+    }
+  }
+
+  #[deprecated(note = "panics on other values, use `as_string`")]
+  pub fn get_string(&self) -> &str {
+    self.as_string().expect("Value is not a string")
+  }
+
+  pub fn is_bigint(&self) -> bool {
+    matches!(self, Value::BigInt(_))
+  }
+
+  pub fn get_bigint(&self) -> i128 {
+    match self {
+      Value::BigInt(bigint) => bigint.get_value(),
+      _ => panic!("Value is not a bigint"),
+    }
+  }
+
+  pub fn is_object(&self) -> bool {
+    matches!(self, Value::Object(_))
+  }
+
+  pub fn get_object(&self) -> &ObjectValue {
+    match self {
+      Value::Object(object) => object,
+      _ => panic!("Value is not an object"),
+    }
+  }
+
+  pub fn is_array(&self) -> bool {
+    matches!(self, Value::Array(_))
+  }
+
+  pub fn get_array(&self) -> &ArrayValue {
+    match self {
+      Value::Array(array) => array,
+      _ => panic!("Value is not an array"),
+    }
+  }
+
+  pub fn is_function(&self) -> bool {
+    matches!(self, Value::Function(_))
+  }
+
+  pub fn get_function(&self) -> &FunctionValue {
+    match self {
+      Value::Function(function) => function,
+      _ => panic!("Value is not a function"),
+    }
+  }
+
+  // the truthiness every conditional (`if`, `!`, `&&`, `||`) goes through
+  pub fn is_truthy(&self) -> bool {
+    self.to_boolean()
+  }
+
+  /*
+  7.1.4 ToNumber ( argument )
+  undefined is NaN, null and false are 0, true is 1 and strings go through StringToNumber.
+
+  @links:
+  - https://tc39.es/ecma262/#sec-tonumber
+  */
+  pub fn to_number(&self) -> f64 {
+    match self {
+      Value::Undefined(_) => f64::NAN,
+      Value::Null(_) => 0.0,
+      Value::Boolean(boolean) => boolean.get_value() as u8 as f64,
+      Value::Number(number) => number.get_value(),
+      Value::String(string) => string_to_number(string.get_value()),
+      // js throws a TypeError for these, there's no way to report it from here yet
+      Value::Symbol(_) | Value::BigInt(_) => f64::NAN,
+      // ToPrimitive of an array is its joined string, so `[] -> 0` and `[5] -> 5`
+      Value::Object(_) | Value::Function(_) | Value::NativeFunction(_) | Value::RegExp(_) | Value::Array(_) => {
+        self.to_primitive().to_number()
+      }
+    }
+  }
+
+  /*
+  7.1.1 ToPrimitive ( input [ , preferredType ] )
+  Primitives are returned as they are, objects become the string their toString gives. user defined
+  valueOf/toString methods aren't called, a value can't run code, so the built-in string is used.
+
+  @links:
+  - https://tc39.es/ecma262/#sec-toprimitive
+  */
+  pub fn to_primitive(&self) -> Value {
+    match self {
+      Value::Object(_) | Value::Array(_) | Value::RegExp(_) | Value::Function(_) | Value::NativeFunction(_) => {
+        Value::create_string_value(self.to_js_string())
+      }
+      _ => self.clone(),
+    }
+  }
+
+  pub fn is_primitive(&self) -> bool {
+    !matches!(
+      self,
+      Value::Object(_) | Value::Array(_) | Value::RegExp(_) | Value::Function(_) | Value::NativeFunction(_)
+    )
+  }
+
+  /*
+  7.2.13 IsLessThan ( x, y, LeftFirst )
+  Two strings compare by their UTF-16 code units, anything else as numbers (bigints exactly against each
+  other). None stands for undefined, a NaN was involved and every relational operator gives false.
+
+  @links:
+  - https://tc39.es/ecma262/#sec-islessthan
+  */
+  pub fn less_than(&self, other: &Value) -> Option<bool> {
+    match (self.to_primitive(), other.to_primitive()) {
+      (Value::String(left), Value::String(right)) => {
+        Some(left.get_value().encode_utf16().lt(right.get_value().encode_utf16()))
+      }
+      (Value::BigInt(left), Value::BigInt(right)) => Some(left.get_value() < right.get_value()),
+      (left, right) => {
+        let left = numeric_value(&left);
+        let right = numeric_value(&right);
+        (!left.is_nan() && !right.is_nan()).then_some(left < right)
+      }
+    }
+  }
+
+  /*
+  7.2.14 IsLooselyEqual ( x, y )
+  Same types compare strictly, null and undefined only equal each other, otherwise
+  booleans become numbers and strings become numbers (or bigints) until both sides match.
+
+  @links:
+  - https://tc39.es/ecma262/#sec-islooselyequal
+  */
+  pub fn loose_equals(&self, other: &Value) -> bool {
+    match (self, other) {
+      (Value::Undefined(_) | Value::Null(_), Value::Undefined(_) | Value::Null(_)) => true,
+      (Value::Undefined(_) | Value::Null(_), _) | (_, Value::Undefined(_) | Value::Null(_)) => false,
+      (Value::Boolean(boolean), _) => Value::create_number_value(boolean.get_value() as u8 as f64).loose_equals(other),
+      (_, Value::Boolean(boolean)) => self.loose_equals(&Value::create_number_value(boolean.get_value() as u8 as f64)),
+      (Value::Number(number), Value::String(string)) | (Value::String(string), Value::Number(number)) => {
+        number.get_value() == string_to_number(string.get_value())
+      }
+      (Value::BigInt(bigint), Value::String(string)) | (Value::String(string), Value::BigInt(bigint)) => {
+        string_to_bigint(string.get_value()).is_some_and(|value| value == bigint.get_value())
+      }
+      // compared exactly, `2n ** 53n + 1n` would round to 2 ** 53 as a number
+      (Value::BigInt(bigint), Value::Number(number)) | (Value::Number(number), Value::BigInt(bigint)) => {
+        let number = number.get_value();
+        let limit = 2f64.powi(127);
+        number.fract() == 0.0 && (-limit..limit).contains(&number) && number as i128 == bigint.get_value()
+      }
+      // an object against a primitive compares its primitive, two objects compare by identity
+      (_, _) if self.is_primitive() != other.is_primitive() => self.to_primitive().loose_equals(&other.to_primitive()),
+      _ => self.strict_equals(other),
+    }
+  }
+}
+
+// ToNumeric for a relational comparison, a bigint against a number compares by its value
+fn numeric_value(value: &Value) -> f64 {
+  match value {
+    Value::BigInt(bigint) => bigint.get_value() as f64,
+    value => value.to_number(),
+  }
+}
+
+/*
+7.1.4.1.1 StringToNumber ( str )
+Surrounding whitespace is ignored, an empty string is 0 and anything that isn't a numeric literal is NaN.
+
+@links:
+- https://tc39.es/ecma262/#sec-stringtonumber
+*/
+pub fn string_to_number(value: &str) -> f64 {
+  let value = value.trim();
+  if value.is_empty() {
+    return 0.0;
+  }
+  let radix = match value.get(..2) {
+    Some("0x" | "0X") => Some(16),
+    Some("0o" | "0O") => Some(8),
+    Some("0b" | "0B") => Some(2),
+    _ => None,
+  };
+  if let Some(radix) = radix {
+    let digits = &value[2..];
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+      return f64::NAN;
+    }
+    return digits
+      .chars()
+      .fold(0.0, |total, c| total * radix as f64 + c.to_digit(radix).unwrap() as f64);
+  }
+  match value.strip_prefix(['+', '-']).unwrap_or(value) {
+    "Infinity" => {
+      return if value.starts_with('-') {
+        f64::NEG_INFINITY
+      } else {
+        f64::INFINITY
+      }
+    }
+    // rust also accepts "inf" and "nan", js doesn't
+    unsigned if !unsigned.starts_with(|c: char| c.is_ascii_digit() || c == '.') => return f64::NAN,
+    _ => {}
+  }
+  value.parse::<f64>().unwrap_or(f64::NAN)
+}
+
+// StringToBigInt, None where js would produce undefined (the string isn't an integer literal)
+fn string_to_bigint(value: &str) -> Option<i128> {
+  let value = value.trim();
+  if value.is_empty() {
+    return Some(0);
+  }
+  let radix = match value.get(..2) {
+    Some("0x" | "0X") => 16,
+    Some("0o" | "0O") => 8,
+    Some("0b" | "0B") => 2,
+    _ => return value.parse::<i128>().ok(),
+  };
+  i128::from_str_radix(&value[2..], radix)
+    .ok()
+    .filter(|_| !value[2..].starts_with(['+', '-']))
 }
 
 // ----------------
@@ -227,3 +682,77 @@ impl Value {
 //     Value::Number(NumberValue::Binary(value))
 //   }
 // }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn number(value: f64) -> Value {
+    Value::create_number_value(value)
+  }
+
+  fn string(value: &str) -> Value {
+    Value::create_string_value(value.to_string())
+  }
+
+  #[test]
+  fn loose_equality_converts_between_types() {
+    let falsy = Value::create_boolean_value(false);
+    assert!(number(0.0).loose_equals(&falsy));
+    assert!(string("").loose_equals(&number(0.0)));
+    assert!(!Value::create_null_value().loose_equals(&number(0.0)));
+    assert!(Value::create_null_value().loose_equals(&Value::create_undefined_value()));
+    assert!(number(1.0).loose_equals(&string("1")));
+    assert!(!number(f64::NAN).loose_equals(&number(f64::NAN)));
+  }
+
+  #[test]
+  fn loose_equality_compares_bigints_and_numbers_exactly() {
+    let power = 2i128.pow(53);
+    assert!(Value::create_bigint_value(power).loose_equals(&number(power as f64)));
+    assert!(!Value::create_bigint_value(power + 1).loose_equals(&number(power as f64)));
+    assert!(!Value::create_bigint_value(i128::MAX).loose_equals(&number(2f64.powi(127))));
+  }
+
+  #[test]
+  fn loose_equality_turns_objects_into_primitives() {
+    let array = Value::create_array_value(vec![number(1.0), number(2.0)]);
+    assert!(array.loose_equals(&string("1,2")));
+    assert!(Value::create_array_value(vec![]).loose_equals(&number(0.0)));
+    assert!(Value::create_object_value().loose_equals(&string("[object Object]")));
+    assert!(!Value::create_object_value().loose_equals(&Value::create_object_value()));
+  }
+
+  #[test]
+  fn strict_equality_matrix() {
+    let number = Value::create_number_value;
+    let string = |value: &str| Value::create_string_value(value.to_string());
+    let object = Value::create_object_value();
+    let cases = [
+      (number(1.0), number(1.0), true),
+      (number(f64::NAN), number(f64::NAN), false),
+      (number(0.0), number(-0.0), true),
+      (number(1.0), string("1"), false),
+      (string("a"), string("a"), true),
+      (Value::create_null_value(), Value::create_undefined_value(), false),
+      (Value::create_boolean_value(true), number(1.0), false),
+      (Value::create_bigint_value(1), Value::create_bigint_value(1), true),
+      (Value::create_bigint_value(1), number(1.0), false),
+      (object.clone(), object.clone(), true),
+      (object, Value::create_object_value(), false),
+    ];
+    for (left, right, expected) in cases {
+      assert_eq!(left.strict_equals(&right), expected, "{left:?} === {right:?}");
+      assert_eq!(right.strict_equals(&left), expected, "{right:?} === {left:?}");
+    }
+  }
+
+  #[test]
+  fn a_value_inside_itself_prints_as_circular() {
+    use crate::test_support::run;
+    assert_eq!(run("let a = {}; a.self = a; a").debug_repr(), "{ self: [Circular] }");
+    assert_eq!(run("let b = [1]; b.push(b); b").debug_repr(), "[ 1, [Circular] ]");
+    // seen twice but not inside itself, so printed both times
+    assert_eq!(run("let c = { n: 1 }; [c, c]").debug_repr(), "[ { n: 1 }, { n: 1 } ]");
+  }
+}
@@ -0,0 +1,302 @@
+/*
+Copyright 2024 Yazalde Filimone <yazaldefilimon@gmail.com>
+
+
+*/
+
+/*
+A NaN-boxed value fits any ECMAScript value in 64 bits. Doubles are stored as they are (every NaN is
+folded into one canonical NaN), the other values live in the payload of quiet NaNs no arithmetic produces:
+undefined, null and the booleans as small tags, everything on the heap as a pointer to a shared `Value`.
+Only built with the `nan-boxing` feature. It's an encoding next to `Value`, not a replacement for it: the
+engine keeps storing the `Value` enum with either feature set, and a `BoxedValue` converts to and from it
+without losing anything while answering `is_number`, `get_number`, `is_string`, `get_string`, Display and
+equality the way the enum does.
+
+@links:
+- https://tc39.es/ecma262/#sec-ecmascript-language-types-number-type
+*/
+use std::fmt;
+use std::rc::Rc;
+
+use crate::values::Value;
+
+// the one NaN arithmetic results are folded into
+const CANONICAL_NAN: u64 = 0x7FF8_0000_0000_0000;
+// a quiet NaN with the bit below the quiet bit set, never the result of arithmetic on doubles
+const QUIET_NAN: u64 = 0x7FFC_0000_0000_0000;
+// set on top of QUIET_NAN for pointers, the low 48 bits are the address
+const SIGN_BIT: u64 = 0x8000_0000_0000_0000;
+const POINTER_MASK: u64 = 0x0000_FFFF_FFFF_FFFF;
+
+const TAG_UNDEFINED: u64 = QUIET_NAN | 1;
+const TAG_NULL: u64 = QUIET_NAN | 2;
+const TAG_FALSE: u64 = QUIET_NAN | 3;
+const TAG_TRUE: u64 = QUIET_NAN | 4;
+
+pub struct BoxedValue {
+  bits: u64,
+}
+
+impl BoxedValue {
+  pub fn new(value: Value) -> Self {
+    let bits = match value {
+      Value::Undefined(_) => TAG_UNDEFINED,
+      Value::Null(_) => TAG_NULL,
+      Value::Boolean(boolean) if boolean.get_value() => TAG_TRUE,
+      Value::Boolean(_) => TAG_FALSE,
+      Value::Number(number) if number.get_value().is_nan() => CANONICAL_NAN,
+      Value::Number(number) => number.get_value().to_bits(),
+      // strings, bigints, symbols and objects keep their `Value` on the heap, shared by every copy
+      value => {
+        let pointer = Rc::into_raw(Rc::new(value)) as u64;
+        debug_assert_eq!(pointer & !POINTER_MASK, 0, "[Value] pointer wider than 48 bits");
+        SIGN_BIT | QUIET_NAN | pointer
+      }
+    };
+    BoxedValue { bits }
+  }
+
+  // the value back in the enum representation
+  pub fn to_value(&self) -> Value {
+    match self.bits {
+      TAG_UNDEFINED => Value::create_undefined_value(),
+      TAG_NULL => Value::create_null_value(),
+      TAG_FALSE => Value::create_boolean_value(false),
+      TAG_TRUE => Value::create_boolean_value(true),
+      _ => match self.as_heap() {
+        Some(value) => value.clone(),
+        None => Value::create_number_value(f64::from_bits(self.bits)),
+      },
+    }
+  }
+
+  pub fn is_undefined(&self) -> bool {
+    self.bits == TAG_UNDEFINED
+  }
+
+  pub fn is_null(&self) -> bool {
+    self.bits == TAG_NULL
+  }
+
+  pub fn is_boolean(&self) -> bool {
+    self.bits == TAG_FALSE || self.bits == TAG_TRUE
+  }
+
+  pub fn get_boolean(&self) -> bool {
+    match self.bits {
+      TAG_TRUE => true,
+      TAG_FALSE => false,
+      _ => panic!("Value is not a boolean"),
+    }
+  }
+
+  // every bit pattern that isn't one of the quiet NaN encodings is a double
+  pub fn is_number(&self) -> bool {
+    self.bits & QUIET_NAN != QUIET_NAN
+  }
+
+  pub fn as_number(&self) -> Option<f64> {
+    self.is_number().then(|| f64::from_bits(self.bits))
+  }
+
+  pub fn get_number(&self) -> f64 {
+    self.as_number().expect("Value is not a number")
+  }
+
+  pub fn is_string(&self) -> bool {
+    self.as_string().is_some()
+  }
+
+  pub fn as_string(&self) -> Option<&str> {
+    self.as_heap().and_then(Value::as_string)
+  }
+
+  pub fn get_string(&self) -> &str {
+    self.as_string().expect("Value is not a string")
+  }
+
+  fn is_pointer(&self) -> bool {
+    self.bits & (SIGN_BIT | QUIET_NAN) == SIGN_BIT | QUIET_NAN
+  }
+
+  fn as_heap(&self) -> Option<&Value> {
+    if !self.is_pointer() {
+      return None;
+    }
+    let pointer = (self.bits & POINTER_MASK) as *const Value;
+    // the pointer came from `Rc::into_raw` in `new`, this box holds one of its strong counts
+    Some(unsafe { &*pointer })
+  }
+}
+
+impl From<Value> for BoxedValue {
+  fn from(value: Value) -> Self {
+    BoxedValue::new(value)
+  }
+}
+
+impl From<&BoxedValue> for Value {
+  fn from(value: &BoxedValue) -> Self {
+    value.to_value()
+  }
+}
+
+// a copy shares the heap value, it only takes one more strong count
+impl Clone for BoxedValue {
+  fn clone(&self) -> Self {
+    if self.is_pointer() {
+      let pointer = (self.bits & POINTER_MASK) as *const Value;
+      // created by `Rc::into_raw` in `new` and still alive, this box holds a count
+      unsafe { Rc::increment_strong_count(pointer) };
+    }
+    BoxedValue { bits: self.bits }
+  }
+}
+
+impl Drop for BoxedValue {
+  fn drop(&mut self) {
+    if self.is_pointer() {
+      let pointer = (self.bits & POINTER_MASK) as *const Value;
+      // gives back the strong count this box took in `new` or `clone`
+      drop(unsafe { Rc::from_raw(pointer) });
+    }
+  }
+}
+
+// the same answers the enum gives
+impl PartialEq for BoxedValue {
+  fn eq(&self, other: &Self) -> bool {
+    self.to_value() == other.to_value()
+  }
+}
+
+impl fmt::Debug for BoxedValue {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{:?}", self.to_value())
+  }
+}
+
+impl fmt::Display for BoxedValue {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.to_value())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::BoxedValue;
+  use crate::test_support::run;
+  use crate::values::Value;
+
+  #[test]
+  fn a_boxed_value_answers_like_the_enum() {
+    let values = [
+      Value::create_undefined_value(),
+      Value::create_null_value(),
+      Value::create_boolean_value(true),
+      Value::create_boolean_value(false),
+      Value::create_number_value(1.5),
+      Value::create_number_value(-0.0),
+      Value::create_number_value(f64::INFINITY),
+      Value::create_string_value("boxed".to_string()),
+      Value::create_bigint_value(10),
+    ];
+    for value in values {
+      let boxed = BoxedValue::new(value.clone());
+      assert_eq!(boxed.to_value(), value);
+      assert_eq!(boxed.is_undefined(), value.is_undefined());
+      assert_eq!(boxed.is_null(), value.is_null());
+      assert_eq!(boxed.is_boolean(), value.is_boolean());
+      assert_eq!(boxed.is_number(), value.is_number());
+      assert_eq!(boxed.is_string(), value.is_string());
+      if let Some(number) = value.as_number() {
+        assert_eq!(boxed.get_number().to_bits(), number.to_bits());
+      }
+      if let Some(string) = value.as_string() {
+        assert_eq!(boxed.get_string(), string);
+      }
+      assert_eq!(boxed.clone(), boxed);
+    }
+    let nan = BoxedValue::new(Value::create_number_value(f64::NAN));
+    assert!(nan.is_number() && nan.get_number().is_nan());
+  }
+
+  #[test]
+  fn a_boxed_value_is_one_word() {
+    assert_eq!(std::mem::size_of::<BoxedValue>(), 8);
+  }
+
+  #[test]
+  fn every_nan_is_a_number_whatever_its_payload() {
+    // the bit patterns of the tags and of a pointer, reached as doubles
+    for bits in [
+      0x7FFC_0000_0000_0001,
+      0x7FFC_0000_0000_0004,
+      0xFFFC_0000_1234_5678,
+      0xFFF8_0000_0000_0000,
+    ] {
+      let boxed = BoxedValue::new(Value::create_number_value(f64::from_bits(bits)));
+      assert!(boxed.is_number() && boxed.get_number().is_nan(), "{:#X}", bits);
+      assert!(!boxed.is_undefined() && !boxed.is_boolean() && !boxed.is_string());
+      assert_eq!(boxed.to_string(), "NaN");
+    }
+  }
+
+  #[test]
+  fn copies_share_the_heap_value_and_the_last_one_frees_it() {
+    let array = Value::create_array_value(vec![Value::create_number_value(1.0)]);
+    let Value::Array(handle) = &array else { unreachable!() };
+    let weak = handle.downgrade();
+    let boxed = BoxedValue::new(array.clone());
+    let copy = boxed.clone();
+    assert!(std::ptr::eq(boxed.as_heap().unwrap(), copy.as_heap().unwrap()));
+    drop(array);
+    drop(boxed);
+    // still reachable through the copy, and the same array as before
+    let Value::Array(back) = copy.to_value() else {
+      panic!("the copy holds the array")
+    };
+    assert!(back.is_same(&weak.upgrade().unwrap()));
+    drop(back);
+    drop(copy);
+    assert!(weak.upgrade().is_none());
+  }
+
+  #[test]
+  fn objects_keep_their_identity_through_a_box() {
+    let object = run("let o = { a: 1 }; o");
+    let boxed = BoxedValue::new(object.clone());
+    assert_eq!(boxed.to_value(), object);
+    assert!(boxed.to_value().strict_equals(&object));
+    let other = run("let o = { a: 1 }; o");
+    assert!(!BoxedValue::new(other).to_value().strict_equals(&object));
+  }
+
+  #[test]
+  fn values_the_engine_produces_print_and_compare_the_same_boxed() {
+    let sources = [
+      "1 + 2",
+      "0 / 0",
+      "\"a\" + 1",
+      "1 === 1",
+      "null",
+      "undefined",
+      "10n * 2n",
+      "[1, [2, 3]].join(\"-\")",
+      "({ a: [1] })",
+      "(x) => x",
+      "-0",
+    ];
+    for source in sources {
+      let value = run(source);
+      let boxed = BoxedValue::new(value.clone());
+      assert_eq!(boxed.to_string(), value.to_string(), "{}", source);
+      assert_eq!(boxed.is_number(), value.is_number(), "{}", source);
+      assert_eq!(boxed.is_string(), value.is_string(), "{}", source);
+      // NaN isn't equal to itself either way
+      assert_eq!(boxed == BoxedValue::from(value.clone()), value == value, "{}", source);
+      assert_eq!(Value::from(&boxed) == value, value == value, "{}", source);
+    }
+  }
+}
@@ -0,0 +1,87 @@
+/*
+Copyright 2024 Yazalde Filimone <yazaldefilimon@gmail.com>
+
+
+*/
+
+/*
+A built-in function object is implemented by the host rather than by ECMAScript code.
+Here it's a plain rust function that receives the engine (for the context and to call back into script
+functions), the `this` value and the call's arguments. Methods of primitives (like `"abc".slice`) are natives bound to their receiver.
+Only natives created with a `prototype` (like `Error`) are constructors, `new` hands them an instance of it as `this`.
+
+@links:
+- https://tc39.es/ecma262/#sec-built-in-function-objects
+*/
+use crate::errors::RuntimeErrorKind;
+use crate::values::{ObjectValue, Value};
+use crate::vm::core::Engine;
+
+// the vm attaches the position of the CALL to any error a native returns
+pub type NativeResult = Result<Value, RuntimeErrorKind>;
+pub type NativeFn = fn(&mut Engine, &Value, &[Value]) -> NativeResult;
+
+#[derive(Debug, Clone)]
+pub struct NativeFunctionValue {
+  name: String,
+  // only reported (like `fn.length`), natives get every argument the script passes
+  arity: usize,
+  function: NativeFn,
+  // the receiver the native was read from, undefined for plain globals
+  this: Box<Value>,
+  // `native.prototype`, none for the natives that can't be called with `new`
+  prototype: Option<ObjectValue>,
+}
+
+impl NativeFunctionValue {
+  pub fn new(name: String, arity: usize, function: NativeFn) -> Self {
+    let this = Box::new(Value::create_undefined_value());
+    NativeFunctionValue { name, arity, function, this, prototype: None }
+  }
+
+  // a native `new` can call, its instances inherit from `prototype`
+  pub fn constructor(name: String, arity: usize, function: NativeFn, prototype: ObjectValue) -> Self {
+    NativeFunctionValue { prototype: Some(prototype), ..NativeFunctionValue::new(name, arity, function) }
+  }
+
+  // a copy that sees `this` as its receiver, how `"abc".slice` remembers its string
+  pub fn bind(&self, this: Value) -> Self {
+    NativeFunctionValue { this: Box::new(this), ..self.clone() }
+  }
+
+  pub fn get_name(&self) -> &str {
+    &self.name
+  }
+
+  pub fn get_arity(&self) -> usize {
+    self.arity
+  }
+
+  pub fn get_prototype(&self) -> Option<ObjectValue> {
+    self.prototype.clone()
+  }
+
+  pub fn get_this(&self) -> &Value {
+    &self.this
+  }
+
+  pub fn call(&self, vm: &mut Engine, arguments: &[Value]) -> NativeResult {
+    (self.function)(vm, &self.this, arguments)
+  }
+
+  // identity, the same rust function registered twice is the same js function
+  pub fn is_same(&self, other: &NativeFunctionValue) -> bool {
+    self.function as usize == other.function as usize
+  }
+
+  // the identity as a number, equal exactly when `is_same` is
+  pub fn id(&self) -> usize {
+    self.function as usize
+  }
+}
+
+impl PartialEq for NativeFunctionValue {
+  fn eq(&self, other: &Self) -> bool {
+    self.name == other.name && self.is_same(other)
+  }
+}
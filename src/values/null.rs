@@ -7,7 +7,7 @@ Copyright 2024 Yazalde Filimone <yazaldefilimon@gmail.com>
 /*
 */
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct NullValue;
 
 impl NullValue {
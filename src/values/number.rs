@@ -5,15 +5,81 @@ Copyright 2024 Yazalde Filimone <yazaldefilimon@gmail.com>
 */
 
 /*
+The Number type has exactly 18437736874454810627 values, the double-precision 64-bit format IEEE 754-2019 values.
+
+@links:
+- https://tc39.es/ecma262/#sec-ecmascript-language-types-number-type
+- https://tc39.es/ecma262/#sec-numeric-types-number-tostring
 */
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct NumberValue {
-  value: i64,
+  value: f64,
 }
 
 impl NumberValue {
-  pub fn new(value: i64) -> Self {
+  pub fn new(value: f64) -> Self {
     NumberValue { value }
   }
+
+  pub fn get_value(&self) -> f64 {
+    self.value
+  }
+
+  // Number::toString(x) with radix 10: integers without a decimal point, exponent notation
+  // from 1e21 up and below 1e-6
+  pub fn to_js_string(&self) -> String {
+    let value = self.value;
+    if value.is_nan() {
+      return "NaN".to_string();
+    }
+    if value == 0.0 {
+      // -0 prints as "0"
+      return "0".to_string();
+    }
+    if value < 0.0 {
+      return format!("-{}", NumberValue::new(-value).to_js_string());
+    }
+    if value.is_infinite() {
+      return "Infinity".to_string();
+    }
+    // rust's `{:e}` gives the shortest digits that round-trip, as the spec asks for
+    let scientific = format!("{:e}", value);
+    let (mantissa, exponent) = scientific.split_once('e').unwrap();
+    let digits = mantissa.replace('.', "");
+    let k = digits.len() as i32;
+    // the decimal point goes after the first `n` digits
+    let n = exponent.parse::<i32>().unwrap() + 1;
+    if k <= n && n <= 21 {
+      return format!("{}{}", digits, "0".repeat((n - k) as usize));
+    }
+    if 0 < n && n <= 21 {
+      return format!("{}.{}", &digits[..n as usize], &digits[n as usize..]);
+    }
+    if -6 < n && n <= 0 {
+      return format!("0.{}{}", "0".repeat(-n as usize), digits);
+    }
+    let sign = if n - 1 > 0 { "+" } else { "-" };
+    if k == 1 {
+      return format!("{}e{}{}", digits, sign, (n - 1).abs());
+    }
+    format!("{}.{}e{}{}", &digits[..1], &digits[1..], sign, (n - 1).abs())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::NumberValue;
+
+  #[test]
+  fn numbers_format_like_number_to_string() {
+    let format = |value: f64| NumberValue::new(value).to_js_string();
+    assert_eq!(format(3.0), "3");
+    assert_eq!(format(-0.0), "0");
+    assert_eq!(format(0.1), "0.1");
+    assert_eq!(format(1e20), "100000000000000000000");
+    assert_eq!(format(1e21), "1e+21");
+    assert_eq!(format(1.5e-7), "1.5e-7");
+    assert_eq!(format(0.000001), "0.000001");
+  }
 }
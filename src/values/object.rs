@@ -5,13 +5,243 @@ Copyright 2024 Yazalde Filimone <yazaldefilimon@gmail.com>
 */
 
 /*
+An Object is logically a collection of properties, each identified by a property key.
+Properties are kept in insertion order and shared by every reference to the object: the keys in the object's
+shape, the values in a dense vector at the slots the shape gives them.
+Every object has a [[Prototype]] (an object or null), reads of a missing property continue there.
+
+@links:
+- https://tc39.es/ecma262/#sec-object-type
+- https://tc39.es/ecma262/#sec-ordinary-object-internal-methods-and-internal-slots
 */
+use std::cell::{RefCell, RefMut};
+use std::rc::{Rc, Weak};
+
+use crate::gc;
+use crate::values::collection::InternalSlot;
+use crate::values::shape::{InlineCache, Shape};
+use crate::values::Value;
 
-#[derive(Debug)]
-pub struct ObjectValue {}
+// longest prototype chain a lookup follows, a chain that loops on itself stops here instead of hanging
+const MAX_PROTOTYPE_DEPTH: usize = 10_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectValue {
+  shape: Rc<RefCell<Rc<Shape>>>,
+  values: Rc<RefCell<Vec<Value>>>,
+  prototype: Rc<RefCell<Option<ObjectValue>>>,
+  // the data of builtins like Map and Set, none for ordinary objects
+  internal_slot: Rc<RefCell<Option<InternalSlot>>>,
+}
 
 impl ObjectValue {
   pub fn new() -> Self {
-    ObjectValue {}
+    let object = ObjectValue {
+      shape: Rc::new(RefCell::new(Shape::root())),
+      values: Rc::new(RefCell::new(Vec::new())),
+      prototype: Rc::new(RefCell::new(None)),
+      internal_slot: Rc::new(RefCell::new(None)),
+    };
+    gc::record_object(&object);
+    object
+  }
+
+  // an empty object whose missing properties are read from `prototype`, what `new` creates
+  pub fn with_prototype(prototype: ObjectValue) -> Self {
+    let object = ObjectValue::new();
+    object.set_prototype(Some(prototype));
+    object
+  }
+
+  pub fn get_prototype(&self) -> Option<ObjectValue> {
+    self.prototype.borrow().clone()
+  }
+
+  pub fn set_prototype(&self, prototype: Option<ObjectValue>) {
+    *self.prototype.borrow_mut() = prototype;
+  }
+
+  pub fn internal_slot(&self) -> RefMut<'_, Option<InternalSlot>> {
+    self.internal_slot.borrow_mut()
+  }
+
+  pub fn set_internal_slot(&self, slot: InternalSlot) {
+    *self.internal_slot.borrow_mut() = Some(slot);
+  }
+
+  pub fn get_own_property(&self, key: &str) -> Option<Value> {
+    let slot = self.shape.borrow().slot(key)?;
+    Some(self.values.borrow()[slot].clone())
+  }
+
+  // the own property read by the GET_PROPERTY at `offset`, the slot comes from `cache` when the object has
+  // the shape last seen there
+  pub fn get_own_property_cached(&self, key: &str, cache: &InlineCache, offset: usize) -> Option<Value> {
+    let shape = self.shape.borrow();
+    let slot = match cache.get(offset, &shape) {
+      Some(slot) => slot,
+      None => {
+        let slot = shape.slot(key)?;
+        cache.set(offset, &shape, slot);
+        slot
+      }
+    };
+    Some(self.values.borrow()[slot].clone())
+  }
+
+  // own property first, then up the prototype chain. missing properties are undefined
+  pub fn get_property(&self, key: &str) -> Value {
+    let mut object = self.clone();
+    for _ in 0..MAX_PROTOTYPE_DEPTH {
+      if let Some(value) = object.get_own_property(key) {
+        return value;
+      }
+      match object.get_prototype() {
+        Some(prototype) => object = prototype,
+        None => break,
+      }
+    }
+    Value::create_undefined_value()
+  }
+
+  // whether `prototype` is somewhere up the chain of this object, the check behind `instanceof`
+  pub fn inherits_from(&self, prototype: &ObjectValue) -> bool {
+    let mut current = self.get_prototype();
+    for _ in 0..MAX_PROTOTYPE_DEPTH {
+      match current {
+        Some(object) if object.is_same(prototype) => return true,
+        Some(object) => current = object.get_prototype(),
+        None => return false,
+      }
+    }
+    false
+  }
+
+  // overwrites in place, so an existing key keeps its position
+  pub fn set_property(&self, key: &str, value: Value) {
+    let mut shape = self.shape.borrow_mut();
+    match shape.slot(key) {
+      Some(slot) => self.values.borrow_mut()[slot] = value,
+      None => {
+        Shape::add_key(&mut shape, key);
+        self.values.borrow_mut().push(value);
+      }
+    }
+  }
+
+  pub fn has_property(&self, key: &str) -> bool {
+    self.shape.borrow().slot(key).is_some()
+  }
+
+  // keys in the order they were first set, the order `Object.keys` and `for-in` see
+  pub fn keys(&self) -> Vec<String> {
+    self.shape.borrow().keys().iter().map(|key| key.to_string()).collect()
+  }
+
+  pub fn get_properties(&self) -> Vec<(String, Value)> {
+    let shape = self.shape.borrow();
+    let values = self.values.borrow();
+    let keys = shape.keys().iter().map(|key| key.to_string());
+    keys.zip(values.iter().cloned()).collect()
+  }
+
+  pub fn len(&self) -> usize {
+    self.values.borrow().len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.values.borrow().is_empty()
+  }
+
+  // identity, objects compare by reference
+  pub fn is_same(&self, other: &ObjectValue) -> bool {
+    Rc::ptr_eq(&self.values, &other.values)
+  }
+
+  // the identity as a number, equal exactly when `is_same` is, so it can key a hash table
+  pub fn id(&self) -> usize {
+    Rc::as_ptr(&self.values) as usize
+  }
+
+  pub fn downgrade(&self) -> WeakObject {
+    WeakObject {
+      shape: Rc::downgrade(&self.shape),
+      values: Rc::downgrade(&self.values),
+      prototype: Rc::downgrade(&self.prototype),
+      internal_slot: Rc::downgrade(&self.internal_slot),
+    }
+  }
+
+  // drops everything the object refers to, how the collector breaks a cycle nothing can reach
+  pub fn clear(&self) {
+    *self.shape.borrow_mut() = Shape::root();
+    self.values.borrow_mut().clear();
+    self.prototype.borrow_mut().take();
+    self.internal_slot.borrow_mut().take();
+  }
+}
+
+// an object the collector tracks without keeping it alive
+#[derive(Debug, Clone)]
+pub struct WeakObject {
+  shape: Weak<RefCell<Rc<Shape>>>,
+  values: Weak<RefCell<Vec<Value>>>,
+  prototype: Weak<RefCell<Option<ObjectValue>>>,
+  internal_slot: Weak<RefCell<Option<InternalSlot>>>,
+}
+
+impl WeakObject {
+  // none once the last reference to the object is gone
+  pub fn upgrade(&self) -> Option<ObjectValue> {
+    Some(ObjectValue {
+      shape: self.shape.upgrade()?,
+      values: self.values.upgrade()?,
+      prototype: self.prototype.upgrade()?,
+      internal_slot: self.internal_slot.upgrade()?,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn a_prototype_cycle_does_not_hang_lookups() {
+    let a = ObjectValue::new();
+    let b = ObjectValue::with_prototype(a.clone());
+    a.set_prototype(Some(b.clone()));
+    assert!(a.get_property("missing").is_undefined());
+    assert!(!a.inherits_from(&ObjectValue::new()));
+    assert!(a.inherits_from(&b));
+    // the cycle would keep both alive
+    a.set_prototype(None);
+  }
+
+  #[test]
+  fn properties_keep_their_insertion_order() {
+    let object = ObjectValue::new();
+    object.set_property("b", Value::create_number_value(1.0));
+    object.set_property("a", Value::create_string_value("x".to_string()));
+    object.set_property("b", Value::create_number_value(2.0));
+    assert_eq!(object.keys(), vec!["b", "a"]);
+    let value = Value::Object(object);
+    assert_eq!(value.to_js_string(), "[object Object]");
+    assert_eq!(value.debug_repr(), "{ b: 2, a: 'x' }");
+    assert_eq!(Value::create_object_value().debug_repr(), "{}");
+  }
+
+  #[test]
+  fn objects_built_alike_share_a_shape() {
+    let build = |keys: &[&str]| {
+      let object = ObjectValue::new();
+      for key in keys {
+        object.set_property(key, Value::create_number_value(1.0));
+      }
+      object
+    };
+    let (a, b, c) = (build(&["x", "y"]), build(&["x", "y"]), build(&["y", "x"]));
+    assert!(Rc::ptr_eq(&a.shape.borrow(), &b.shape.borrow()));
+    assert!(!Rc::ptr_eq(&a.shape.borrow(), &c.shape.borrow()));
   }
 }
@@ -0,0 +1,51 @@
+/*
+Copyright 2024 Yazalde Filimone <yazaldefilimon@gmail.com>
+
+
+*/
+
+/*
+A RegExp object holds a pattern and the flags it was created with (`/ab+c/g` has the source `ab+c`
+and the flags `g`). Only the literal itself is kept for now, matching isn't implemented yet.
+
+@links:
+- https://tc39.es/ecma262/#sec-regexp-regular-expression-objects
+- https://tc39.es/ecma262/#sec-literals-regular-expression-literals
+*/
+use std::rc::Rc;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegExpValue {
+  // shared, every copy of the same literal is the same object
+  pattern: Rc<str>,
+  flags: Rc<str>,
+}
+
+impl RegExpValue {
+  pub fn new(pattern: &str, flags: &str) -> Self {
+    RegExpValue { pattern: Rc::from(pattern), flags: Rc::from(flags) }
+  }
+
+  pub fn get_pattern(&self) -> &str {
+    &self.pattern
+  }
+
+  pub fn get_flags(&self) -> &str {
+    &self.flags
+  }
+
+  // `/pattern/flags`, what String(regexp) gives
+  pub fn to_js_string(&self) -> String {
+    format!("/{}/{}", self.pattern, self.flags)
+  }
+
+  // identity, not the pattern: two literals with the same text are different objects
+  pub fn is_same(&self, other: &RegExpValue) -> bool {
+    Rc::ptr_eq(&self.pattern, &other.pattern)
+  }
+
+  // the identity as a number, equal exactly when `is_same` is
+  pub fn id(&self) -> usize {
+    Rc::as_ptr(&self.pattern) as *const u8 as usize
+  }
+}
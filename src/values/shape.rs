@@ -0,0 +1,175 @@
+/*
+Copyright 2024 Yazalde Filimone <yazaldefilimon@gmail.com>
+
+
+*/
+
+/*
+A shape (hidden class) is the layout of an object's own properties: its keys in insertion order and the slot
+each value is stored at. Adding a key moves an object to the shape reached through a transition cached on its
+current one, so objects that get the same keys in the same order share a shape, and a property read that
+already saw a shape knows the slot without looking the key up again.
+An object that keeps growing stops sharing and gets a dictionary shape of its own, extended in place.
+
+@links:
+- https://v8.dev/docs/hidden-classes
+- https://mathiasbynens.be/notes/shapes-ics
+*/
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::{Rc, Weak};
+
+// keys a shared shape can have, past this an object gets a dictionary shape so building a large object
+// doesn't leave a chain of shapes with a copy of every prefix of its keys
+const MAX_SHARED_KEYS: usize = 64;
+
+thread_local! {
+  // where every object starts, the transitions out of it are shared by the whole program
+  static ROOT: Rc<Shape> = Rc::new(Shape::default());
+}
+
+#[derive(Default)]
+pub struct Shape {
+  keys: Vec<Rc<str>>,
+  slots: HashMap<Rc<str>, usize>,
+  // the shapes one more key leads to, kept alive so the next object with those keys finds them
+  transitions: RefCell<HashMap<Rc<str>, Rc<Shape>>>,
+  dictionary: bool,
+}
+
+impl Shape {
+  pub fn root() -> Rc<Shape> {
+    ROOT.with(Rc::clone)
+  }
+
+  pub fn slot(&self, key: &str) -> Option<usize> {
+    self.slots.get(key).copied()
+  }
+
+  pub fn keys(&self) -> &[Rc<str>] {
+    &self.keys
+  }
+
+  pub fn len(&self) -> usize {
+    self.keys.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.keys.is_empty()
+  }
+
+  // the shape after adding `key`, which gets the next slot. `shape` is replaced, a dictionary shape only
+  // owned by this object is extended in place
+  pub fn add_key(shape: &mut Rc<Shape>, key: &str) {
+    if shape.dictionary {
+      Rc::make_mut(shape).push_key(key.into());
+      return;
+    }
+    if shape.len() >= MAX_SHARED_KEYS {
+      let mut dictionary = Shape { dictionary: true, ..(**shape).clone() };
+      dictionary.push_key(key.into());
+      *shape = Rc::new(dictionary);
+      return;
+    }
+    let next = shape.transitions.borrow().get(key).cloned();
+    let next = next.unwrap_or_else(|| {
+      let mut next = Shape { transitions: RefCell::default(), ..(**shape).clone() };
+      next.push_key(key.into());
+      let next = Rc::new(next);
+      shape.transitions.borrow_mut().insert(key.into(), Rc::clone(&next));
+      next
+    });
+    *shape = next;
+  }
+
+  fn push_key(&mut self, key: Rc<str>) {
+    self.slots.insert(Rc::clone(&key), self.keys.len());
+    self.keys.push(key);
+  }
+}
+
+// a copy has the same keys but none of the transitions, they belong to the shape in the tree
+impl Clone for Shape {
+  fn clone(&self) -> Self {
+    Shape {
+      keys: self.keys.clone(),
+      slots: self.slots.clone(),
+      transitions: RefCell::default(),
+      dictionary: self.dictionary,
+    }
+  }
+}
+
+impl PartialEq for Shape {
+  fn eq(&self, other: &Self) -> bool {
+    self.keys == other.keys
+  }
+}
+
+impl fmt::Debug for Shape {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_list().entries(self.keys.iter()).finish()
+  }
+}
+
+// a monomorphic inline cache for each GET_PROPERTY of a chunk: the shape last read at that offset and the
+// slot the key had in it. the weak handle keeps the shape's address from being reused by another shape
+#[derive(Debug, Default)]
+pub struct InlineCache {
+  entries: RefCell<HashMap<usize, (Weak<Shape>, usize)>>,
+}
+
+impl InlineCache {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  // the cached slot when `shape` is the one last seen at `offset`
+  pub fn get(&self, offset: usize, shape: &Rc<Shape>) -> Option<usize> {
+    let entries = self.entries.borrow();
+    let (cached, slot) = entries.get(&offset)?;
+    std::ptr::eq(cached.as_ptr(), Rc::as_ptr(shape)).then_some(*slot)
+  }
+
+  // a different shape at the same offset replaces the entry
+  pub fn set(&self, offset: usize, shape: &Rc<Shape>, slot: usize) {
+    self.entries.borrow_mut().insert(offset, (Rc::downgrade(shape), slot));
+  }
+}
+
+// a cache is only a hint, a copy starts empty and two chunks don't differ by what they've cached
+impl Clone for InlineCache {
+  fn clone(&self) -> Self {
+    Self::default()
+  }
+}
+
+impl PartialEq for InlineCache {
+  fn eq(&self, _: &Self) -> bool {
+    true
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{InlineCache, Shape};
+  use std::rc::Rc;
+
+  #[test]
+  fn the_inline_cache_hits_only_for_the_shape_it_saw() {
+    let mut xy = Shape::root();
+    Shape::add_key(&mut xy, "x");
+    Shape::add_key(&mut xy, "y");
+    let mut yx = Shape::root();
+    Shape::add_key(&mut yx, "y");
+    Shape::add_key(&mut yx, "x");
+    assert!(!Rc::ptr_eq(&xy, &yx));
+    let cache = InlineCache::new();
+    assert_eq!(cache.get(0, &xy), None);
+    cache.set(0, &xy, xy.slot("y").unwrap());
+    assert_eq!(cache.get(0, &xy), Some(1));
+    assert_eq!(cache.get(0, &yx), None);
+    assert_eq!(cache.get(4, &xy), None);
+  }
+}
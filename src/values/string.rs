@@ -6,13 +6,36 @@ Copyright 2024 Yazalde Filimone <yazaldefilimon@gmail.com>
 
 /*
 */
-#[derive(Debug)]
+use std::rc::Rc;
+
+#[derive(Debug, Clone)]
 pub struct StringValue {
-  value: String,
+  // shared with the context's string interner when the string comes from the source
+  value: Rc<str>,
 }
 
 impl StringValue {
   pub fn new(value: String) -> Self {
+    StringValue { value: Rc::from(value) }
+  }
+
+  pub fn from_interned(value: Rc<str>) -> Self {
     StringValue { value }
   }
+
+  pub fn get_value(&self) -> &str {
+    &self.value
+  }
+
+  pub fn get_shared(&self) -> Rc<str> {
+    self.value.clone()
+  }
+}
+
+// interned strings share one allocation, so equal constants and keys compare by pointer
+// without reading their text. other strings still compare by content
+impl PartialEq for StringValue {
+  fn eq(&self, other: &Self) -> bool {
+    Rc::ptr_eq(&self.value, &other.value) || self.value == other.value
+  }
 }
@@ -9,7 +9,7 @@ Copyright 2024 Yazalde Filimone <yazaldefilimon@gmail.com>
 @links:
 -
 */
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct SymbolValue {
   // todo: consider using a better type for Symbol (tips. check in t39 spec or jscore ...)
   value: String,
@@ -19,4 +19,8 @@ impl SymbolValue {
   pub fn new(value: String) -> Self {
     SymbolValue { value }
   }
+
+  pub fn get_value(&self) -> &str {
+    &self.value
+  }
 }
@@ -12,7 +12,7 @@ The Undefined type has exactly one value, called undefined. Any variable that ha
 - https://tc39.es/ecma262/#sec-ecmascript-language-types-undefined-type
 */
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct UndefinedValue;
 
 impl UndefinedValue {
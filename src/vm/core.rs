@@ -1,219 +1,1725 @@
+use std::io::{self, IsTerminal};
+use std::mem;
+
 use crate::{
-  bytecode::opcode,
-  compiler::{compile, compiler::CompilerReturn},
+  builtins::{
+    array::array_method,
+    error::error_object,
+    object::enumerable_keys,
+    string::{char_at, string_method},
+    ITERATOR_KEY,
+  },
+  bytecode::{chunk::Chunk, opcode, verifier::verify},
+  compiler::{compile, compile_repl, compiler::CompilerReturn},
   context::Context,
   disassembler::Disassembler,
+  errors::{other, EngineError, RuntimeError, RuntimeErrorKind},
+  gc,
   stack::Stack,
-  utils::STACK_LIMIT,
-  values::Value,
+  utils::{decode_operand, opcode_operands, FRAMES_LIMIT, STACK_LIMIT},
+  values::{FunctionValue, NativeFunctionValue, ObjectValue, Value},
+  vm::interrupt::InterruptHandle,
 };
+
+// what executing one instruction gives, Some(value) once the program halts
+type StepResult = Result<Option<Value>, RuntimeErrorKind>;
+
+// a try block being run: where its catch block starts, and the frames and stack to go back to
+struct TryHandler {
+  catch: usize,
+  frames: usize,
+  stack: usize,
+}
+
+// the caller's registers, restored when the callee returns
+struct CallFrame {
+  function: FunctionValue,
+  instruction_pointer: usize,
+  frame_pointer: usize,
+  this: Value,
+  // the callee was called with `new`, it evaluates to its `this` unless it returns an object
+  is_construct: bool,
+}
+
 #[allow(dead_code)]
 pub struct Engine<'ctx> {
   ctx: &'ctx mut Context,
   compiler: &'ctx CompilerReturn,
   stack: &'ctx mut Stack,
+  // the function being executed, the top-level program is the `main` function
+  function: FunctionValue,
+  frames: Vec<CallFrame>,
+  this: Value,
+  // stack index of the first argument of the current call
   frame_pointer: usize,
   instruction_pointer: usize,
+  // natives running right now, the values they hold in rust aren't visible to the collector
+  native_depth: usize,
+  // instructions left before execution stops with OutOfFuel, no limit without one
+  fuel: Option<u64>,
+  // instructions dispatched so far, including the ones run for natives calling back into scripts
+  consumed_fuel: u64,
+  interrupt: InterruptHandle,
+  // try blocks entered and not left yet, innermost last, across every frame
+  handlers: Vec<TryHandler>,
 }
 #[allow(dead_code)]
 impl<'ctx> Engine<'ctx> {
   pub fn new(ctx: &'ctx mut Context, stack: &'ctx mut Stack, compiler: &'ctx CompilerReturn) -> Self {
     //  return VM with 'ctx
-    Self { ctx, compiler, stack, instruction_pointer: 0, frame_pointer: 0 }
+    let function = FunctionValue::new(compiler.name.clone(), 0, false, compiler.chunk.clone());
+    let frames = vec![];
+    let this = Value::create_undefined_value();
+    let mut vm = Self {
+      ctx,
+      compiler,
+      stack,
+      function,
+      frames,
+      this,
+      instruction_pointer: 0,
+      frame_pointer: 0,
+      native_depth: 0,
+      fuel: None,
+      consumed_fuel: 0,
+      interrupt: InterruptHandle::new(),
+      handlers: Vec::new(),
+    };
+    vm.reserve_locals();
+    vm
   }
-  pub fn bootstrap(ctx: &'ctx mut Context, source: &String, _debug: bool) -> Value {
+
+  // stops `run` with OutOfFuel once `fuel` instructions were dispatched, so a script like `while (true) {}`
+  // can't hang its embedder
+  pub fn with_fuel(mut self, fuel: u64) -> Self {
+    self.fuel = Some(fuel);
+    self
+  }
+
+  pub fn consumed_fuel(&self) -> u64 {
+    self.consumed_fuel
+  }
+
+  // what's left of the budget, None when there is no limit
+  pub fn remaining_fuel(&self) -> Option<u64> {
+    self.fuel
+  }
+
+  // shares a handle the host created earlier, e.g. before installing a Ctrl-C handler
+  pub fn with_interrupt_handle(mut self, interrupt: InterruptHandle) -> Self {
+    self.interrupt = interrupt;
+    self
+  }
+
+  // a handle to stop this engine from another thread, it stays usable after the engine is gone
+  pub fn interrupt_handle(&self) -> InterruptHandle {
+    self.interrupt.clone()
+  }
+
+  // the check point on backward jumps and calls
+  fn check_interrupt(&self) -> Result<(), RuntimeErrorKind> {
+    if self.interrupt.take() {
+      return Err(RuntimeErrorKind::Interrupted);
+    }
+    Ok(())
+  }
+  // compiles and runs `source`, a compile error is returned before anything runs
+  pub fn bootstrap(ctx: &'ctx mut Context, source: &String, debug: bool) -> Result<Value, EngineError> {
     let arena_allocator = oxc_allocator::Allocator::default();
-    let compiler = compile(&arena_allocator, source, ctx);
-    let mut stack = Stack::new(STACK_LIMIT);
-    let vm = Engine::new(ctx, &mut stack, &compiler);
+    let compiler = compile(&arena_allocator, source, ctx).map_err(other)?;
+    Engine::execute(ctx, &compiler, debug).map_err(|error| other(*error))
+  }
+
+  // runs one repl entry, its last expression statement is the result. globals declared by earlier
+  // entries live in `ctx`, so they stay visible
+  pub fn bootstrap_repl(ctx: &'ctx mut Context, source: &String) -> Result<Value, EngineError> {
+    let arena_allocator = oxc_allocator::Allocator::default();
+    let compiler = compile_repl(&arena_allocator, source, ctx).map_err(other)?;
+    Engine::execute(ctx, &compiler, false).map_err(|error| other(*error))
+  }
+
+  // compiles and runs `source` with a budget of `fuel` instructions, see `with_fuel`. a program that
+  // finishes in time gives its value and the fuel it didn't use
+  pub fn bootstrap_with_fuel(ctx: &'ctx mut Context, source: &String, fuel: u64) -> Result<(Value, u64), EngineError> {
+    let arena_allocator = oxc_allocator::Allocator::default();
+    let compiler = compile(&arena_allocator, source, ctx).map_err(other)?;
+    Engine::verify_compiled(ctx, &compiler);
+    let mut stack = Engine::program_stack(&compiler.chunk);
+    let mut vm = Engine::new(ctx, &mut stack, &compiler).with_fuel(fuel);
+    let value = vm.run().map_err(|error| other(*error))?;
+    Ok((value, vm.remaining_fuel().unwrap_or(0)))
+  }
+
+  // runs a chunk loaded from a .cbc file, nothing is parsed or compiled.
+  // the bytes came from outside the compiler, so they are always verified first
+  pub fn bootstrap_chunk(ctx: &'ctx mut Context, chunk: Chunk, debug: bool) -> Result<Value, EngineError> {
+    ctx.restore_globals(&chunk.globals).map_err(other)?;
+    verify(&chunk, ctx).map_err(other)?;
+    let compiler = CompilerReturn { name: "main".to_string(), chunk };
+    Engine::execute(ctx, &compiler, debug).map_err(|error| other(*error))
+  }
+
+  // compiled code failing here is a compiler bug, only checked in debug builds
+  fn verify_compiled(ctx: &Context, compiler: &CompilerReturn) {
+    if cfg!(debug_assertions) {
+      if let Err(error) = verify(&compiler.chunk, ctx) {
+        panic!("[Engine] {}", error);
+      }
+    }
+  }
+
+  // enough for the program itself, calls grow it as they need
+  fn program_stack(chunk: &Chunk) -> Stack {
+    let size = chunk.locals.len() + chunk.max_stack;
+    Stack::new(size.min(STACK_LIMIT))
+  }
+
+  fn execute(ctx: &mut Context, compiler: &CompilerReturn, debug: bool) -> Result<Value, Box<RuntimeError>> {
+    Engine::verify_compiled(ctx, compiler);
+    let mut stack = Engine::program_stack(&compiler.chunk);
+    let mut vm = Engine::new(ctx, &mut stack, compiler);
     // debug
-    if _debug {
-      let mut disassembler = Disassembler::new(&compiler.code, "main.ts", &compiler.constants, vm.ctx);
-      disassembler.disassemble();
+    if debug {
+      let mut disassembler = Disassembler::new(&compiler.chunk, "main.ts", vm.ctx)
+        .with_constants(true)
+        .with_variables(true)
+        .with_stats(true)
+        .with_color(io::stdout().is_terminal());
+      disassembler.print();
     }
     vm.run()
   }
 
-  fn run(mut self) -> Value {
+  // runs the program until it halts. the engine stays around afterwards, so the fuel it used can be read
+  pub fn run(&mut self) -> Result<Value, Box<RuntimeError>> {
+    // objects built while running, by the vm or by natives, belong to this context's heap
+    let recording = gc::start_recording();
+    let result = self.run_instructions(Self::execute_instruction);
+    self.ctx.heap_mut().adopt_constructed();
+    if recording {
+      gc::stop_recording();
+    }
+    result
+  }
+
+  // the loop behind `run`, generic over how an instruction is executed so tests can drive both paths
+  #[inline(always)]
+  fn run_instructions(&mut self, execute: impl Fn(&mut Self, u8) -> StepResult) -> Result<Value, Box<RuntimeError>> {
+    // the running code and the offset in it live here, not behind `self.function`. the engine's copy of
+    // the offset is only written for the instruction to read its operands, and both are reloaded after
+    // it, since a jump, call or return moves them
+    let mut chunk = self.function.shared_chunk();
+    let mut offset = self.instruction_pointer;
     loop {
-      let instruction = self.read();
-      match instruction {
-        opcode::OPCODE_CONST => {
-          let index = self.get_constant();
-          self.stack.push(index);
+      self.debug_assert_stack_bound();
+      // the opcode is fetched once here, `dispatch` gets it instead of reading the code again
+      let Some(&instruction) = chunk.code.get(offset) else {
+        return Err(self.runtime_error(RuntimeErrorKind::UnexpectedEnd, opcode::OPCODE_HALT, offset));
+      };
+      if let Err(kind) = self.consume_fuel() {
+        return Err(self.runtime_error(kind, instruction, offset));
+      }
+      self.instruction_pointer = offset + 1;
+      match execute(self, instruction) {
+        Ok(Some(value)) => return Ok(value),
+        Ok(None) => {}
+        Err(kind) if self.catch(&kind, 0) => {}
+        Err(kind) => return Err(self.runtime_error(kind, instruction, offset)),
+      }
+      offset = self.instruction_pointer;
+      if !std::ptr::eq(&*chunk, self.function.get_chunk()) {
+        chunk = self.function.shared_chunk();
+      }
+    }
+  }
+
+  // reads and executes one instruction
+  fn step(&mut self) -> StepResult {
+    self.consume_fuel()?;
+    let instruction = self.read()?;
+    self.execute_instruction(instruction)
+  }
+
+  // charges one instruction, the one about to be dispatched isn't run once the budget is spent
+  #[inline(always)]
+  fn consume_fuel(&mut self) -> Result<(), RuntimeErrorKind> {
+    if let Some(fuel) = &mut self.fuel {
+      if *fuel == 0 {
+        return Err(RuntimeErrorKind::OutOfFuel);
+      }
+      *fuel -= 1;
+    }
+    self.consumed_fuel += 1;
+    Ok(())
+  }
+
+  // the instruction whose opcode was just read, through the handler table with the `handler-table` feature
+  #[inline(always)]
+  fn execute_instruction(&mut self, instruction: u8) -> StepResult {
+    if cfg!(feature = "handler-table") {
+      HANDLERS[instruction as usize](self, instruction)
+    } else {
+      self.dispatch(instruction)
+    }
+  }
+
+  // one arm per opcode, every arm calls the same operation its entry in `HANDLERS` does
+  #[inline(always)]
+  fn dispatch(&mut self, instruction: u8) -> StepResult {
+    match instruction {
+      opcode::OPCODE_CONST | opcode::OPCODE_CONST_LONG => self.constant_operation(instruction)?,
+      opcode::OPCODE_ADD => self._addition_operation()?,
+      opcode::OPCODE_SUB => self._subtraction_operation()?,
+      opcode::OPCODE_MUL => self._multplication_operation()?,
+      opcode::OPCODE_DIV => self._division_operation()?,
+      opcode::OPCODE_ADD_INT => self.integer_operation(i64::checked_add, Self::_addition_operation)?,
+      opcode::OPCODE_SUB_INT => self.integer_operation(i64::checked_sub, Self::_subtraction_operation)?,
+      opcode::OPCODE_MUL_INT => self.integer_operation(i64::checked_mul, Self::_multplication_operation)?,
+      opcode::OPCODE_ADD_CONST => self.add_constant_operation()?,
+      opcode::OPCODE_EQ => self._eq_operation()?,
+      opcode::OPCODE_WEAK_EQ => self.loose_equality_operation(false)?,
+      opcode::OPCODE_NE => self.loose_equality_operation(true)?,
+      opcode::OPCODE_LT | opcode::OPCODE_LE | opcode::OPCODE_GT | opcode::OPCODE_GE => {
+        self.relational_operation(instruction)?
+      }
+      opcode::OPCODE_LT_CONST => self.less_than_constant_operation()?,
+      opcode::OPCODE_NOT => self.not_operation()?,
+      opcode::OPCODE_JUMP => self._jump_operation()?,
+      opcode::OPCODE_JUMP_IF_FALSE => self._jump_if_false_operation()?,
+      opcode::OPCODE_LOAD_GLOBAL_SCOPE | opcode::OPCODE_LOAD_GLOBAL_SCOPE_LONG => {
+        self.load_global_scope_operation(instruction)?
+      }
+      opcode::OPCODE_SET_GLOBAL_SCOPE | opcode::OPCODE_SET_GLOBAL_SCOPE_LONG => {
+        self.set_global_scope_operation(instruction)?
+      }
+      opcode::OPCODE_POP => {
+        self.pop()?;
+      }
+      opcode::OPCODE_SET_LOCAL => self.set_local_operation()?,
+      opcode::OPCODE_GET_LOCAL => self.get_local_operation()?,
+      opcode::OPCODE_SET_LOCAL_POP => self.set_local_pop_operation()?,
+      opcode::OPCODE_SET_GLOBAL_POP => self.set_global_pop_operation()?,
+      opcode::OPCODE_LOAD_THIS => self.load_this_operation(),
+      opcode::OPCODE_LOAD_ARGUMENT => self.load_argument_operation()?,
+      opcode::OPCODE_SET_ARGUMENT => self.set_argument_operation()?,
+      opcode::OPCODE_CALL => self.call_operation()?,
+      opcode::OPCODE_CALL_METHOD => self.call_method_operation()?,
+      opcode::OPCODE_INSTANCEOF => self.instance_of_operation()?,
+      opcode::OPCODE_DUP => self.dup_operation()?,
+      opcode::OPCODE_SWAP => self.swap_operation()?,
+      opcode::OPCODE_NEW => self.new_operation()?,
+      opcode::OPCODE_ITERATE => self.iterate_operation()?,
+      opcode::OPCODE_ITERATE_NEXT => self.iterate_next_operation()?,
+      opcode::OPCODE_ENUMERATE => self.enumerate_operation()?,
+      opcode::OPCODE_NEW_ARRAY => self.new_array_operation()?,
+      opcode::OPCODE_GET_PROPERTY => self.get_property_operation()?,
+      opcode::OPCODE_NEW_OBJECT => self.push_allocated(Value::create_object_value()),
+      opcode::OPCODE_INIT_PROPERTY => self.init_property_operation()?,
+      opcode::OPCODE_SET_PROPERTY => self.set_property_operation()?,
+      opcode::OPCODE_SET_ELEMENT => self.set_element_operation()?,
+      opcode::OPCODE_GET_ELEMENT => self.get_element_operation()?,
+      opcode::OPCODE_RETURN => self.return_operation()?,
+      opcode::OPCODE_THROW => self.throw_operation()?,
+      opcode::OPCODE_SETUP_TRY => self.setup_try_operation()?,
+      opcode::OPCODE_POP_TRY => {
+        self.handlers.pop();
+      }
+      opcode::OPCODE_HALT => return Ok(Some(self.halt_operation())),
+      _ => return Err(RuntimeErrorKind::UnknownOpcode),
+    }
+    Ok(None)
+  }
+
+  fn constant_operation(&mut self, opcode: u8) -> Result<(), RuntimeErrorKind> {
+    let value = match self.get_constant(opcode)? {
+      // evaluating an arrow closes over the `this` of the running frame
+      Value::Function(function) if function.is_arrow() => Value::Function(function.bind_this(self.this.clone())),
+      value => value,
+    };
+    self.stack.push(value);
+    Ok(())
+  }
+
+  fn not_operation(&mut self) -> Result<(), RuntimeErrorKind> {
+    let value = self.pop()?;
+    self.stack.push(Value::create_boolean_value(!value.is_truthy()));
+    Ok(())
+  }
+
+  fn call_method_operation(&mut self) -> Result<(), RuntimeErrorKind> {
+    let argc = self.read_operand(opcode::OPCODE_CALL_METHOD)?;
+    self.call_method(argc)
+  }
+
+  fn new_operation(&mut self) -> Result<(), RuntimeErrorKind> {
+    let argc = self.read_operand(opcode::OPCODE_NEW)?;
+    self.construct(argc)
+  }
+
+  fn instance_of_operation(&mut self) -> Result<(), RuntimeErrorKind> {
+    let (value, constructor) = self.pop_operands()?;
+    let result = self.instance_of(&value, &constructor)?;
+    self.stack.push(Value::create_boolean_value(result));
+    Ok(())
+  }
+
+  fn dup_operation(&mut self) -> Result<(), RuntimeErrorKind> {
+    let value = self.peek(0)?;
+    self.stack.push(value);
+    Ok(())
+  }
+
+  fn swap_operation(&mut self) -> Result<(), RuntimeErrorKind> {
+    let (below, top) = self.pop_operands()?;
+    self.stack.push(top);
+    self.stack.push(below);
+    Ok(())
+  }
+
+  fn enumerate_operation(&mut self) -> Result<(), RuntimeErrorKind> {
+    let value = self.pop()?;
+    self.push_allocated(Value::create_array_value(enumerable_keys(&value)));
+    Ok(())
+  }
+
+  fn get_property_operation(&mut self) -> Result<(), RuntimeErrorKind> {
+    let offset = self.instruction_pointer - 1;
+    let key = self.get_constant(opcode::OPCODE_GET_PROPERTY)?;
+    let object = self.pop()?;
+    let key = key.to_property_key();
+    // own properties of an object go through the inline cache of this site, the rest the slow way
+    let cached = match &object {
+      Value::Object(object) => object.get_own_property_cached(&key, &self.function.get_chunk().inline_cache, offset),
+      _ => None,
+    };
+    let value = match cached {
+      Some(value) => value,
+      None => self.get_property(&object, &key)?,
+    };
+    self.stack.push(value);
+    Ok(())
+  }
+
+  fn init_property_operation(&mut self) -> Result<(), RuntimeErrorKind> {
+    let key = self.get_constant(opcode::OPCODE_INIT_PROPERTY)?;
+    let value = self.pop()?;
+    // the object stays on the stack for the next property
+    match self.peek(0)? {
+      Value::Object(object) => object.set_property(&key.to_property_key(), value),
+      _ => return Err(RuntimeErrorKind::TypeError("INIT_PROPERTY on a non-object".to_string())),
+    }
+    Ok(())
+  }
+
+  fn set_property_operation(&mut self) -> Result<(), RuntimeErrorKind> {
+    let key = self.get_constant(opcode::OPCODE_SET_PROPERTY)?;
+    let value = self.pop()?;
+    let object = self.pop()?;
+    self.set_property(&object, &key.to_property_key(), value.clone())?;
+    self.stack.push(value);
+    Ok(())
+  }
+
+  fn set_element_operation(&mut self) -> Result<(), RuntimeErrorKind> {
+    let value = self.pop()?;
+    let (object, key) = self.pop_operands()?;
+    self.set_property(&object, &key.to_property_key(), value.clone())?;
+    self.stack.push(value);
+    Ok(())
+  }
+
+  fn get_element_operation(&mut self) -> Result<(), RuntimeErrorKind> {
+    let (object, key) = self.pop_operands()?;
+    let value = self.get_property(&object, &key.to_property_key())?;
+    self.stack.push(value);
+    Ok(())
+  }
+
+  // the value the program stops with, undefined when the stack is empty
+  // the program's value is whatever is above its locals, a program ending in a statement has none
+  fn halt_operation(&mut self) -> Value {
+    let locals = self.frame_pointer + self.function.get_arity() + self.function.get_chunk().locals.len();
+    if self.stack.len() <= locals {
+      return Value::create_undefined_value();
+    }
+    self.stack.pop().unwrap_or_else(|_| Value::create_undefined_value())
+  }
+
+  // attaches the failing instruction, its line and the active calls to `kind`
+  fn runtime_error(&self, kind: RuntimeErrorKind, opcode: u8, offset: usize) -> Box<RuntimeError> {
+    let line = self.function.get_chunk().line_for_offset(offset);
+    let call_stack = self.call_stack(offset);
+    Box::new(RuntimeError { kind, opcode, offset, line, call_stack })
+  }
+
+  // `name (line N)` for the running function at `offset`, then for each caller at the call it's waiting on
+  fn call_stack(&self, offset: usize) -> Vec<String> {
+    let callers = self
+      .frames
+      .iter()
+      .rev()
+      .map(|frame| (&frame.function, frame.instruction_pointer.saturating_sub(1)));
+    std::iter::once((&self.function, offset))
+      .chain(callers)
+      .map(
+        |(function, offset)| match function.get_chunk().line_for_offset(offset) {
+          Some(line) => format!("{} (line {})", function.get_name(), line),
+          None => function.get_name().to_string(),
+        },
+      )
+      .collect()
+  }
+
+  // the active calls as a native sees them, the innermost one is at the instruction that called it
+  pub fn stack_trace(&self) -> Vec<String> {
+    self.call_stack(self.instruction_pointer.saturating_sub(1))
+  }
+
+  // `kind` as the value scripts will catch, an Error object unless a script threw it
+  pub fn error_value(&mut self, kind: &RuntimeErrorKind) -> Value {
+    match kind {
+      RuntimeErrorKind::Thrown(value) => value.clone(),
+      _ => Value::Object(error_object(self, kind)),
+    }
+  }
+
+  // the thrown value goes to the innermost catch block through `catch`, or leaves the engine as the error
+  fn throw_operation(&mut self) -> Result<(), RuntimeErrorKind> {
+    let value = self.pop()?;
+    Err(RuntimeErrorKind::Thrown(value))
+  }
+
+  fn setup_try_operation(&mut self) -> Result<(), RuntimeErrorKind> {
+    let distance = self.read_operand(opcode::OPCODE_SETUP_TRY)?;
+    let catch = self
+      .instruction_pointer
+      .wrapping_add_signed(distance as u16 as i16 as isize);
+    self
+      .handlers
+      .push(TryHandler { catch, frames: self.frames.len(), stack: self.stack.len() });
+    Ok(())
+  }
+
+  // hands a catchable error to the innermost try block, unless it belongs to a frame below `frames`. the
+  // frames called since the try block are left, the stack goes back to what it was at SETUP_TRY and the
+  // error's value is pushed for the catch block
+  fn catch(&mut self, kind: &RuntimeErrorKind, frames: usize) -> bool {
+    if !kind.is_catchable() || self.handlers.last().is_none_or(|handler| handler.frames < frames) {
+      return false;
+    }
+    let value = self.error_value(kind);
+    let handler = self.handlers.pop().expect("[Engine] try block to catch the error");
+    self.unwind(handler.frames);
+    self.stack.truncate(handler.stack);
+    self.stack.push(value);
+    self.instruction_pointer = handler.catch;
+    true
+  }
+
+  // a frame that returns from inside a try block leaves it too
+  fn drop_handlers_of_returned_frames(&mut self) {
+    while self
+      .handlers
+      .last()
+      .is_some_and(|handler| handler.frames > self.frames.len())
+    {
+      self.handlers.pop();
+    }
+  }
+
+  // locals live in the slots reserved after the arguments of the frame
+  fn local_slot(&self, slot: usize) -> usize {
+    self.frame_pointer + self.function.get_arity() + slot
+  }
+
+  fn set_local_operation(&mut self) -> Result<(), RuntimeErrorKind> {
+    let slot = self.read_operand(opcode::OPCODE_SET_LOCAL)?;
+    // like globals, the assigned value stays on the stack as the expression result
+    let value = self.peek(0)?;
+    self
+      .stack
+      .set(self.local_slot(slot), value)
+      .map_err(|_| RuntimeErrorKind::StackUnderflow)
+  }
+
+  fn set_local_pop_operation(&mut self) -> Result<(), RuntimeErrorKind> {
+    self.set_local_operation()?;
+    self.pop()?;
+    Ok(())
+  }
+
+  fn get_local_operation(&mut self) -> Result<(), RuntimeErrorKind> {
+    let slot = self.read_operand(opcode::OPCODE_GET_LOCAL)?;
+    let value = self
+      .stack
+      .get(self.local_slot(slot))
+      .map_err(|_| RuntimeErrorKind::StackUnderflow)?
+      .clone();
+    self.stack.push(value);
+    Ok(())
+  }
+
+  // undefined in every local slot of the chunk about to run, the operand stack starts above them
+  fn reserve_locals(&mut self) {
+    for _ in 0..self.function.get_chunk().locals.len() {
+      self.stack.push(Value::create_undefined_value());
+    }
+  }
+
+  fn set_global_scope_operation(&mut self, opcode: u8) -> Result<(), RuntimeErrorKind> {
+    let index = self.read_operand(opcode)?;
+    if self.ctx.get_global_variable(index).is_none() {
+      return Err(RuntimeErrorKind::ReferenceError(format!(
+        "global {} is not defined",
+        index
+      )));
+    }
+    //  get last value from stack
+    let value = self.peek(0)?;
+    self.ctx.set_variable(index, value);
+    Ok(())
+  }
+
+  fn set_global_pop_operation(&mut self) -> Result<(), RuntimeErrorKind> {
+    self.set_global_scope_operation(opcode::OPCODE_SET_GLOBAL_POP)?;
+    self.pop()?;
+    Ok(())
+  }
+
+  // a site that already resolved its slot in the current generation skips decoding and checking the operand
+  fn load_global_scope_operation(&mut self, opcode: u8) -> Result<(), RuntimeErrorKind> {
+    let offset = self.instruction_pointer - 1;
+    let generation = self.ctx.generation();
+    let cache = &self.function.get_chunk().global_cache;
+    if let Some(index) = cache.get(offset, generation) {
+      self.instruction_pointer += opcode_operands(opcode);
+      self.stack.push(self.ctx.get_global_value(index).clone());
+      return Ok(());
+    }
+    let index = self.read_operand(opcode)?;
+    let Some(store) = self.ctx.get_global_variable(index) else {
+      return Err(RuntimeErrorKind::ReferenceError(format!(
+        "global {} is not defined",
+        index
+      )));
+    };
+    self.stack.push(store.value.to_owned());
+    self.function.get_chunk().global_cache.set(offset, generation, index);
+    Ok(())
+  }
+  fn load_this_operation(&mut self) {
+    self.stack.push(self.this.clone());
+  }
+
+  fn load_argument_operation(&mut self) -> Result<(), RuntimeErrorKind> {
+    let slot = self.read_operand(opcode::OPCODE_LOAD_ARGUMENT)?;
+    let value = self
+      .stack
+      .get(self.frame_pointer + slot)
+      .map_err(|_| RuntimeErrorKind::StackUnderflow)?
+      .clone();
+    self.stack.push(value);
+    Ok(())
+  }
+
+  fn set_argument_operation(&mut self) -> Result<(), RuntimeErrorKind> {
+    let slot = self.read_operand(opcode::OPCODE_SET_ARGUMENT)?;
+    // like globals, the assigned value stays on the stack as the expression result
+    let value = self.peek(0)?;
+    self
+      .stack
+      .set(self.frame_pointer + slot, value)
+      .map_err(|_| RuntimeErrorKind::StackUnderflow)
+  }
+
+  fn new_array_operation(&mut self) -> Result<(), RuntimeErrorKind> {
+    let count = self.read_operand(opcode::OPCODE_NEW_ARRAY)?;
+    if count > self.stack.len() {
+      return Err(RuntimeErrorKind::StackUnderflow);
+    }
+    let elements = self.stack.top(count).to_vec();
+    self.stack.truncate(self.stack.len() - count);
+    self.push_allocated(Value::create_array_value(elements));
+    Ok(())
+  }
+
+  // pushes a new object or array and lets the heap track it, collecting once enough has been allocated
+  fn push_allocated(&mut self, value: Value) {
+    let heap = self.ctx.heap_mut();
+    heap.track(&value);
+    heap.adopt_constructed();
+    self.stack.push(value);
+    // the values a native holds in rust would be swept from under it
+    if self.native_depth == 0 && self.ctx.heap().should_collect() {
+      self.collect_garbage();
+    }
+  }
+
+  // a full collection, the roots are the stack, the active calls and the variables.
+  // returns how many values were reclaimed
+  pub fn collect_garbage(&mut self) -> usize {
+    let mut roots = self.stack.values().to_vec();
+    roots.push(Value::Function(self.function.clone()));
+    roots.push(self.this.clone());
+    for frame in &self.frames {
+      roots.push(Value::Function(frame.function.clone()));
+      roots.push(frame.this.clone());
+    }
+    roots.extend(self.ctx.variable_values().cloned());
+    self.ctx.heap_mut().collect(&roots)
+  }
+
+  // leaves the two values ITERATE_NEXT works from on the stack. arrays and strings are walked by index
+  // (the iterable, then the next index), anything else goes through the iterator protocol: the iterable is
+  // replaced by what its `@@iterator` method returns, followed by that iterator's `next`
+  fn iterate_operation(&mut self) -> Result<(), RuntimeErrorKind> {
+    let iterable = self.peek(0)?;
+    if matches!(iterable, Value::Array(_) | Value::String(_)) {
+      self.stack.push(Value::create_number_value(0.0));
+      return Ok(());
+    }
+    let method = match &iterable {
+      Value::Object(object) => object.get_property(ITERATOR_KEY),
+      _ => Value::create_undefined_value(),
+    };
+    if !matches!(method, Value::Function(_) | Value::NativeFunction(_)) {
+      let message = format!("{} is not iterable", iterable.type_name());
+      return Err(RuntimeErrorKind::TypeError(message));
+    }
+    let iterator = self.call_method_value(iterable, method, &[])?;
+    if !matches!(iterator, Value::Object(_)) {
+      let message = format!("Result of the {} method is not an object", ITERATOR_KEY);
+      return Err(RuntimeErrorKind::TypeError(message));
+    }
+    // `next` is read once, like js does
+    let next = self.get_property(&iterator, "next")?;
+    self.pop()?;
+    self.stack.push(iterator);
+    self.stack.push(next);
+    Ok(())
+  }
+
+  // pushes the next element of the iteration set up by ITERATE, or leaves the loop once there's none
+  fn iterate_next_operation(&mut self) -> Result<(), RuntimeErrorKind> {
+    let distance = self.read_operand(opcode::OPCODE_ITERATE_NEXT)?;
+    let (iterable, state) = (self.peek(1)?, self.peek(0)?);
+    let Value::Number(index) = state else {
+      return self.iterator_next(iterable, state, distance);
+    };
+    let index = index.get_value() as usize;
+    let element = match iterable {
+      Value::Array(array) if index < array.len() => Some(array.get(index)),
+      // strings are walked one UTF-16 code unit at a time
+      Value::String(string) => string
+        .get_value()
+        .encode_utf16()
+        .nth(index)
+        .map(|unit| Value::create_string_value(String::from_utf16_lossy(&[unit]))),
+      _ => None,
+    };
+    let Some(element) = element else {
+      self.jump_by(distance);
+      return Ok(());
+    };
+    let top = self.stack.len() - 1;
+    self
+      .stack
+      .set(top, Value::create_number_value((index + 1) as f64))
+      .map_err(|_| RuntimeErrorKind::StackUnderflow)?;
+    self.stack.push(element);
+    Ok(())
+  }
+
+  // one step of the iterator protocol: `iterator.next()` gives `{ value, done }`
+  fn iterator_next(&mut self, iterator: Value, next: Value, distance: usize) -> Result<(), RuntimeErrorKind> {
+    let result = self.call_method_value(iterator, next, &[])?;
+    let Value::Object(result) = result else {
+      let message = format!("Iterator result {} is not an object", result.debug_repr());
+      return Err(RuntimeErrorKind::TypeError(message));
+    };
+    if result.get_property("done").is_truthy() {
+      self.jump_by(distance);
+      return Ok(());
+    }
+    self.stack.push(result.get_property("value"));
+    Ok(())
+  }
+
+  // `object[key]`, anything without the property reads as undefined
+  fn get_property(&self, object: &Value, key: &str) -> Result<Value, RuntimeErrorKind> {
+    let value = match object {
+      Value::Undefined(_) | Value::Null(_) => {
+        let message = format!("Cannot read properties of {} (reading '{}')", object, key);
+        return Err(RuntimeErrorKind::TypeError(message));
+      }
+      Value::Object(object) => object.get_property(key),
+      Value::Function(function) if key == "prototype" => Value::Object(function.get_prototype_object()),
+      Value::NativeFunction(native) if key == "prototype" => match native.get_prototype() {
+        Some(prototype) => Value::Object(prototype),
+        None => Value::create_undefined_value(),
+      },
+      Value::Array(array) if key == "length" => Value::create_number_value(array.len() as f64),
+      // only canonical indices ("1", not "01") address elements
+      Value::Array(array) => match key.parse::<usize>() {
+        Ok(index) if index.to_string() == key => array.get(index),
+        _ => match array_method(key) {
+          Some(method) => Value::NativeFunction(method.bind(object.clone())),
+          None => Value::create_undefined_value(),
+        },
+      },
+      Value::String(string) if key == "length" => {
+        Value::create_number_value(string.get_value().encode_utf16().count() as f64)
+      }
+      Value::String(string) => match key.parse::<usize>() {
+        Ok(index) if index.to_string() == key => char_at(string.get_value(), index),
+        // methods come bound to the string, CALL then runs them like any native
+        _ => match string_method(key) {
+          Some(method) => Value::NativeFunction(method.bind(object.clone())),
+          None => Value::create_undefined_value(),
+        },
+      },
+      Value::RegExp(regexp) => match key {
+        "source" => Value::create_string_value(regexp.get_pattern().to_string()),
+        "flags" => Value::create_string_value(regexp.get_flags().to_string()),
+        "global" => Value::create_boolean_value(regexp.get_flags().contains('g')),
+        _ => Value::create_undefined_value(),
+      },
+      _ => Value::create_undefined_value(),
+    };
+    Ok(value)
+  }
+
+  // `object[key] = value`, only objects and array elements can be written for now
+  fn set_property(&self, object: &Value, key: &str, value: Value) -> Result<(), RuntimeErrorKind> {
+    match object {
+      Value::Undefined(_) | Value::Null(_) => {
+        let message = format!("Cannot set properties of {} (setting '{}')", object, key);
+        return Err(RuntimeErrorKind::TypeError(message));
+      }
+      Value::Object(object) => object.set_property(key, value),
+      // a non-object prototype is ignored, `new` then falls back to a fresh one like js does
+      Value::Function(function) if key == "prototype" => {
+        if let Value::Object(prototype) = value {
+          function.set_prototype_object(prototype);
         }
-        opcode::OPCODE_ADD => self._addition_operation(),
-        opcode::OPCODE_SUB => self._subtraction_operation(),
-        opcode::OPCODE_MUL => self._multplication_operation(),
-        opcode::OPCODE_DIV => self._division_operation(),
-        opcode::OPCODE_EQ => self._eq_operation(),
-        opcode::OPCODE_JUMP => self._jump_operation(),
-        opcode::OPCODE_JUMP_IF_FALSE => self._jump_if_false_operation(),
-        opcode::OPCODE_LOAD_GLOBAL_SCOPE => self.load_global_scope_operation(),
-        opcode::OPCODE_SET_GLOBAL_SCOPE => self.set_global_scope_operation(),
-        opcode::OPCODE_POP => return self.stack.peek(0).unwrap().to_owned(),
-        opcode::OPCODE_SET_LOCAL_SCOPE => self.set_local_scope_operation(),
-        opcode::OPCODE_LOAD_LOCAL_SCOPE => self.load_local_scope_operation(),
-        opcode::OPCODE_HALF => {
-          if !self.stack.is_empty() {
-            let value = self.stack.pop().unwrap();
-            return value;
-          } else {
-            return Value::Undefined;
+      }
+      Value::Array(array) => {
+        if let Ok(index) = key.parse::<usize>() {
+          if index.to_string() == key {
+            let mut elements = array.get_elements_mut();
+            if index >= elements.len() {
+              elements.resize(index + 1, Value::create_undefined_value());
+            }
+            elements[index] = value;
           }
         }
-        _ => todo!("opcode not implemented"),
       }
+      // writes to primitives are silently lost, like in sloppy mode
+      _ => {}
     }
+    Ok(())
   }
 
-  fn set_local_scope_operation(&mut self) {}
-  fn load_local_scope_operation(&mut self) {
-    // let index = self.read();
+  fn call_operation(&mut self) -> Result<(), RuntimeErrorKind> {
+    let argc = self.read_operand(opcode::OPCODE_CALL)?;
+    self.call(argc)
   }
 
-  fn set_global_scope_operation(&mut self) {
-    let index = self.read();
-    //  get last value from stack
-    let value = self.stack.peek(0).unwrap();
-    self.ctx.set_variable(index, value.to_owned());
+  // calls the callee below the `argc` arguments on top of the stack. natives finish right away,
+  // script functions get a frame and run from the next step
+  fn call(&mut self, argc: usize) -> Result<(), RuntimeErrorKind> {
+    let callee = self.peek(argc)?;
+    match callee {
+      Value::Function(function) => {
+        // arrows don't bind their own `this`, they use the one captured where they were created
+        let this = function
+          .get_this()
+          .cloned()
+          .unwrap_or_else(Value::create_undefined_value);
+        self.enter_function(function, argc, this, false)
+      }
+      Value::NativeFunction(native) => self.call_native(&native, argc),
+      _ => {
+        let message = format!("{} is not a function", callee.type_name());
+        Err(RuntimeErrorKind::TypeError(message))
+      }
+    }
   }
 
-  fn load_global_scope_operation(&mut self) {
-    let index = self.read();
-    let value = self.ctx.get_variable_value(index);
-    self.stack.push(value.to_owned());
+  // `receiver.method(...)`: the receiver below the callee is lifted out and becomes `this`,
+  // then it's an ordinary call
+  fn call_method(&mut self, argc: usize) -> Result<(), RuntimeErrorKind> {
+    let index = self
+      .stack
+      .len()
+      .checked_sub(argc + 2)
+      .ok_or(RuntimeErrorKind::StackUnderflow)?;
+    let receiver = self.stack.remove(index).map_err(|_| RuntimeErrorKind::StackUnderflow)?;
+    self.call_with_receiver(receiver, argc)
   }
-  fn _jump_operation(&mut self) {
-    let index = self.read();
-    self.instruction_pointer = index;
+
+  // calls the callee below the `argc` arguments with `receiver` as its `this`
+  fn call_with_receiver(&mut self, receiver: Value, argc: usize) -> Result<(), RuntimeErrorKind> {
+    match self.peek(argc)? {
+      Value::Function(function) if !function.is_arrow() => self.enter_function(function, argc, receiver, false),
+      // a native read off a prototype (like `error.toString`) is bound to the receiver it was called on
+      Value::NativeFunction(native) => self.call_native(&native.bind(receiver), argc),
+      _ => self.call(argc),
+    }
   }
-  fn _jump_if_false_operation(&mut self) {
-    let index = self.read();
-    let condition = self.stack.pop().unwrap();
-    if !condition.is_truthy() {
-      self.instruction_pointer = index;
+
+  // `value instanceof constructor`, walks the prototype chain of `value`
+  fn instance_of(&self, value: &Value, constructor: &Value) -> Result<bool, RuntimeErrorKind> {
+    let prototype = match constructor {
+      Value::Function(function) => function.get_prototype_object(),
+      Value::NativeFunction(native) => match native.get_prototype() {
+        Some(prototype) => prototype,
+        // natives that aren't constructors have no `prototype`, nothing is an instance of them
+        None => return Ok(false),
+      },
+      _ => {
+        let message = "Right-hand side of 'instanceof' is not callable".to_string();
+        return Err(RuntimeErrorKind::TypeError(message));
+      }
+    };
+    match value {
+      Value::Object(object) => Ok(object.inherits_from(&prototype)),
+      _ => Ok(false),
     }
   }
-  fn read(&mut self) -> usize {
-    let instruction = self.compiler.code[self.instruction_pointer];
-    self.instruction_pointer += 1;
-    instruction
+
+  // `new callee(...)`, the callee runs with a fresh object as `this`
+  fn construct(&mut self, argc: usize) -> Result<(), RuntimeErrorKind> {
+    let callee = self.peek(argc)?;
+    match callee {
+      Value::Function(function) if !function.is_arrow() => {
+        let this = Value::Object(ObjectValue::with_prototype(function.get_prototype_object()));
+        self.ctx.heap_mut().track(&this);
+        self.enter_function(function, argc, this, true)
+      }
+      Value::Function(function) => {
+        let message = format!("{} is not a constructor", function.get_name());
+        Err(RuntimeErrorKind::TypeError(message))
+      }
+      Value::NativeFunction(native) => match native.get_prototype() {
+        Some(prototype) => {
+          let this = Value::Object(ObjectValue::with_prototype(prototype));
+          self.call_native(&native.bind(this), argc)
+        }
+        None => {
+          let message = format!("{} is not a constructor", native.get_name());
+          Err(RuntimeErrorKind::TypeError(message))
+        }
+      },
+      _ => {
+        let message = format!("{} is not a constructor", callee.type_name());
+        Err(RuntimeErrorKind::TypeError(message))
+      }
+    }
   }
 
-  fn get_constant(&mut self) -> Value {
-    let index = self.read();
-    self.compiler.constants[index].clone()
+  // pushes a frame for `function`, whose `argc` arguments sit on top of the stack
+  fn enter_function(
+    &mut self,
+    function: FunctionValue,
+    argc: usize,
+    this: Value,
+    is_construct: bool,
+  ) -> Result<(), RuntimeErrorKind> {
+    self.check_interrupt()?;
+    if self.frames.len() >= FRAMES_LIMIT {
+      return Err(RuntimeErrorKind::RangeError(
+        "Maximum call stack size exceeded".to_string(),
+      ));
+    }
+    // missing arguments are undefined, extra ones are dropped
+    let arity = function.get_arity();
+    for _ in argc..arity {
+      self.stack.push(Value::create_undefined_value());
+    }
+    for _ in arity..argc {
+      self.pop()?;
+    }
+    let caller = CallFrame {
+      function: mem::replace(&mut self.function, function),
+      instruction_pointer: self.instruction_pointer,
+      frame_pointer: self.frame_pointer,
+      this: mem::replace(&mut self.this, this),
+      is_construct,
+    };
+    self.frames.push(caller);
+    self.frame_pointer = self.stack.len() - arity;
+    self.instruction_pointer = 0;
+    self.reserve_locals();
+    Ok(())
   }
-  fn _eq_operation(&mut self) {
-    let (right, left) = (self.stack.pop().unwrap(), self.stack.pop().unwrap());
-    let value = Value::new_boolean(right.is_equal(&left));
-    self.stack.push(value);
+
+  // natives run to completion without a frame, their result replaces the callee and arguments
+  fn call_native(&mut self, native: &NativeFunctionValue, argc: usize) -> Result<(), RuntimeErrorKind> {
+    let arguments = self.stack.top(argc).to_vec();
+    self.native_depth += 1;
+    let result = native.call(self, &arguments);
+    self.native_depth -= 1;
+    let result = result?;
+    self.stack.truncate(self.stack.len() - argc - 1);
+    // objects natives build (`new Map()`, `array.map(...)`) are tracked like the ones the vm builds
+    self.push_allocated(result);
+    Ok(())
   }
 
-  fn _multplication_operation(&mut self) {
-    let (right, left) = (self.stack.pop().unwrap(), self.stack.pop().unwrap());
-    if left.is_float() && right.is_float() {
-      let result = Value::new_float(left.get_float() - right.get_float());
-      self.stack.push(result);
-      return;
+  // calls `callee` from native code (like the callback of `map`) and runs it to completion.
+  // on error the frames it pushed are dropped, so the error is reported at the native's CALL
+  pub fn call_value(&mut self, callee: Value, arguments: &[Value]) -> Result<Value, RuntimeErrorKind> {
+    self.invoke(callee, None, arguments)
+  }
+
+  // `call_value` with `receiver` as `this`, like `receiver.method(...arguments)`
+  pub fn call_method_value(
+    &mut self,
+    receiver: Value,
+    callee: Value,
+    arguments: &[Value],
+  ) -> Result<Value, RuntimeErrorKind> {
+    self.invoke(callee, Some(receiver), arguments)
+  }
+
+  fn invoke(&mut self, callee: Value, receiver: Option<Value>, arguments: &[Value]) -> Result<Value, RuntimeErrorKind> {
+    let depth = self.frames.len();
+    let stack_len = self.stack.len();
+    self.stack.push(callee);
+    for argument in arguments {
+      self.stack.push(argument.clone());
     }
-    if left.is_integer() && right.is_integer() {
-      let result = Value::new_integer(left.get_integer() - right.get_integer());
-      self.stack.push(result);
-      return;
+    if let Err(error) = self.run_until_return(receiver, arguments.len(), depth) {
+      self.unwind(depth);
+      self.stack.truncate(stack_len);
+      return Err(error);
     }
-    if left.is_integer() && right.is_float() {
-      let result = Value::new_float(left.get_integer() as f64 - right.get_float());
-      self.stack.push(result);
-      return;
+    self.pop()
+  }
+
+  fn run_until_return(&mut self, receiver: Option<Value>, argc: usize, depth: usize) -> Result<(), RuntimeErrorKind> {
+    match receiver {
+      Some(receiver) => self.call_with_receiver(receiver, argc)?,
+      None => self.call(argc)?,
     }
-    if left.is_float() && right.is_integer() {
-      let result = Value::new_float(left.get_float() - right.get_integer() as f64);
-      self.stack.push(result);
-      return;
+    while self.frames.len() > depth {
+      self.debug_assert_stack_bound();
+      match self.step() {
+        // a function body always ends in RETURN, treat a stray HALT as one
+        Ok(Some(value)) => {
+          self.stack.push(value);
+          self.unwind(depth);
+        }
+        Ok(None) => {}
+        // only a try block inside the callee, the ones around the native calling it catch at its CALL
+        Err(kind) if self.catch(&kind, depth + 1) => {}
+        Err(kind) => return Err(kind),
+      }
     }
-    panic!("Unsupported operation, left: {:?} - right: {:?}", left, right);
+    Ok(())
   }
-  pub fn _addition_operation(&mut self) {
-    let (right, left) = (self.stack.pop().unwrap(), self.stack.pop().unwrap());
-    if left.is_number() && right.is_float() {
-      let result = Value::new_float(left.get_integer() as f64 + right.get_float());
-      self.stack.push(result);
+
+  // pops frames until `depth` are left, restoring the registers of the outermost one popped
+  fn unwind(&mut self, depth: usize) {
+    if self.frames.len() <= depth {
       return;
     }
-    if left.is_float() && right.is_integer() {
-      let result = Value::new_float(left.get_float() + right.get_integer() as f64);
-      self.stack.push(result);
-      return;
+    let caller = self.frames.drain(depth..).next().expect("[Engine] frame to unwind to");
+    self.function = caller.function;
+    self.instruction_pointer = caller.instruction_pointer;
+    self.frame_pointer = caller.frame_pointer;
+    self.this = caller.this;
+    self.drop_handlers_of_returned_frames();
+  }
+
+  pub fn ctx(&mut self) -> &mut Context {
+    self.ctx
+  }
+
+  fn return_operation(&mut self) -> Result<(), RuntimeErrorKind> {
+    let mut result = self.pop()?;
+    let caller = self.frames.pop().ok_or(RuntimeErrorKind::ReturnOutsideFunction)?;
+    // a constructor returning a primitive still evaluates to the object it built
+    let is_object = matches!(
+      result,
+      Value::Object(_) | Value::Array(_) | Value::Function(_) | Value::NativeFunction(_) | Value::RegExp(_)
+    );
+    if caller.is_construct && !is_object {
+      result = self.this.clone();
     }
-    if left.is_integer() && right.is_integer() {
-      let result = Value::new_integer(left.get_integer() + right.get_integer());
-      self.stack.push(result);
-      return;
+    // drops the locals, the arguments and the callee below them
+    self.stack.truncate(self.frame_pointer - 1);
+    self.function = caller.function;
+    self.instruction_pointer = caller.instruction_pointer;
+    self.frame_pointer = caller.frame_pointer;
+    self.this = caller.this;
+    self.drop_handlers_of_returned_frames();
+    self.stack.push(result);
+    Ok(())
+  }
+  fn _jump_operation(&mut self) -> Result<(), RuntimeErrorKind> {
+    let distance = self.read_operand(opcode::OPCODE_JUMP)?;
+    // only loops jump backwards
+    if (distance as u16 as i16) < 0 {
+      self.check_interrupt()?;
+    }
+    self.jump_by(distance);
+    Ok(())
+  }
+  fn _jump_if_false_operation(&mut self) -> Result<(), RuntimeErrorKind> {
+    let distance = self.read_operand(opcode::OPCODE_JUMP_IF_FALSE)?;
+    let condition = self.pop()?;
+    if !condition.is_truthy() {
+      self.jump_by(distance);
     }
+    Ok(())
+  }
+  // jump operands are signed 16 bit distances from the instruction after the jump
+  fn jump_by(&mut self, distance: usize) {
+    let distance = distance as u16 as i16;
+    self.instruction_pointer = self.instruction_pointer.wrapping_add_signed(distance as isize);
+  }
+  // the compiler's max_stack must hold for the frame being executed
+  fn debug_assert_stack_bound(&self) {
+    let chunk = self.function.get_chunk();
+    let bound = self.frame_pointer + self.function.get_arity() + chunk.locals.len() + chunk.max_stack;
+    debug_assert!(
+      self.stack.len() <= bound,
+      "[Engine] stack depth {} exceeds max_stack of '{}' ({}) at {:04X}",
+      self.stack.len(),
+      self.function.get_name(),
+      bound,
+      self.instruction_pointer
+    );
+  }
 
-    if left.is_string() && right.is_string() {
-      let result = Value::new_string(left.get_string() + &right.get_string());
-      self.stack.push(result);
-      return;
+  fn read(&mut self) -> Result<u8, RuntimeErrorKind> {
+    let instruction = *self
+      .function
+      .get_chunk()
+      .code
+      .get(self.instruction_pointer)
+      .ok_or(RuntimeErrorKind::UnexpectedEnd)?;
+    self.instruction_pointer += 1;
+    Ok(instruction)
+  }
+
+  // the little-endian operand that follows `opcode`
+  fn read_operand(&mut self, opcode: u8) -> Result<usize, RuntimeErrorKind> {
+    let width = opcode_operands(opcode);
+    let operand = decode_operand(&self.function.get_chunk().code, self.instruction_pointer, width)
+      .ok_or(RuntimeErrorKind::UnexpectedEnd)?;
+    self.instruction_pointer += width;
+    Ok(operand)
+  }
+
+  fn get_constant(&mut self, opcode: u8) -> Result<Value, RuntimeErrorKind> {
+    let index = self.read_operand(opcode)?;
+    let constant = self.function.get_chunk().constants.get(index);
+    constant.cloned().ok_or(RuntimeErrorKind::InvalidConstant(index))
+  }
+
+  fn pop(&mut self) -> Result<Value, RuntimeErrorKind> {
+    self.stack.pop().map_err(|_| RuntimeErrorKind::StackUnderflow)
+  }
+
+  fn peek(&self, index: usize) -> Result<Value, RuntimeErrorKind> {
+    if index >= self.stack.len() {
+      return Err(RuntimeErrorKind::StackUnderflow);
     }
-    panic!("Unsupported operation, left: {:?} + right: {:?}", left, right);
+    Ok(
+      self
+        .stack
+        .peek(index)
+        .map_err(|_| RuntimeErrorKind::StackUnderflow)?
+        .clone(),
+    )
   }
 
-  pub fn _subtraction_operation(&mut self) {
-    let (right, left) = (self.stack.pop().unwrap(), self.stack.pop().unwrap());
-    if left.is_float() && right.is_float() {
-      let result = Value::new_float(left.get_float() - right.get_float());
-      self.stack.push(result);
-      return;
+  // pops the two operands of a binary instruction, the right one is on top
+  fn pop_operands(&mut self) -> Result<(Value, Value), RuntimeErrorKind> {
+    let right = self.pop()?;
+    let left = self.pop()?;
+    Ok((left, right))
+  }
+
+  // ToNumber on both operands, symbols and bigints can't be mixed into number arithmetic
+  fn numeric_operands(&mut self, verb: &str) -> Result<(f64, f64), RuntimeErrorKind> {
+    let (left, right) = self.pop_operands()?;
+    for value in [&left, &right] {
+      if matches!(value, Value::Symbol(_) | Value::BigInt(_)) {
+        let message = format!("cannot {} {} by {}", verb, left.type_name(), right.type_name());
+        return Err(RuntimeErrorKind::TypeError(message));
+      }
     }
-    if left.is_integer() && right.is_integer() {
-      let result = Value::new_integer(left.get_integer() - right.get_integer());
-      self.stack.push(result);
-      return;
+    Ok((left.to_number(), right.to_number()))
+  }
+
+  fn _eq_operation(&mut self) -> Result<(), RuntimeErrorKind> {
+    let (left, right) = self.pop_operands()?;
+    let value = Value::create_boolean_value(left.strict_equals(&right));
+    self.stack.push(value);
+    Ok(())
+  }
+
+  // `<`, `<=`, `>` and `>=`, each one asks IsLessThan with the operands in the order it needs
+  fn relational_operation(&mut self, instruction: u8) -> Result<(), RuntimeErrorKind> {
+    let (left, right) = self.pop_operands()?;
+    let result = match instruction {
+      opcode::OPCODE_LT => left.less_than(&right) == Some(true),
+      opcode::OPCODE_GT => right.less_than(&left) == Some(true),
+      opcode::OPCODE_LE => right.less_than(&left) == Some(false),
+      _ => left.less_than(&right) == Some(false),
+    };
+    self.stack.push(Value::create_boolean_value(result));
+    Ok(())
+  }
+
+  // CONST then LT, like `i < 10` in a loop condition
+  fn less_than_constant_operation(&mut self) -> Result<(), RuntimeErrorKind> {
+    let constant = self.get_constant(opcode::OPCODE_LT_CONST)?;
+    self.stack.push(constant);
+    self.relational_operation(opcode::OPCODE_LT)
+  }
+
+  // `==`, or `!=` when `negate` is set
+  fn loose_equality_operation(&mut self, negate: bool) -> Result<(), RuntimeErrorKind> {
+    let (left, right) = self.pop_operands()?;
+    let value = Value::create_boolean_value(left.loose_equals(&right) != negate);
+    self.stack.push(value);
+    Ok(())
+  }
+
+  // `+ - * /` between two bigints, true once the result is pushed. false when neither operand is a
+  // bigint and the number arithmetic should run, mixing a bigint with anything else throws like in js
+  fn bigint_operation(&mut self, operation: fn(i128, i128) -> Option<i128>) -> Result<bool, RuntimeErrorKind> {
+    match (self.peek(1)?, self.peek(0)?) {
+      (Value::BigInt(left), Value::BigInt(right)) => {
+        self.pop_operands()?;
+        // i128 stands in for arbitrary precision, going past it is an error instead of wrapping
+        let Some(result) = operation(left.get_value(), right.get_value()) else {
+          return Err(RuntimeErrorKind::RangeError("Maximum BigInt size exceeded".to_string()));
+        };
+        self.stack.push(Value::create_bigint_value(result));
+        Ok(true)
+      }
+      (Value::BigInt(_), _) | (_, Value::BigInt(_)) => Err(RuntimeErrorKind::TypeError(
+        "Cannot mix BigInt and other types, use explicit conversions".to_string(),
+      )),
+      _ => Ok(false),
     }
-    if left.is_integer() && right.is_float() {
-      let result = Value::new_float(left.get_integer() as f64 - right.get_float());
-      self.stack.push(result);
-      return;
+  }
+
+  fn _multplication_operation(&mut self) -> Result<(), RuntimeErrorKind> {
+    if self.bigint_operation(i128::checked_mul)? {
+      return Ok(());
     }
-    if left.is_float() && right.is_integer() {
-      let result = Value::new_float(left.get_float() - right.get_integer() as f64);
-      self.stack.push(result);
-      return;
+    let (left, right) = self.numeric_operands("multiply")?;
+    self.stack.push(Value::create_number_value(left * right));
+    Ok(())
+  }
+  // the operands of the integer opcodes are usually small integers, computed on i64 without the type checks
+  // of the generic operation. anything else (a float, a string, a result a double can't hold exactly, or -0
+  // from a product) takes `fallback`, so the result is always the one the generic opcode gives
+  fn integer_operation(
+    &mut self,
+    operation: fn(i64, i64) -> Option<i64>,
+    fallback: fn(&mut Self) -> Result<(), RuntimeErrorKind>,
+  ) -> Result<(), RuntimeErrorKind> {
+    if self.stack.len() < 2 {
+      return fallback(self);
+    }
+    let operands = self.stack.top(2);
+    let (Some(left), Some(right)) = (small_integer(&operands[0]), small_integer(&operands[1])) else {
+      return fallback(self);
+    };
+    match operation(left, right) {
+      Some(result) if result.abs() <= MAX_SAFE_INTEGER && (result != 0 || (left >= 0 && right >= 0)) => {
+        self.stack.pop_values(2);
+        self.stack.push(Value::create_number_value(result as f64));
+        Ok(())
+      }
+      _ => fallback(self),
     }
-    panic!("Unsupported operation, left: {:?} - right: {:?}", left, right);
   }
 
-  fn _division_operation(&mut self) {
-    let (right, left) = (self.stack.pop().unwrap(), self.stack.pop().unwrap());
-    if left.is_float() && right.is_float() {
-      let result = Value::new_float(left.get_float() / right.get_float());
+  // CONST then ADD_INT, the peephole pass fuses both when the constant is the right operand
+  fn add_constant_operation(&mut self) -> Result<(), RuntimeErrorKind> {
+    let constant = self.get_constant(opcode::OPCODE_ADD_CONST)?;
+    self.stack.push(constant);
+    self.integer_operation(i64::checked_add, Self::_addition_operation)
+  }
+
+  pub fn _addition_operation(&mut self) -> Result<(), RuntimeErrorKind> {
+    // `+` concatenates as soon as one side is a string
+    if matches!(self.peek(1)?, Value::String(_)) || matches!(self.peek(0)?, Value::String(_)) {
+      let (left, right) = self.pop_operands()?;
+      let result = Value::create_string_value(left.to_js_string() + &right.to_js_string());
       self.stack.push(result);
-      return;
+      return Ok(());
     }
-    if left.is_integer() && right.is_integer() {
-      let result = Value::new_integer(left.get_integer() / right.get_integer());
-      self.stack.push(result);
-      return;
+    if self.bigint_operation(i128::checked_add)? {
+      return Ok(());
     }
-    if left.is_integer() && right.is_float() {
-      let result = Value::new_float(left.get_integer() as f64 / right.get_float());
-      self.stack.push(result);
-      return;
+    let (left, right) = self.numeric_operands("add")?;
+    self.stack.push(Value::create_number_value(left + right));
+    Ok(())
+  }
+
+  pub fn _subtraction_operation(&mut self) -> Result<(), RuntimeErrorKind> {
+    if self.bigint_operation(i128::checked_sub)? {
+      return Ok(());
     }
-    if left.is_float() && right.is_integer() {
-      let result = Value::new_float(left.get_float() / right.get_integer() as f64);
-      self.stack.push(result);
-      return;
+    let (left, right) = self.numeric_operands("subtract")?;
+    self.stack.push(Value::create_number_value(left - right));
+    Ok(())
+  }
+
+  fn _division_operation(&mut self) -> Result<(), RuntimeErrorKind> {
+    // bigint division truncates, there's no Infinity to divide by zero into
+    if matches!((self.peek(1)?, self.peek(0)?), (Value::BigInt(_), Value::BigInt(right)) if right.get_value() == 0) {
+      return Err(RuntimeErrorKind::RangeError("Division by zero".to_string()));
+    }
+    if self.bigint_operation(i128::checked_div)? {
+      return Ok(());
     }
-    panic!("Unsupported operation, left: {:?} / right: {:?}", left, right);
+    let (left, right) = self.numeric_operands("divide")?;
+    // x / 0 is Infinity (NaN for 0 / 0), f64 already does that
+    self.stack.push(Value::create_number_value(left / right));
+    Ok(())
   }
+}
 
-  fn binary_operation(&mut self, op: fn(Value, Value) -> Value) {
-    let (right, left) = (self.stack.pop().unwrap(), self.stack.pop().unwrap());
-    let result = op(left, right);
-    self.stack.push(result);
+// 2^53 - 1, past it not every integer is a double
+const MAX_SAFE_INTEGER: i64 = (1 << 53) - 1;
+
+// an integral number the integer opcodes can compute with, -0 isn't one
+fn small_integer(value: &Value) -> Option<i64> {
+  let number = value.as_number()?;
+  let is_integer = number.fract() == 0.0 && number.abs() <= MAX_SAFE_INTEGER as f64;
+  (is_integer && !(number == 0.0 && number.is_sign_negative())).then_some(number as i64)
+}
+
+// executes the instruction whose opcode indexes it, the other dispatch strategy next to the `match` in
+// `Engine::dispatch`. every entry calls the same operation as the matching arm there
+type Handler = fn(&mut Engine, u8) -> StepResult;
+
+static HANDLERS: [Handler; 256] = {
+  let mut table = [unknown_opcode as Handler; 256];
+  table[opcode::OPCODE_CONST as usize] = |vm, instruction| vm.constant_operation(instruction).and(Ok(None));
+  table[opcode::OPCODE_CONST_LONG as usize] = |vm, instruction| vm.constant_operation(instruction).and(Ok(None));
+  table[opcode::OPCODE_ADD as usize] = |vm, _| vm._addition_operation().and(Ok(None));
+  table[opcode::OPCODE_SUB as usize] = |vm, _| vm._subtraction_operation().and(Ok(None));
+  table[opcode::OPCODE_MUL as usize] = |vm, _| vm._multplication_operation().and(Ok(None));
+  table[opcode::OPCODE_DIV as usize] = |vm, _| vm._division_operation().and(Ok(None));
+  table[opcode::OPCODE_ADD_INT as usize] = |vm, _| {
+    vm.integer_operation(i64::checked_add, Engine::_addition_operation)
+      .and(Ok(None))
+  };
+  table[opcode::OPCODE_SUB_INT as usize] = |vm, _| {
+    vm.integer_operation(i64::checked_sub, Engine::_subtraction_operation)
+      .and(Ok(None))
+  };
+  table[opcode::OPCODE_MUL_INT as usize] = |vm, _| {
+    vm.integer_operation(i64::checked_mul, Engine::_multplication_operation)
+      .and(Ok(None))
+  };
+  table[opcode::OPCODE_ADD_CONST as usize] = |vm, _| vm.add_constant_operation().and(Ok(None));
+  table[opcode::OPCODE_EQ as usize] = |vm, _| vm._eq_operation().and(Ok(None));
+  table[opcode::OPCODE_WEAK_EQ as usize] = |vm, _| vm.loose_equality_operation(false).and(Ok(None));
+  table[opcode::OPCODE_NE as usize] = |vm, _| vm.loose_equality_operation(true).and(Ok(None));
+  table[opcode::OPCODE_LT as usize] = |vm, instruction| vm.relational_operation(instruction).and(Ok(None));
+  table[opcode::OPCODE_LE as usize] = |vm, instruction| vm.relational_operation(instruction).and(Ok(None));
+  table[opcode::OPCODE_GT as usize] = |vm, instruction| vm.relational_operation(instruction).and(Ok(None));
+  table[opcode::OPCODE_GE as usize] = |vm, instruction| vm.relational_operation(instruction).and(Ok(None));
+  table[opcode::OPCODE_LT_CONST as usize] = |vm, _| vm.less_than_constant_operation().and(Ok(None));
+  table[opcode::OPCODE_NOT as usize] = |vm, _| vm.not_operation().and(Ok(None));
+  table[opcode::OPCODE_JUMP as usize] = |vm, _| vm._jump_operation().and(Ok(None));
+  table[opcode::OPCODE_JUMP_IF_FALSE as usize] = |vm, _| vm._jump_if_false_operation().and(Ok(None));
+  table[opcode::OPCODE_LOAD_GLOBAL_SCOPE as usize] =
+    |vm, instruction| vm.load_global_scope_operation(instruction).and(Ok(None));
+  table[opcode::OPCODE_LOAD_GLOBAL_SCOPE_LONG as usize] =
+    |vm, instruction| vm.load_global_scope_operation(instruction).and(Ok(None));
+  table[opcode::OPCODE_SET_GLOBAL_SCOPE as usize] =
+    |vm, instruction| vm.set_global_scope_operation(instruction).and(Ok(None));
+  table[opcode::OPCODE_SET_GLOBAL_SCOPE_LONG as usize] =
+    |vm, instruction| vm.set_global_scope_operation(instruction).and(Ok(None));
+  table[opcode::OPCODE_POP as usize] = |vm, _| vm.pop().and(Ok(None));
+  table[opcode::OPCODE_SET_LOCAL as usize] = |vm, _| vm.set_local_operation().and(Ok(None));
+  table[opcode::OPCODE_GET_LOCAL as usize] = |vm, _| vm.get_local_operation().and(Ok(None));
+  table[opcode::OPCODE_SET_LOCAL_POP as usize] = |vm, _| vm.set_local_pop_operation().and(Ok(None));
+  table[opcode::OPCODE_SET_GLOBAL_POP as usize] = |vm, _| vm.set_global_pop_operation().and(Ok(None));
+  table[opcode::OPCODE_LOAD_THIS as usize] = |vm, _| {
+    vm.load_this_operation();
+    Ok(None)
+  };
+  table[opcode::OPCODE_LOAD_ARGUMENT as usize] = |vm, _| vm.load_argument_operation().and(Ok(None));
+  table[opcode::OPCODE_SET_ARGUMENT as usize] = |vm, _| vm.set_argument_operation().and(Ok(None));
+  table[opcode::OPCODE_CALL as usize] = |vm, _| vm.call_operation().and(Ok(None));
+  table[opcode::OPCODE_CALL_METHOD as usize] = |vm, _| vm.call_method_operation().and(Ok(None));
+  table[opcode::OPCODE_INSTANCEOF as usize] = |vm, _| vm.instance_of_operation().and(Ok(None));
+  table[opcode::OPCODE_DUP as usize] = |vm, _| vm.dup_operation().and(Ok(None));
+  table[opcode::OPCODE_SWAP as usize] = |vm, _| vm.swap_operation().and(Ok(None));
+  table[opcode::OPCODE_NEW as usize] = |vm, _| vm.new_operation().and(Ok(None));
+  table[opcode::OPCODE_ITERATE as usize] = |vm, _| vm.iterate_operation().and(Ok(None));
+  table[opcode::OPCODE_ITERATE_NEXT as usize] = |vm, _| vm.iterate_next_operation().and(Ok(None));
+  table[opcode::OPCODE_ENUMERATE as usize] = |vm, _| vm.enumerate_operation().and(Ok(None));
+  table[opcode::OPCODE_NEW_ARRAY as usize] = |vm, _| vm.new_array_operation().and(Ok(None));
+  table[opcode::OPCODE_GET_PROPERTY as usize] = |vm, _| vm.get_property_operation().and(Ok(None));
+  table[opcode::OPCODE_NEW_OBJECT as usize] = |vm, _| {
+    vm.push_allocated(Value::create_object_value());
+    Ok(None)
+  };
+  table[opcode::OPCODE_INIT_PROPERTY as usize] = |vm, _| vm.init_property_operation().and(Ok(None));
+  table[opcode::OPCODE_SET_PROPERTY as usize] = |vm, _| vm.set_property_operation().and(Ok(None));
+  table[opcode::OPCODE_SET_ELEMENT as usize] = |vm, _| vm.set_element_operation().and(Ok(None));
+  table[opcode::OPCODE_GET_ELEMENT as usize] = |vm, _| vm.get_element_operation().and(Ok(None));
+  table[opcode::OPCODE_RETURN as usize] = |vm, _| vm.return_operation().and(Ok(None));
+  table[opcode::OPCODE_THROW as usize] = |vm, _| vm.throw_operation().and(Ok(None));
+  table[opcode::OPCODE_SETUP_TRY as usize] = |vm, _| vm.setup_try_operation().and(Ok(None));
+  table[opcode::OPCODE_POP_TRY as usize] = |vm, _| {
+    vm.handlers.pop();
+    Ok(None)
+  };
+  table[opcode::OPCODE_HALT as usize] = |vm, _| Ok(Some(vm.halt_operation()));
+  table
+};
+
+fn unknown_opcode(_: &mut Engine, _: u8) -> StepResult {
+  Err(RuntimeErrorKind::UnknownOpcode)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::errors::{CompileError, DecodeError};
+  use crate::test_support::{run, runtime_error};
+
+  fn run_chunk(code: Vec<u8>) -> Result<Value, Box<RuntimeError>> {
+    let mut ctx = Context::new();
+    let mut chunk = Chunk::new();
+    chunk.lines = vec![(0, 1)];
+    chunk.code = code;
+    let compiler = CompilerReturn { name: "main".to_string(), chunk };
+    let mut stack = Stack::new(8);
+    let mut vm = Engine::new(&mut ctx, &mut stack, &compiler);
+    vm.run()
+  }
+
+  #[test]
+  fn an_arrow_function_is_called_with_its_arguments() {
+    assert_eq!(
+      run("let add = (a, b) => a + b; add(2, 3)"),
+      Value::create_number_value(5.0)
+    );
+  }
+
+  #[test]
+  fn an_arrow_keeps_the_this_of_where_it_was_created() {
+    let source = "function make() { return () => this.x; } let o = { x: 7, make: make }; let f = o.make(); f()";
+    assert_eq!(run(source), Value::create_number_value(7.0));
+  }
+
+  #[test]
+  fn loose_equality_runs_in_the_vm() {
+    let yes = Value::create_boolean_value(true);
+    let no = Value::create_boolean_value(false);
+    assert_eq!(run("0 == false"), yes);
+    assert_eq!(run(r#""" == 0"#), yes);
+    assert_eq!(run("null == 0"), no);
+    assert_eq!(run(r#"1 == "1""#), yes);
+    assert_eq!(run(r#"1 != "1""#), no);
+    assert_eq!(run("9007199254740993n == 9007199254740992"), no);
+  }
+
+  // runs `source` with every instruction going through `execute`
+  fn run_with(source: &str, execute: fn(&mut Engine, u8) -> StepResult) -> Value {
+    let mut ctx = Context::with_builtins();
+    let compiled = compile_repl(&oxc_allocator::Allocator::default(), &source.to_string(), &mut ctx).unwrap();
+    let mut stack = Stack::new(64);
+    let mut vm = Engine::new(&mut ctx, &mut stack, &compiled);
+    vm.run_instructions(execute).unwrap()
+  }
+
+  #[test]
+  fn the_handler_table_and_the_match_run_a_program_the_same() {
+    let source = r#"
+      function square(n) { return n * n; }
+      let total = 0;
+      for (let n of [1, 2, 3]) { total = total + square(n); }
+      let o = { total: total, name: "sum" };
+      let caught = 0;
+      try { throw 1; } catch (e) { caught = e; }
+      [o.name, o.total, total == "14", caught].join(" ")
+    "#;
+    let table = run_with(source, |vm, instruction| {
+      HANDLERS[instruction as usize](vm, instruction)
+    });
+    let matched = run_with(source, |vm, instruction| vm.dispatch(instruction));
+    assert_eq!(table, matched);
+    assert_eq!(table, Value::create_string_value("sum 14 true 1".to_string()));
+  }
+
+  #[test]
+  fn the_handler_table_and_the_match_know_the_same_opcodes() {
+    for instruction in 0..=u8::MAX {
+      let execute = |execute: fn(&mut Engine, u8) -> StepResult| {
+        let mut ctx = Context::new();
+        let mut chunk = Chunk::new();
+        chunk.write(instruction, 1);
+        let compiler = CompilerReturn { name: "main".to_string(), chunk };
+        let mut stack = Stack::new(8);
+        let mut vm = Engine::new(&mut ctx, &mut stack, &compiler);
+        // past the opcode, like `run` leaves it
+        vm.instruction_pointer = 1;
+        matches!(execute(&mut vm, instruction), Err(RuntimeErrorKind::UnknownOpcode))
+      };
+      assert_eq!(
+        execute(|vm, instruction| HANDLERS[instruction as usize](vm, instruction)),
+        execute(|vm, instruction| vm.dispatch(instruction)),
+        "{:#04X}",
+        instruction
+      );
+    }
+  }
+
+  #[test]
+  fn relational_operators_compare_numbers_and_strings() {
+    let yes = Value::create_boolean_value(true);
+    let no = Value::create_boolean_value(false);
+    assert_eq!(run("let i = 1; i < 2"), yes);
+    assert_eq!(run("let i = 3; i >= 3"), yes);
+    assert_eq!(run("let i = 3; i > 3"), no);
+    assert_eq!(run(r#"let a = "a"; a < "b""#), yes);
+    assert_eq!(run(r#"let a = "10"; a < "9""#), yes);
+    assert_eq!(run("let n = NaN; n <= 1"), no);
+    assert_eq!(run("let n = NaN; n >= 1"), no);
+    assert_eq!(run("let b = 1n; b < 2"), yes);
+  }
+
+  #[test]
+  fn a_while_loop_runs_until_its_test_is_false() {
+    let source = "let i = 0; let total = 0;
+      while (i < 10) { i = i + 1; if (i === 5) continue; if (i === 8) break; total = total + i; }
+      total";
+    assert_eq!(run(source), Value::create_number_value(23.0));
+  }
+
+  #[test]
+  fn an_infinite_loop_runs_out_of_fuel() {
+    let mut ctx = Context::new();
+    let error = Engine::bootstrap_with_fuel(&mut ctx, &"while (true) {}".to_string(), 10_000).unwrap_err();
+    let EngineError::Other(error) = error else {
+      panic!("expected a runtime error")
+    };
+    let error = error.downcast_ref::<RuntimeError>().unwrap();
+    assert!(matches!(error.kind, RuntimeErrorKind::OutOfFuel), "{}", error);
+  }
+
+  #[test]
+  fn a_finished_program_reports_the_fuel_it_left() {
+    let mut ctx = Context::new();
+    let source = "let i = 0; while (i < 10) { i = i + 1; }".to_string();
+    let (_, remaining) = Engine::bootstrap_with_fuel(&mut ctx, &source, 10_000).unwrap();
+    assert!(remaining > 0 && remaining < 10_000, "{remaining}");
+  }
+
+  #[test]
+  fn a_thrown_value_is_bound_by_the_catch_block() {
+    let source = "let r = 0; try { throw 5; } catch (e) { r = e + 1; } r";
+    assert_eq!(run(source), Value::create_number_value(6.0));
+  }
+
+  #[test]
+  fn a_finally_block_runs_when_the_try_block_throws() {
+    let source = r#"let log = "";
+      try { try { throw 1; } finally { log = log + "f"; } } catch (e) { log = log + "c" + e; }
+      log"#;
+    assert_eq!(run(source), Value::create_string_value("fc1".to_string()));
+  }
+
+  #[test]
+  fn a_finally_block_runs_when_the_try_block_finishes() {
+    let source = r#"let log = ""; try { log = log + "t"; } finally { log = log + "f"; } log"#;
+    assert_eq!(run(source), Value::create_string_value("tf".to_string()));
+  }
+
+  #[test]
+  fn a_program_ending_in_a_block_halts_with_undefined() {
+    assert_eq!(
+      run("try { throw 5; } catch (e) { e + 1; }"),
+      Value::create_undefined_value()
+    );
+    assert_eq!(run("{ let x = 1; }"), Value::create_undefined_value());
+  }
+
+  #[test]
+  fn calling_a_number_is_a_type_error() {
+    let error = runtime_error("let x = 1; x();");
+    assert!(matches!(error.kind, RuntimeErrorKind::TypeError(_)), "{}", error);
+  }
+
+  #[test]
+  fn popping_an_empty_stack_is_a_stack_underflow() {
+    let error = run_chunk(vec![opcode::OPCODE_POP, opcode::OPCODE_HALT]).unwrap_err();
+    assert!(matches!(error.kind, RuntimeErrorKind::StackUnderflow));
+    assert_eq!(error.opcode, opcode::OPCODE_POP);
+    assert_eq!(error.offset, 0);
+  }
+
+  #[test]
+  fn an_unassigned_byte_is_an_unknown_opcode() {
+    let error = run_chunk(vec![0xFF]).unwrap_err();
+    assert!(matches!(error.kind, RuntimeErrorKind::UnknownOpcode));
+  }
+
+  #[test]
+  fn a_compile_error_is_returned_not_run() {
+    let mut ctx = Context::new();
+    let error = Engine::bootstrap(&mut ctx, &"x; let x = 1;".to_string(), false).unwrap_err();
+    let EngineError::Other(error) = error else {
+      panic!("expected a compile error")
+    };
+    assert!(error.downcast_ref::<CompileError>().is_some());
+  }
+
+  #[test]
+  fn methods_are_inherited_through_the_prototype_chain() {
+    let source = r#"
+      function Animal() {}
+      function speak() { return "hi"; }
+      Animal.prototype.speak = speak;
+      let a = new Animal();
+      [a.speak(), a instanceof Animal, {} instanceof Animal].join(" ")
+    "#;
+    assert_eq!(run(source).to_js_string(), "hi true false");
+  }
+
+  #[test]
+  fn for_of_visits_arrays_and_strings() {
+    let source = r#"
+      let sum = 0;
+      for (let x of [1, 2, 3, 4]) {
+        if (x == 2) { continue; }
+        if (x == 4) { break; }
+        sum = sum + x;
+      }
+      sum
+    "#;
+    assert_eq!(run(source), Value::create_number_value(4.0));
+    let source = r#"let out = ""; for (let c of "abc") { out = c + out; } out"#;
+    assert_eq!(run(source).to_js_string(), "cba");
+  }
+
+  #[test]
+  fn for_of_over_a_non_iterable_is_a_type_error() {
+    let error = runtime_error("for (let x of 5) {}");
+    assert!(matches!(error.kind, RuntimeErrorKind::TypeError(_)), "{error}");
+  }
+
+  #[test]
+  fn for_in_visits_keys_and_indices() {
+    let source = r#"
+      let keys = [];
+      for (let k in { a: 1, b: 2 }) { keys.push(k); }
+      for (let i in [7, 8]) { keys.push(i); }
+      keys.join(",")
+    "#;
+    assert_eq!(run(source).to_js_string(), "a,b,0,1");
+    let source = "let n = 0; for (let k in null) { n = n + 1; } n";
+    assert_eq!(run(source), Value::create_number_value(0.0));
+  }
+
+  #[test]
+  fn bigints_add_to_bigints_only() {
+    assert_eq!(run("10n + 20n"), Value::create_bigint_value(30));
+    let error = runtime_error("10n + 1");
+    assert!(matches!(error.kind, RuntimeErrorKind::TypeError(_)), "{error}");
+  }
+
+  #[test]
+  fn new_passes_its_arguments_and_returns_a_fresh_object() {
+    let source = "function Thing(a, b) { this.sum = a + b; } let t = new Thing(1, 2);".to_string();
+    let mut ctx = Context::new();
+    let compiled = compile_repl(&oxc_allocator::Allocator::default(), &source, &mut ctx).unwrap();
+    let instructions = Disassembler::new(&compiled.chunk, "main", &ctx).instructions();
+    let new = instructions
+      .iter()
+      .find(|instruction| instruction.opcode == opcode::OPCODE_NEW as usize)
+      .unwrap();
+    assert_eq!(new.operands, vec![2]);
+    let source = r#"
+      function Thing(a, b) { this.sum = a + b; }
+      let t = new Thing(1, 2);
+      let u = new Thing(1, 2);
+      [t.sum, t === u].join(" ")
+    "#;
+    assert_eq!(run(source).to_js_string(), "3 false");
+  }
+
+  #[test]
+  fn a_method_call_binds_this_to_the_receiver() {
+    let source = r#"let o = { x: 4, f() { return this.x; } }; let key = "f"; [o.f(), o[key]()].join(" ")"#;
+    assert_eq!(run(source).to_js_string(), "4 4");
+  }
+
+  #[test]
+  fn a_global_defined_after_a_loop_keeps_its_loads_working() {
+    let source = "let i = 0; while (i < 3) { i = i + 1; } let j = 10; i + j";
+    assert_eq!(run(source), Value::create_number_value(13.0));
+  }
+
+  #[test]
+  fn defining_a_global_invalidates_the_global_load_caches() {
+    let mut ctx = Context::new();
+    let mut run_entry = |source: &str| Engine::bootstrap_repl(&mut ctx, &source.to_string()).unwrap();
+    run_entry(
+      "let step = 2; function total() { let n = 0; let i = 0; while (i < 3) { n = n + step; i = i + 1; } return n; }",
+    );
+    assert_eq!(run_entry("total()"), Value::create_number_value(6.0));
+    // the function's chunk is shared by every call, so is its cache
+    let Some(Value::Function(total)) = ctx.get_global("total").cloned() else {
+      panic!("total is a function");
+    };
+    let step = ctx.get_variable_index("step").unwrap();
+    let loads: Vec<usize> = Disassembler::new(total.get_chunk(), "total", &ctx)
+      .instructions()
+      .iter()
+      .filter(|instruction| instruction.opcode == opcode::OPCODE_LOAD_GLOBAL_SCOPE as usize)
+      .filter(|instruction| instruction.operands == vec![step])
+      .map(|instruction| instruction.offset)
+      .collect();
+    assert_eq!(loads.len(), 1);
+    let cache = &total.get_chunk().global_cache;
+    assert_eq!(cache.get(loads[0], ctx.generation()), Some(step));
+
+    // a global defined after the loop ran starts a new generation, the entry isn't trusted anymore
+    let generation = ctx.generation();
+    Engine::bootstrap_repl(&mut ctx, &"let later = 1;".to_string()).unwrap();
+    assert!(ctx.generation() > generation);
+    assert_eq!(cache.get(loads[0], ctx.generation()), None);
+    assert_eq!(
+      Engine::bootstrap_repl(&mut ctx, &"total()".to_string()).unwrap(),
+      Value::create_number_value(6.0)
+    );
+    assert_eq!(cache.get(loads[0], ctx.generation()), Some(step));
+  }
+
+  #[test]
+  fn bytecode_for_other_globals_is_an_error() {
+    let mut chunk = Chunk::new();
+    chunk.code = vec![opcode::OPCODE_HALT];
+    chunk.lines = vec![(0, 1)];
+    chunk.globals = vec!["stale".to_string()];
+    let mut ctx = Context::new();
+    let error = match Engine::bootstrap_chunk(&mut ctx, chunk, false).unwrap_err() {
+      EngineError::Other(error) => *error.downcast::<DecodeError>().unwrap(),
+      error => panic!("expected a decode error, got {}", error),
+    };
+    assert_eq!(
+      error,
+      DecodeError::GlobalMismatch { index: 0, expected: "stale".to_string(), found: "undefined".to_string() }
+    );
   }
 }
@@ -0,0 +1,71 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+// asks a running engine to stop, from any thread: a Ctrl-C handler, a host-side timeout. the engine checks
+// it on backward jumps and calls, so every loop and every recursion reaches a check point, and stops with
+// Interrupted there. clones share the same flag and outlive the engine
+#[derive(Debug, Clone, Default)]
+pub struct InterruptHandle {
+  requested: Arc<AtomicBool>,
+}
+
+impl InterruptHandle {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn interrupt(&self) {
+    self.requested.store(true, Ordering::Relaxed);
+  }
+
+  pub fn is_interrupted(&self) -> bool {
+    self.requested.load(Ordering::Relaxed)
+  }
+
+  // whether an interrupt is pending, clearing it so the next run with the same handle starts fresh
+  pub fn take(&self) -> bool {
+    self.is_interrupted() && self.requested.swap(false, Ordering::Relaxed)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::thread;
+  use std::time::{Duration, Instant};
+
+  use oxc_allocator::Allocator;
+
+  use super::InterruptHandle;
+  use crate::compiler::compile;
+  use crate::context::Context;
+  use crate::errors::RuntimeErrorKind;
+  use crate::stack::Stack;
+  use crate::vm::core::Engine;
+
+  #[test]
+  fn another_thread_stops_an_infinite_loop() {
+    let mut ctx = Context::new();
+    let compiled = compile(&Allocator::default(), &"while (true) {}".to_string(), &mut ctx).unwrap();
+    let mut stack = Stack::new(8);
+    // the fuel only keeps a broken check point from hanging the test run
+    let mut vm = Engine::new(&mut ctx, &mut stack, &compiled).with_fuel(1_000_000_000);
+    let handle = vm.interrupt_handle();
+    let started = Instant::now();
+    let interrupter = thread::spawn(move || {
+      thread::sleep(Duration::from_millis(50));
+      handle.interrupt();
+    });
+    let error = vm.run().unwrap_err();
+    interrupter.join().unwrap();
+    assert!(matches!(error.kind, RuntimeErrorKind::Interrupted), "{}", error);
+    assert!(started.elapsed() < Duration::from_secs(5));
+  }
+
+  #[test]
+  fn take_clears_the_request() {
+    let handle = InterruptHandle::new();
+    handle.clone().interrupt();
+    assert!(handle.take());
+    assert!(!handle.take());
+  }
+}